@@ -0,0 +1,24 @@
+#!/usr/bin/env bash
+
+set -e
+
+# Builds bonsol-prover-worker against one risc0 version, 1.0.1 for V1_0_1 or 1.2.1 for V1_2_1.
+# The worker is not part of the bonsol workspace, its lockfile is generated here with the risc0 crates pinned.
+# Extra arguments are passed to cargo build, e.g. --features cuda
+
+if [[ -z "$1" || "$1" == "--help" ]]; then
+    echo "Usage: $0 <risc0 version> [cargo build args]"
+    exit 1
+fi
+RISC0_VERSION="$1"
+shift
+
+MANIFEST="$(cd "$(dirname "$0")/.." && pwd)/prover-worker/Cargo.toml"
+
+cargo generate-lockfile --manifest-path "$MANIFEST"
+cargo update --manifest-path "$MANIFEST" -p risc0-zkvm --precise "$RISC0_VERSION" --recursive
+for crate in risc0-zkp risc0-groth16; do
+    cargo update --manifest-path "$MANIFEST" -p "$crate" --precise "$RISC0_VERSION"
+done
+cargo build --release --manifest-path "$MANIFEST" "$@"
+echo "Built $(dirname "$MANIFEST")/target/release/bonsol-prover-worker against risc0 $RISC0_VERSION"
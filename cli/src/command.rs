@@ -92,9 +92,25 @@ pub struct S3UploadArgs {
 
 #[derive(Debug, Clone, Args)]
 pub struct UrlUploadArgs {
-    #[arg(help = "Specify a URL endpoint to deploy to", long, required = true)]
+    #[arg(
+        help = "Specify a URL endpoint to deploy to, ipfs://<cid> and ar://<txid> uris are supported",
+        long,
+        required = true
+    )]
     pub url: String,
 
+    #[arg(
+        help = "Specify an IPFS gateway to fetch ipfs:// uris through, repeat to try several in order",
+        long = "ipfs-gateway"
+    )]
+    pub ipfs_gateways: Vec<String>,
+
+    #[arg(
+        help = "Specify an Arweave gateway to fetch ar:// uris through, repeat to try several in order",
+        long = "arweave-gateway"
+    )]
+    pub arweave_gateways: Vec<String>,
+
     #[command(flatten)]
     pub shared_args: SharedDeployArgs,
 }
@@ -5,6 +5,7 @@ use std::str::FromStr;
 
 use anyhow::{Context, Result};
 use bonsol_prover::input_resolver::{ProgramInput, ResolvedInput};
+use bonsol_sdk::accounts_input::{AccountSlice, AccountsInput};
 use bonsol_sdk::instructions::CallbackConfig;
 use bonsol_sdk::{InputT, InputType, ProgramInputType};
 use clap::Args;
@@ -14,6 +15,7 @@ use serde::{Deserialize, Serialize};
 use solana_rpc_client::nonblocking::rpc_client;
 use solana_sdk::instruction::AccountMeta;
 use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
 
 use crate::error::{BonsolCliError, ParseConfigError};
 
@@ -67,7 +69,11 @@ impl ToString for CliInputType {
         match self.0 {
             InputType::PublicData => "PublicData".to_string(),
             InputType::PublicAccountData => "PublicAccountData".to_string(),
+            InputType::PublicAccounts => "PublicAccounts".to_string(),
+            InputType::PublicTransaction => "PublicTransaction".to_string(),
+            InputType::PublicExecutionProof => "PublicExecutionProof".to_string(),
             InputType::PublicUrl => "PublicUrl".to_string(),
+            InputType::PublicUrlHashed => "PublicUrlHashed".to_string(),
             InputType::Private => "Private".to_string(),
             InputType::PublicProof => "PublicProof".to_string(),
             InputType::PrivateLocal => "PrivateUrl".to_string(),
@@ -83,7 +89,11 @@ impl FromStr for CliInputType {
         match s {
             "PublicData" => Ok(CliInputType(InputType::PublicData)),
             "PublicAccountData" => Ok(CliInputType(InputType::PublicAccountData)),
+            "PublicAccounts" => Ok(CliInputType(InputType::PublicAccounts)),
+            "PublicTransaction" => Ok(CliInputType(InputType::PublicTransaction)),
+            "PublicExecutionProof" => Ok(CliInputType(InputType::PublicExecutionProof)),
             "PublicUrl" => Ok(CliInputType(InputType::PublicUrl)),
+            "PublicUrlHashed" => Ok(CliInputType(InputType::PublicUrlHashed)),
             "Private" => Ok(CliInputType(InputType::Private)),
             "PublicProof" => Ok(CliInputType(InputType::PublicProof)),
             "PrivateUrl" => Ok(CliInputType(InputType::PrivateLocal)),
@@ -92,6 +102,52 @@ impl FromStr for CliInputType {
     }
 }
 
+/// Json data of a `PublicAccounts` cli input
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CliAccountsInput {
+    #[serde(default)]
+    pub min_context_slot: u64,
+    pub accounts: Vec<CliAccountSlice>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CliAccountSlice {
+    pub pubkey: String,
+    pub owner: Option<String>,
+    #[serde(default)]
+    pub offset: u32,
+    #[serde(default)]
+    pub length: u32,
+}
+
+impl TryFrom<CliAccountsInput> for AccountsInput {
+    type Error = anyhow::Error;
+
+    fn try_from(input: CliAccountsInput) -> Result<Self> {
+        let accounts = input
+            .accounts
+            .into_iter()
+            .map(|a| {
+                Ok(AccountSlice {
+                    pubkey: Pubkey::from_str(&a.pubkey)?.to_bytes(),
+                    owner: a
+                        .owner
+                        .map(|o| Pubkey::from_str(&o).map(|o| o.to_bytes()))
+                        .transpose()?,
+                    offset: a.offset,
+                    length: a.length,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(AccountsInput {
+            min_context_slot: input.min_context_slot,
+            accounts,
+        })
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ExecutionRequestFile {
@@ -119,6 +175,8 @@ pub struct CliCallbackConfig {
     pub program_id: Option<Pubkey>,
     pub instruction_prefix: Option<Vec<u8>>,
     pub extra_accounts: Option<Vec<CliAccountMeta>>,
+    #[serde(default, with = "bonsol_sdk::instructions::serde_helpers::optpubkey")]
+    pub lookup_table: Option<Pubkey>,
 }
 
 impl From<CliCallbackConfig> for CallbackConfig {
@@ -130,6 +188,7 @@ impl From<CliCallbackConfig> for CallbackConfig {
                 .extra_accounts
                 .map(|v| v.into_iter().map(|a| a.into()).collect())
                 .unwrap_or_default(),
+            lookup_table: val.lookup_table,
         }
     }
 }
@@ -295,12 +354,77 @@ pub fn execute_transform_cli_inputs(inputs: Vec<CliInput>) -> Result<Vec<InputT>
                 }
                 res.push(InputT::public(input.data.into_bytes()));
             }
+            InputType::PublicAccounts => {
+                let accounts: CliAccountsInput = serde_json::from_str(&input.data)
+                    .map_err(|e| anyhow::anyhow!("Invalid PublicAccounts input: {:?}", e))?;
+                res.push(InputT::public_accounts(&accounts.try_into()?));
+            }
+            InputType::PublicTransaction => {
+                let signature = Signature::from_str(&input.data)
+                    .map_err(|_| anyhow::anyhow!("Invalid transaction signature"))?;
+                res.push(InputT::public_transaction(signature.as_ref().try_into()?));
+            }
+            InputType::PublicProof => {
+                // <url> or <image id>@<url> to only accept receipts of that image
+                match input.data.split_once('@').filter(|(id, _)| id.len() == 64) {
+                    Some((image_id, url)) => {
+                        let image_id: [u8; 32] = hex::decode(image_id)?
+                            .try_into()
+                            .map_err(|_| anyhow::anyhow!("Invalid image id"))?;
+                        res.push(InputT::public_proof_with_image_id(url, &image_id));
+                    }
+                    None => res.push(InputT::public_proof(input.data.into_bytes())),
+                }
+            }
+            InputType::PublicExecutionProof => {
+                // <requester>/<execution id> or <image id>@<requester>/<execution id> to only accept
+                // receipts of that image
+                let (image_id, execution_ref) = match input.data.split_once('@') {
+                    Some((image_id, execution_ref)) => {
+                        let image_id: [u8; 32] = hex::decode(image_id)?
+                            .try_into()
+                            .map_err(|_| anyhow::anyhow!("Invalid image id"))?;
+                        (Some(image_id), execution_ref)
+                    }
+                    None => (None, input.data.as_str()),
+                };
+                let (requester, execution_id) = execution_ref
+                    .split_once('/')
+                    .ok_or(anyhow::anyhow!("Invalid execution reference"))?;
+                let requester = Pubkey::from_str(requester)?.to_bytes();
+                res.push(match image_id {
+                    Some(image_id) => {
+                        InputT::execution_proof_with_image_id(&requester, execution_id, &image_id)
+                    }
+                    None => InputT::execution_proof(&requester, execution_id),
+                });
+            }
             _ => res.push(InputT::new(input_type, Some(input.data.into_bytes()))),
         }
     }
     Ok(res)
 }
 
+/// Pins every `PublicUrlHashed` input to the current body of its url
+/// The cli input data is the plain url, the hash is computed by downloading it
+pub async fn execute_pin_url_inputs(inputs: Vec<InputT>) -> Result<Vec<InputT>> {
+    let mut res = Vec::with_capacity(inputs.len());
+    for input in inputs.into_iter() {
+        if input.input_type != InputType::PublicUrlHashed {
+            res.push(input);
+            continue;
+        }
+        let url = input
+            .data
+            .map(String::from_utf8)
+            .transpose()?
+            .ok_or(anyhow::anyhow!("PublicUrlHashed input requires a url"))?;
+        let data = bonsol_sdk::url_with_hash_input(&url).await?;
+        res.push(InputT::new(InputType::PublicUrlHashed, Some(data)));
+    }
+    Ok(res)
+}
+
 fn is_valid_hex(s: &str) -> (bool, Vec<u8>) {
     if s.len() % 4 != 0 {
         return (false, vec![]);
@@ -442,6 +566,7 @@ pub fn rand_id(chars: usize) -> String {
 #[cfg(test)]
 mod test {
     use super::*;
+    use bonsol_interface::bonsol_schema::decode_execution_ref;
 
     #[test]
     fn test_proof_parse_stdin() {
@@ -500,6 +625,83 @@ mod test {
         assert_eq!(num.unwrap(), NumberType::Integer(-2000));
     }
 
+    #[test]
+    fn test_execute_transform_cli_accounts_input() {
+        let pubkey = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let input = CliInput {
+            input_type: "PublicAccounts".to_string(),
+            data: format!(
+                r#"{{"minContextSlot":7,"accounts":[{{"pubkey":"{}","owner":"{}","offset":8,"length":32}}]}}"#,
+                pubkey, owner
+            ),
+        };
+        let parsed_inputs = execute_transform_cli_inputs(vec![input]).unwrap();
+        assert_eq!(
+            parsed_inputs,
+            vec![InputT::public_accounts(&AccountsInput {
+                min_context_slot: 7,
+                accounts: vec![AccountSlice {
+                    pubkey: pubkey.to_bytes(),
+                    owner: Some(owner.to_bytes()),
+                    offset: 8,
+                    length: 32,
+                }],
+            })]
+        );
+    }
+
+    #[test]
+    fn test_execute_transform_cli_proof_input() {
+        let image_id = [7u8; 32];
+        let inputs = vec![
+            CliInput {
+                input_type: "PublicProof".to_string(),
+                data: "https://proofs.example/receipt".to_string(),
+            },
+            CliInput {
+                input_type: "PublicProof".to_string(),
+                data: format!("{}@https://proofs.example/receipt", hex::encode(image_id)),
+            },
+        ];
+        let parsed_inputs = execute_transform_cli_inputs(inputs).unwrap();
+        assert_eq!(
+            parsed_inputs,
+            vec![
+                InputT::public_proof(b"https://proofs.example/receipt".to_vec()),
+                InputT::public_proof_with_image_id("https://proofs.example/receipt", &image_id),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_execute_transform_cli_execution_proof_input() {
+        let image_id = [7u8; 32];
+        let requester = Pubkey::new_unique();
+        let inputs = vec![
+            CliInput {
+                input_type: "PublicExecutionProof".to_string(),
+                data: format!("{}/exec", requester),
+            },
+            CliInput {
+                input_type: "PublicExecutionProof".to_string(),
+                data: format!("{}@{}/exec", hex::encode(image_id), requester),
+            },
+        ];
+        let parsed_inputs = execute_transform_cli_inputs(inputs).unwrap();
+        let decoded = parsed_inputs
+            .iter()
+            .map(|input| decode_execution_ref(input.data.as_ref().unwrap()).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            decoded,
+            vec![
+                (&requester.to_bytes(), None, "exec"),
+                (&requester.to_bytes(), Some(&image_id), "exec"),
+            ]
+        );
+    }
+
     #[test]
     fn test_execute_transform_cli_inputs() {
         let input = CliInput {
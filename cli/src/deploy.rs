@@ -1,11 +1,15 @@
 use std::fs::{self, File};
 use std::path::Path;
+use std::time::Duration;
 
 use anyhow::Result;
+use bonsol_prover::content::{Gateways, DEFAULT_ARWEAVE_GATEWAYS, DEFAULT_IPFS_GATEWAYS};
+use bonsol_prover::fetch::{RetryPolicy, UrlFetcher};
 use bonsol_sdk::{BonsolClient, ProgramInputType};
 use indicatif::ProgressBar;
 use object_store::aws::AmazonS3Builder;
 use object_store::ObjectStore;
+use reqwest::Url;
 use solana_rpc_client::nonblocking::rpc_client::RpcClient;
 use solana_sdk::commitment_config::CommitmentConfig;
 use solana_sdk::signature::Keypair;
@@ -15,6 +19,8 @@ use crate::command::{DeployArgs, S3UploadArgs, SharedDeployArgs};
 use crate::common::ZkProgramManifest;
 use crate::error::{BonsolCliError, S3ClientError, ZkManifestError};
 
+const DOWNLOAD_TIMEOUT_SECS: u64 = 120;
+
 pub async fn deploy(rpc_url: String, signer: Keypair, deploy_args: DeployArgs) -> Result<()> {
     let bar = ProgressBar::new_spinner();
     let rpc_client = RpcClient::new_with_commitment(rpc_url.clone(), CommitmentConfig::confirmed());
@@ -100,8 +106,25 @@ pub async fn deploy(rpc_url: String, signer: Keypair, deploy_args: DeployArgs) -
             url
         }
         DeployArgs::Url(url_upload) => {
-            let req = reqwest::get(&url_upload.url).await?;
-            let bytes = req.bytes().await?;
+            let or_default = |gateways: Vec<String>, default: &[&str]| {
+                if gateways.is_empty() {
+                    default.iter().map(|g| g.to_string()).collect()
+                } else {
+                    gateways
+                }
+            };
+            let gateways = Gateways::new(
+                &or_default(url_upload.ipfs_gateways, DEFAULT_IPFS_GATEWAYS),
+                &or_default(url_upload.arweave_gateways, DEFAULT_ARWEAVE_GATEWAYS),
+            )?;
+            let fetcher = UrlFetcher::new(reqwest::Client::new(), gateways, RetryPolicy::default());
+            let bytes = fetcher
+                .fetch(
+                    &Url::parse(&url_upload.url)?,
+                    loaded_binary.len(),
+                    Duration::from_secs(DOWNLOAD_TIMEOUT_SECS),
+                )
+                .await?;
             if bytes != loaded_binary {
                 return Err(BonsolCliError::OriginBinaryMismatch {
                     url: url_upload.url,
@@ -127,7 +127,8 @@ pub async fn execute(
         };
 
     let signer = keypair.pubkey();
-    let transformed_inputs = execute_transform_cli_inputs(inputs)?;
+    indicator.set_message("Pinning url inputs");
+    let transformed_inputs = execute_pin_url_inputs(execute_transform_cli_inputs(inputs)?).await?;
     let verify_input_hash = execution_request_file
         .execution_config
         .verify_input_hash
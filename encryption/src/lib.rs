@@ -0,0 +1,88 @@
+use anyhow::Result;
+use crypto_box::{aead::OsRng, PublicKey};
+pub use crypto_box::{SecretKey, KEY_SIZE};
+use solana_sdk::hash::hashv;
+use solana_sdk::signer::Signer;
+
+/// Message signed by a node identity to derive its input encryption key
+/// Ed25519 signatures are deterministic so any signer, including remote ones, always derives the same key
+pub const ENCRYPTION_KEY_DERIVATION_MESSAGE: &[u8] = b"bonsol-private-input-encryption-v1";
+
+/// Content type private input servers use when the response body is sealed to the claimer's encryption key
+pub const SEALED_INPUT_CONTENT_TYPE: &str = "application/x-bonsol-sealed";
+
+/// Whether a response content type marks the body as sealed, parameters and case are ignored
+pub fn is_sealed_content_type(content_type: &str) -> bool {
+    content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .eq_ignore_ascii_case(SEALED_INPUT_CONTENT_TYPE)
+}
+
+/// Derives the X25519 secret key a node uses to open encrypted private inputs from its Solana identity
+pub fn derive_encryption_secret(signer: &(dyn Signer + Send + Sync)) -> SecretKey {
+    let sig = signer.sign_message(ENCRYPTION_KEY_DERIVATION_MESSAGE);
+    SecretKey::from_bytes(hashv(&[sig.as_ref()]).to_bytes())
+}
+
+/// The X25519 public key a node publishes in its claims and private input requests
+pub fn encryption_public_key(signer: &(dyn Signer + Send + Sync)) -> [u8; KEY_SIZE] {
+    derive_encryption_secret(signer).public_key().to_bytes()
+}
+
+/// Seals an input to a node's published encryption key, used by private input servers
+pub fn seal_input(encryption_key: &[u8; KEY_SIZE], data: &[u8]) -> Result<Vec<u8>> {
+    PublicKey::from_bytes(*encryption_key)
+        .seal(&mut OsRng, data)
+        .map_err(|_| anyhow::anyhow!("Failed to seal input"))
+}
+
+/// Opens an input sealed to this node's encryption key
+pub fn open_sealed_input(secret: &SecretKey, data: &[u8]) -> Result<Vec<u8>> {
+    secret
+        .unseal(data)
+        .map_err(|_| anyhow::anyhow!("Failed to open sealed input"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use solana_sdk::signature::Keypair;
+
+    #[test]
+    fn test_derived_key_is_stable() {
+        let kp = Keypair::new();
+        assert_eq!(encryption_public_key(&kp), encryption_public_key(&kp));
+        assert_ne!(
+            encryption_public_key(&kp),
+            encryption_public_key(&Keypair::new())
+        );
+    }
+
+    #[test]
+    fn test_sealed_content_type() {
+        assert!(is_sealed_content_type("application/x-bonsol-sealed"));
+        assert!(is_sealed_content_type(
+            "application/x-bonsol-sealed; charset=binary"
+        ));
+        assert!(is_sealed_content_type(" Application/X-Bonsol-Sealed ;q=1"));
+        assert!(!is_sealed_content_type("application/octet-stream"));
+        assert!(!is_sealed_content_type("application/x-bonsol-sealed-v2"));
+    }
+
+    #[test]
+    fn test_seal_and_open() {
+        let kp = Keypair::new();
+        let sealed = seal_input(&encryption_public_key(&kp), b"secret input").unwrap();
+        let secret = derive_encryption_secret(&kp);
+        assert_eq!(
+            open_sealed_input(&secret, &sealed).unwrap(),
+            b"secret input"
+        );
+
+        let other = derive_encryption_secret(&Keypair::new());
+        assert!(open_sealed_input(&other, &sealed).is_err());
+    }
+}
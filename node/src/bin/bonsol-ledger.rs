@@ -0,0 +1,68 @@
+use {
+    anyhow::Result,
+    bonsol_node::{
+        config,
+        ledger::{unix_time, Ledger, ProfitSummary, SummaryGroup},
+    },
+};
+
+const DEFAULT_WINDOW_HOURS: u64 = 24;
+
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 3 || args[1] != "-f" {
+        eprintln!("Usage: bonsol-ledger -f <config_file> [window_hours]");
+        return Ok(());
+    }
+    let config = config::load_config(&args[2]);
+    let Some(ledger_config) = config.ledger.as_ref() else {
+        eprintln!("No ledger configured in {}", args[2]);
+        return Ok(());
+    };
+    let hours = match args.get(3) {
+        Some(hours) => hours.parse::<u64>()?,
+        None => DEFAULT_WINDOW_HOURS,
+    };
+    let ledger = Ledger::from_config(Some(ledger_config))?;
+    let until = unix_time() + 1;
+    let since = until.saturating_sub(hours * 3600);
+    println!("Profit over the last {} hours, in lamports", hours);
+    print_summary("image", &ledger.summary(SummaryGroup::Image, since, until)?);
+    print_summary(
+        "requester",
+        &ledger.summary(SummaryGroup::Requester, since, until)?,
+    );
+    Ok(())
+}
+
+fn print_summary(group: &str, rows: &[ProfitSummary]) {
+    println!();
+    println!(
+        "{:<64} {:>6} {:>9} {:>14} {:>14} {:>12} {:>14} {:>16} {:>12} {:>14}",
+        group,
+        "claims",
+        "completed",
+        "earned",
+        "stake",
+        "fees",
+        "profit",
+        "cycles",
+        "proving_ms",
+        "compression_ms"
+    );
+    for row in rows {
+        println!(
+            "{:<64} {:>6} {:>9} {:>14} {:>14} {:>12} {:>14} {:>16} {:>12} {:>14}",
+            row.key,
+            row.executions,
+            row.completed,
+            row.earned,
+            row.stake,
+            row.fees,
+            row.profit,
+            row.cycles,
+            row.proving_ms,
+            row.compression_ms
+        );
+    }
+}
@@ -0,0 +1,45 @@
+use {
+    anyhow::Result,
+    bonsol_node::segment_worker::{serve, DEFAULT_LISTEN_ADDRESS},
+    rlimit::Resource,
+    std::net::TcpListener,
+    tracing::{error, info},
+    tracing_subscriber,
+};
+
+/// Environment variable holding the token nodes must send, it is kept out of the process arguments
+const TOKEN_ENV: &str = "BONSOL_SEGMENT_WORKER_TOKEN";
+
+fn main() -> Result<()> {
+    // proving needs the same stack as the node
+    match rlimit::setrlimit(Resource::STACK, u64::MAX, u64::MAX) {
+        Ok(_) => {}
+        Err(e) => error!("Error setting rlimit: {}", e),
+    }
+    tracing_subscriber::fmt()
+        .json()
+        .with_timer(tracing_subscriber::fmt::time::UtcTime::rfc_3339())
+        .init();
+    let args: Vec<String> = std::env::args().collect();
+    let listen_address = match args.get(1..) {
+        Some([]) => DEFAULT_LISTEN_ADDRESS,
+        Some([flag, address]) if flag == "-l" => address.as_str(),
+        _ => {
+            error!("Usage: bonsol-segment-worker [-l <listen address>]");
+            return Ok(());
+        }
+    };
+    let token = std::env::var(TOKEN_ENV).ok().filter(|t| !t.is_empty());
+    let listener = TcpListener::bind(listen_address)?;
+    let local_addr = listener.local_addr()?;
+    // segments carry private inputs, only local nodes may send them without a token
+    if token.is_none() && !local_addr.ip().is_loopback() {
+        error!(
+            "Set {} to listen on {}, workers beyond localhost require a token",
+            TOKEN_ENV, local_addr
+        );
+        return Ok(());
+    }
+    info!("Segment worker listening on {}", local_addr);
+    serve(listener, token)
+}
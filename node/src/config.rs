@@ -1,10 +1,20 @@
 use {
+    crate::{
+        ledger::LedgerConfig, prover_worker::ProverWorkerConfig,
+        segment_worker::SegmentWorkersConfig, transaction_sender::lookup_tables::LookupTableConfig,
+    },
+    bonsol_prover::{
+        content::{Gateways, DEFAULT_ARWEAVE_GATEWAYS, DEFAULT_IPFS_GATEWAYS},
+        fetch::{RetryPolicy, UrlFetcher},
+        receipt_store::ReceiptStoreConfig,
+        url_guard::{UrlGuard, UrlGuardConfig},
+    },
     figment::{
         providers::{Format, Toml},
         Figment,
     },
     serde::{Deserialize, Serialize},
-    std::path::Path,
+    std::{path::Path, sync::Arc},
 };
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -41,6 +51,88 @@ pub enum MissingImageStrategy {
     Fail,
 }
 
+/// Worker counts of the claim pipeline stages, proving and compression compete for the same machine so they
+/// default to one job at a time while the io bound stages run several
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct PipelineConfig {
+    /// Private input downloads
+    pub input_workers: usize,
+    pub proving_workers: usize,
+    /// Groth16 compression with `stark_verify` and `rapidsnark`
+    pub compression_workers: usize,
+    pub submission_workers: usize,
+}
+
+impl Default for PipelineConfig {
+    fn default() -> Self {
+        PipelineConfig {
+            input_workers: 8,
+            proving_workers: 1,
+            compression_workers: 1,
+            submission_workers: 8,
+        }
+    }
+}
+
+/// Requests that arrive while the node is at `maximum_concurrent_proofs`, or while their image is loading under
+/// `DownloadAndMiss`, wait here until a slot frees up or the image is loaded
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct DeferredClaimsConfig {
+    /// Requests beyond this are dropped, 0 disables deferring
+    pub max_deferred: usize,
+    /// Deferred requests closer than this to their expiry are dropped, a late claim risks the stake
+    pub min_remaining_blocks: u64,
+}
+
+impl Default for DeferredClaimsConfig {
+    fn default() -> Self {
+        DeferredClaimsConfig {
+            max_deferred: 1000,
+            min_remaining_blocks: 150,
+        }
+    }
+}
+
+/// Lamports kept aside for fees, the bonsol program needs the claimer to hold the tip and moves half of it
+/// into the claim account as stake
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct WalletConfig {
+    /// Claiming pauses while the balance is below this, and claims that would take it below are refused
+    pub reserve_lamports: u64,
+    pub refresh_secs: u64,
+    pub top_up: Option<TopUpConfig>,
+}
+
+impl Default for WalletConfig {
+    fn default() -> Self {
+        WalletConfig {
+            reserve_lamports: 50_000_000,
+            refresh_secs: 10,
+            top_up: None,
+        }
+    }
+}
+
+/// Funds the node from another local keypair when its balance runs low
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct TopUpConfig {
+    pub funding_signer: SignerConfig,
+    pub below_lamports: u64,
+    pub amount_lamports: u64,
+}
+
+/// Proves every eligible request without claiming it or submitting anything, to measure a node against live
+/// traffic without risking stake
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(default)]
+pub struct ShadowConfig {
+    /// Also compress proofs to Groth16 and run the on-chain verification on them
+    pub compress: bool,
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct ProverNodeConfig {
     pub env: Option<String>,
@@ -60,6 +152,9 @@ pub struct ProverNodeConfig {
     pub input_download_timeout_secs: u32,
     #[serde(default = "default_maximum_concurrent_proofs")]
     pub maximum_concurrent_proofs: u32,
+    /// Claim executions again when another node claimed them and missed its block commitment
+    #[serde(default = "default_reclaim_missed_commitments")]
+    pub reclaim_missed_commitments: bool,
     #[serde(default = "default_ingester_config")]
     pub ingester_config: IngesterConfig,
     #[serde(default = "default_transaction_sender_config")]
@@ -72,6 +167,37 @@ pub struct ProverNodeConfig {
     pub metrics_config: MetricsConfig,
     #[serde(default)]
     pub missing_image_strategy: MissingImageStrategy,
+    #[serde(default = "default_ipfs_gateways")]
+    pub ipfs_gateways: Vec<String>,
+    #[serde(default = "default_arweave_gateways")]
+    pub arweave_gateways: Vec<String>,
+    #[serde(default)]
+    pub download_retry_policy: RetryPolicy,
+    #[serde(default)]
+    pub url_guard: UrlGuardConfig,
+    /// Where proven receipts are published for chained executions, unset disables execution proof inputs
+    #[serde(default)]
+    pub receipt_store: Option<ReceiptStoreConfig>,
+    #[serde(default)]
+    pub pipeline: PipelineConfig,
+    #[serde(default)]
+    pub deferred_claims: DeferredClaimsConfig,
+    #[serde(default)]
+    pub segment_workers: SegmentWorkersConfig,
+    /// Workers proving the prover versions the node is not built with
+    #[serde(default)]
+    pub prover_workers: Vec<ProverWorkerConfig>,
+    /// Address lookup tables for callbacks with many accounts
+    #[serde(default)]
+    pub lookup_tables: LookupTableConfig,
+    #[serde(default)]
+    pub wallet: WalletConfig,
+    /// Earnings ledger of claimed executions, unset disables it
+    #[serde(default)]
+    pub ledger: Option<LedgerConfig>,
+    /// Shadow mode, unset claims and submits as usual
+    #[serde(default)]
+    pub shadow: Option<ShadowConfig>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -126,6 +252,10 @@ const fn default_maximum_concurrent_proofs() -> u32 {
     100
 }
 
+const fn default_reclaim_missed_commitments() -> bool {
+    true
+}
+
 fn default_ingester_config() -> IngesterConfig {
     IngesterConfig::RpcBlockSubscription {
         wss_rpc_url: "ws://localhost:8900".to_string(),
@@ -138,6 +268,20 @@ fn default_transaction_sender_config() -> TransactionSenderConfig {
     }
 }
 
+fn default_ipfs_gateways() -> Vec<String> {
+    DEFAULT_IPFS_GATEWAYS
+        .iter()
+        .map(|g| g.to_string())
+        .collect()
+}
+
+fn default_arweave_gateways() -> Vec<String> {
+    DEFAULT_ARWEAVE_GATEWAYS
+        .iter()
+        .map(|g| g.to_string())
+        .collect()
+}
+
 fn default_signer_config() -> SignerConfig {
     SignerConfig::KeypairFile {
         path: "./node-keypair.json".to_string(),
@@ -156,16 +300,50 @@ impl Default for ProverNodeConfig {
             image_download_timeout_secs: default_image_download_timeout_secs(),
             input_download_timeout_secs: default_input_download_timeout_secs(),
             maximum_concurrent_proofs: default_maximum_concurrent_proofs(),
+            reclaim_missed_commitments: default_reclaim_missed_commitments(),
             ingester_config: default_ingester_config(),
             transaction_sender_config: default_transaction_sender_config(),
             signer_config: default_signer_config(),
             stark_compression_tools_path: default_stark_compression_tools_path(),
             metrics_config: default_metrics_config(),
             missing_image_strategy: MissingImageStrategy::default(),
+            ipfs_gateways: default_ipfs_gateways(),
+            arweave_gateways: default_arweave_gateways(),
+            download_retry_policy: RetryPolicy::default(),
+            url_guard: UrlGuardConfig::default(),
+            receipt_store: None,
+            pipeline: PipelineConfig::default(),
+            deferred_claims: DeferredClaimsConfig::default(),
+            segment_workers: SegmentWorkersConfig::default(),
+            prover_workers: Vec::new(),
+            lookup_tables: LookupTableConfig::default(),
+            wallet: WalletConfig::default(),
+            ledger: None,
+            shadow: None,
         }
     }
 }
 
+impl ProverNodeConfig {
+    pub fn gateways(&self) -> anyhow::Result<Gateways> {
+        Gateways::new(&self.ipfs_gateways, &self.arweave_gateways)
+    }
+
+    pub fn build_url_guard(&self) -> anyhow::Result<Arc<UrlGuard>> {
+        Ok(Arc::new(UrlGuard::new(self.url_guard.clone())?))
+    }
+
+    /// Input and image downloads share the gateways, retry policy and guard but use their own client
+    pub fn url_fetcher(
+        &self,
+        client: reqwest::Client,
+        guard: Arc<UrlGuard>,
+    ) -> anyhow::Result<UrlFetcher> {
+        let fetcher = UrlFetcher::new(client, self.gateways()?, self.download_retry_policy.clone());
+        Ok(fetcher.with_guard(guard))
+    }
+}
+
 pub fn load_config(config_path: &str) -> ProverNodeConfig {
     let figment = Figment::new().merge(Toml::file(config_path));
     figment.extract().unwrap()
@@ -262,4 +440,104 @@ KeypairFile = { path = "node_keypair.json" }"#;
         }
         Ok(())
     }
+
+    #[test]
+    fn test_pipeline_config_defaults() {
+        let config: ProverNodeConfig = toml::from_str(
+            r#"
+[pipeline]
+proving_workers = 4"#,
+        )
+        .unwrap();
+        assert_eq!(config.pipeline.proving_workers, 4);
+        assert_eq!(config.pipeline.compression_workers, 1);
+        assert_eq!(config.pipeline.input_workers, 8);
+    }
+
+    #[test]
+    fn test_prover_workers_config() {
+        let config: ProverNodeConfig = toml::from_str(
+            r#"
+[[prover_workers]]
+prover_version = "V1_0_1"
+command = "/usr/opt/bonsol/bonsol-prover-worker-1.0"
+stark_compression_tools_path = "/usr/opt/bonsol/stark-1.0"
+"#,
+        )
+        .unwrap();
+        assert_eq!(config.prover_workers.len(), 1);
+        assert_eq!(config.prover_workers[0].prover_version, "V1_0_1");
+        assert!(config.prover_workers[0].args.is_empty());
+        assert_eq!(
+            config.prover_workers[0]
+                .stark_compression_tools_path
+                .as_deref(),
+            Some("/usr/opt/bonsol/stark-1.0")
+        );
+    }
+
+    #[test]
+    fn test_lookup_tables_config() {
+        let config: ProverNodeConfig = toml::from_str(
+            r#"
+[lookup_tables]
+min_accounts = 4
+"#,
+        )
+        .unwrap();
+        assert!(config.lookup_tables.manage);
+        assert_eq!(config.lookup_tables.min_accounts, 4);
+    }
+
+    #[test]
+    fn test_wallet_config() {
+        let config: ProverNodeConfig = toml::from_str(
+            r#"
+[wallet]
+reserve_lamports = 1000
+
+[wallet.top_up]
+funding_signer = { KeypairFile = { path = "funder.json" } }
+below_lamports = 2000
+amount_lamports = 5000
+"#,
+        )
+        .unwrap();
+        assert_eq!(config.wallet.reserve_lamports, 1000);
+        assert_eq!(config.wallet.refresh_secs, 10);
+        let top_up = config.wallet.top_up.unwrap();
+        assert_eq!(top_up.below_lamports, 2000);
+        assert_eq!(top_up.amount_lamports, 5000);
+        assert!(
+            matches!(top_up.funding_signer, SignerConfig::KeypairFile { path } if path == "funder.json")
+        );
+    }
+
+    #[test]
+    fn test_ledger_config() {
+        let config: ProverNodeConfig = toml::from_str(
+            r#"
+[ledger]
+path = "ledger.sqlite"
+"#,
+        )
+        .unwrap();
+        assert_eq!(config.ledger.unwrap().path, "ledger.sqlite");
+        assert!(ProverNodeConfig::default().ledger.is_none());
+    }
+
+    #[test]
+    fn test_shadow_config() {
+        let config: ProverNodeConfig = toml::from_str(
+            r#"
+[shadow]
+compress = true
+"#,
+        )
+        .unwrap();
+        assert!(config.shadow.unwrap().compress);
+        let config: ProverNodeConfig = toml::from_str("[shadow]").unwrap();
+        assert!(!config.shadow.unwrap().compress);
+        assert!(ProverNodeConfig::default().shadow.is_none());
+    }
 }
@@ -0,0 +1,47 @@
+use {
+    super::{Ingester, TxChannel},
+    crate::types::BonsolInstruction,
+    anyhow::{anyhow, Result},
+    solana_sdk::pubkey::Pubkey,
+    std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    tokio::sync::mpsc::{unbounded_channel, UnboundedSender},
+};
+
+/// Delivers the instructions sent on the channel returned by `new`, dropping the sender ends the stream
+pub struct ChannelIngester {
+    receiver: Option<TxChannel>,
+    pub stopped: Arc<AtomicBool>,
+    /// Stopping records the stop and then fails
+    pub fail_stop: bool,
+}
+
+impl ChannelIngester {
+    pub fn new() -> (Self, UnboundedSender<Vec<BonsolInstruction>>) {
+        let (tx, rx) = unbounded_channel();
+        let ingester = ChannelIngester {
+            receiver: Some(rx),
+            stopped: Arc::new(AtomicBool::new(false)),
+            fail_stop: false,
+        };
+        (ingester, tx)
+    }
+}
+
+impl Ingester for ChannelIngester {
+    fn start(&mut self, _program: Pubkey) -> Result<TxChannel> {
+        self.receiver
+            .take()
+            .ok_or(anyhow!("Ingester already started"))
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        self.stopped.store(true, Ordering::SeqCst);
+        if self.fail_stop {
+            return Err(anyhow!("Ingester failed to stop"));
+        }
+        Ok(())
+    }
+}
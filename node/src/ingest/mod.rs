@@ -1,5 +1,7 @@
 mod block_subscription;
 mod grpc_stream;
+#[cfg(test)]
+pub mod mock;
 
 use anyhow::Result;
 pub use {block_subscription::RpcIngester, grpc_stream::GrpcIngester};
@@ -28,7 +30,7 @@ pub struct IngestError {
 }
 
 pub type IngesterResult = Result<(), IngestError>;
-pub trait Ingester {
+pub trait Ingester: Send {
     fn start(&mut self, program: Pubkey) -> Result<TxChannel>;
 
     fn stop(&mut self) -> Result<()>;
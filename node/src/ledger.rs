@@ -0,0 +1,366 @@
+use {
+    crate::transaction_sender::TransactionSender,
+    anyhow::Result,
+    rusqlite::{params, Connection},
+    serde::{Deserialize, Serialize},
+    solana_sdk::{pubkey::Pubkey, signature::Signature},
+    std::{
+        sync::Mutex,
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    },
+    tracing::error,
+};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS executions (
+    execution_id TEXT PRIMARY KEY,
+    image_id TEXT NOT NULL,
+    requester TEXT NOT NULL,
+    tip INTEGER NOT NULL,
+    stake INTEGER NOT NULL,
+    pending_stake INTEGER NOT NULL DEFAULT 0,
+    cycles INTEGER,
+    proving_ms INTEGER,
+    compression_ms INTEGER,
+    fees INTEGER NOT NULL DEFAULT 0,
+    earned INTEGER NOT NULL DEFAULT 0,
+    outcome TEXT NOT NULL,
+    claimed_at INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS executions_claimed_at ON executions (claimed_at);
+";
+
+/// Where the earnings ledger is kept, one row per claimed execution
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct LedgerConfig {
+    /// SQLite database, created if missing
+    pub path: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum_macros::Display)]
+pub enum Outcome {
+    Claimed,
+    ClaimFailed,
+    /// Another prover claimed or completed the execution
+    Lost,
+    Completed,
+    /// This node reported a failure status
+    Failed,
+    /// The proof was proven but could not be submitted
+    SubmitFailed,
+    Expired,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SummaryGroup {
+    Image,
+    Requester,
+}
+
+impl SummaryGroup {
+    const fn column(&self) -> &'static str {
+        match self {
+            SummaryGroup::Image => "image_id",
+            SummaryGroup::Requester => "requester",
+        }
+    }
+}
+
+/// Totals of the executions of one image or requester, in lamports unless noted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfitSummary {
+    pub key: String,
+    pub executions: u64,
+    pub completed: u64,
+    pub earned: u64,
+    pub stake: u64,
+    pub fees: u64,
+    /// Earned tips less stake and fees, the bonsol program keeps the stake of a claim in the claim account
+    pub profit: i64,
+    pub cycles: u64,
+    pub proving_ms: u64,
+    pub compression_ms: u64,
+}
+
+/// Records the tip, stake, proving work, fees and outcome of every execution the node claims. Writes that fail
+/// are logged and dropped, the ledger never holds up proving
+pub struct Ledger {
+    connection: Option<Mutex<Connection>>,
+}
+
+impl Ledger {
+    pub fn from_config(config: Option<&LedgerConfig>) -> Result<Self> {
+        match config {
+            Some(config) => Self::open(Connection::open(&config.path)?),
+            None => Ok(Ledger { connection: None }),
+        }
+    }
+
+    fn open(connection: Connection) -> Result<Self> {
+        connection.execute_batch(SCHEMA)?;
+        Ok(Ledger {
+            connection: Some(Mutex::new(connection)),
+        })
+    }
+
+    /// A claim sent by this node, its stake counts once [`Ledger::record_claim_landed`] confirms it, claims
+    /// that lose the race fail on-chain and move no stake
+    pub fn record_claim(
+        &self,
+        execution_id: &str,
+        image_id: &str,
+        requester: &Pubkey,
+        tip: u64,
+        stake: u64,
+    ) {
+        let now = unix_time() as i64;
+        self.write(
+            "INSERT INTO executions (execution_id, image_id, requester, tip, stake, pending_stake, outcome, claimed_at,
+                updated_at)
+             VALUES (?1, ?2, ?3, ?4, 0, ?5, ?6, ?7, ?7)
+             ON CONFLICT (execution_id) DO UPDATE SET
+                pending_stake = excluded.pending_stake, outcome = excluded.outcome, updated_at = excluded.updated_at",
+            params![
+                execution_id,
+                image_id,
+                requester.to_string(),
+                tip as i64,
+                stake as i64,
+                Outcome::Claimed.to_string(),
+                now
+            ],
+        );
+    }
+
+    /// The claim transaction confirmed without error, claiming an execution again adds the stake of the new claim
+    pub fn record_claim_landed(&self, execution_id: &str) {
+        self.write(
+            "UPDATE executions SET stake = stake + pending_stake, pending_stake = 0, updated_at = ?2
+             WHERE execution_id = ?1",
+            params![execution_id, unix_time() as i64],
+        );
+    }
+
+    /// Cycles are only known for executions proven in process
+    pub fn record_proving(&self, execution_id: &str, cycles: Option<u64>, proving: Duration) {
+        self.write(
+            "UPDATE executions SET cycles = ?2, proving_ms = ?3, updated_at = ?4 WHERE execution_id = ?1",
+            params![
+                execution_id,
+                cycles.map(|c| c as i64),
+                proving.as_millis() as i64,
+                unix_time() as i64
+            ],
+        );
+    }
+
+    pub fn record_compression(&self, execution_id: &str, compression: Duration) {
+        self.write(
+            "UPDATE executions SET compression_ms = ?2, updated_at = ?3 WHERE execution_id = ?1",
+            params![
+                execution_id,
+                compression.as_millis() as i64,
+                unix_time() as i64
+            ],
+        );
+    }
+
+    /// Looks up the fee of a confirmed transaction of the execution, failed transactions pay it too
+    pub async fn record_fee(
+        &self,
+        transaction_sender: &dyn TransactionSender,
+        execution_id: &str,
+        signature: &Signature,
+    ) {
+        if self.connection.is_none() {
+            return;
+        }
+        match transaction_sender.get_transaction_fee(signature).await {
+            Ok(fee) => self.write(
+                "UPDATE executions SET fees = fees + ?2, updated_at = ?3 WHERE execution_id = ?1",
+                params![execution_id, fee as i64, unix_time() as i64],
+            ),
+            Err(e) => error!("Error reading fee of {}: {:?}", signature, e),
+        }
+    }
+
+    /// Only executions this node claimed are updated, a completed execution earns its tip
+    pub fn record_outcome(&self, execution_id: &str, outcome: Outcome) {
+        self.write(
+            "UPDATE executions SET outcome = ?2, earned = CASE WHEN ?3 THEN tip ELSE 0 END, updated_at = ?4
+             WHERE execution_id = ?1",
+            params![
+                execution_id,
+                outcome.to_string(),
+                outcome == Outcome::Completed,
+                unix_time() as i64
+            ],
+        );
+    }
+
+    /// Totals of the executions claimed in `[since, until)`, unix seconds, most profitable first
+    pub fn summary(
+        &self,
+        group: SummaryGroup,
+        since: u64,
+        until: u64,
+    ) -> Result<Vec<ProfitSummary>> {
+        let Some(connection) = &self.connection else {
+            return Ok(Vec::new());
+        };
+        let connection = connection.lock().unwrap();
+        let column = group.column();
+        let mut statement = connection.prepare(&format!(
+            "SELECT {column}, COUNT(*), SUM(outcome = ?3), SUM(earned), SUM(stake), SUM(fees),
+                SUM(earned) - SUM(stake) - SUM(fees) AS profit, TOTAL(cycles), TOTAL(proving_ms),
+                TOTAL(compression_ms)
+             FROM executions WHERE claimed_at >= ?1 AND claimed_at < ?2
+             GROUP BY {column} ORDER BY profit DESC"
+        ))?;
+        let rows = statement.query_map(
+            params![since as i64, until as i64, Outcome::Completed.to_string()],
+            |row| {
+                Ok(ProfitSummary {
+                    key: row.get(0)?,
+                    executions: row.get::<_, i64>(1)? as u64,
+                    completed: row.get::<_, i64>(2)? as u64,
+                    earned: row.get::<_, i64>(3)? as u64,
+                    stake: row.get::<_, i64>(4)? as u64,
+                    fees: row.get::<_, i64>(5)? as u64,
+                    profit: row.get(6)?,
+                    cycles: row.get::<_, f64>(7)? as u64,
+                    proving_ms: row.get::<_, f64>(8)? as u64,
+                    compression_ms: row.get::<_, f64>(9)? as u64,
+                })
+            },
+        )?;
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+
+    fn write(&self, sql: &str, params: impl rusqlite::Params) {
+        if let Some(connection) = &self.connection {
+            if let Err(e) = connection.lock().unwrap().execute(sql, params) {
+                error!("Error writing to the ledger: {:?}", e);
+            }
+        }
+    }
+}
+
+pub fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ledger() -> Ledger {
+        Ledger::open(Connection::open_in_memory().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_profit_per_image() {
+        let ledger = ledger();
+        let requester = Pubkey::new_unique();
+        ledger.record_claim("a", "image", &requester, 1000, 500);
+        ledger.record_claim_landed("a");
+        ledger.record_proving("a", Some(2048), Duration::from_millis(300));
+        ledger.record_compression("a", Duration::from_millis(200));
+        ledger.record_outcome("a", Outcome::Completed);
+        // lost the race, the claim failed on-chain and moved no stake
+        ledger.record_claim("b", "image", &requester, 1000, 500);
+        ledger.record_outcome("b", Outcome::Lost);
+        ledger.record_claim("c", "other", &requester, 400, 200);
+        ledger.record_claim_landed("c");
+        ledger.record_outcome("c", Outcome::Completed);
+
+        let now = unix_time();
+        let summary = ledger
+            .summary(SummaryGroup::Image, now - 60, now + 60)
+            .unwrap();
+
+        assert_eq!(
+            summary,
+            vec![
+                ProfitSummary {
+                    key: "image".to_string(),
+                    executions: 2,
+                    completed: 1,
+                    earned: 1000,
+                    stake: 500,
+                    fees: 0,
+                    profit: 500,
+                    cycles: 2048,
+                    proving_ms: 300,
+                    compression_ms: 200,
+                },
+                ProfitSummary {
+                    key: "other".to_string(),
+                    executions: 1,
+                    completed: 1,
+                    earned: 400,
+                    stake: 200,
+                    fees: 0,
+                    profit: 200,
+                    cycles: 0,
+                    proving_ms: 0,
+                    compression_ms: 0,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reclaim_keeps_row() {
+        let ledger = ledger();
+        let requester = Pubkey::new_unique();
+        ledger.record_claim("a", "image", &requester, 1000, 500);
+        ledger.record_claim_landed("a");
+        ledger.record_outcome("a", Outcome::Lost);
+        ledger.record_claim("a", "image", &requester, 1000, 0);
+        ledger.record_claim_landed("a");
+        ledger.record_outcome("a", Outcome::Completed);
+        ledger.record_outcome("unclaimed", Outcome::Lost);
+
+        let now = unix_time();
+        let summary = ledger
+            .summary(SummaryGroup::Requester, now - 60, now + 60)
+            .unwrap();
+
+        assert_eq!(summary.len(), 1);
+        assert_eq!(summary[0].key, requester.to_string());
+        assert_eq!(summary[0].executions, 1);
+        assert_eq!(summary[0].stake, 500);
+        assert_eq!(summary[0].profit, 500);
+        assert!(ledger
+            .summary(SummaryGroup::Requester, now + 60, now + 120)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_records_fees() {
+        let ledger = ledger();
+        let sender = crate::transaction_sender::mock::RecordingTransactionSender::default();
+        ledger.record_claim("a", "image", &Pubkey::new_unique(), 1000, 500);
+        ledger.record_claim_landed("a");
+        ledger
+            .record_fee(&sender, "a", &Signature::new_unique())
+            .await;
+        ledger
+            .record_fee(&sender, "a", &Signature::new_unique())
+            .await;
+
+        let now = unix_time();
+        let summary = ledger
+            .summary(SummaryGroup::Image, now - 60, now + 60)
+            .unwrap();
+
+        assert_eq!(summary[0].fees, 10_000);
+        assert_eq!(summary[0].profit, -10_500);
+    }
+}
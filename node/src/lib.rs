@@ -0,0 +1,13 @@
+pub mod types;
+#[macro_use]
+pub mod observe;
+pub mod config;
+pub mod ingest;
+pub mod ledger;
+mod node;
+pub mod prover_worker;
+mod risc0_runner;
+pub mod segment_worker;
+pub mod transaction_sender;
+
+pub use node::{Node, NodeBuilder, NodeError, NodeStatus};
@@ -0,0 +1,454 @@
+use {
+    crate::{
+        config::{IngesterConfig, ProverNodeConfig, SignerConfig, TransactionSenderConfig},
+        ingest::{GrpcIngester, Ingester, RpcIngester},
+        observe::*,
+        risc0_runner::Risc0Runner,
+        transaction_sender::{RpcTransactionSender, TransactionSender},
+    },
+    anyhow::Result,
+    bonsol_prover::{
+        input_resolver::{DefaultInputResolver, InputResolver},
+        receipt_store::ReceiptStore,
+        resolver_registry::InputResolverRegistry,
+        InputType,
+    },
+    solana_rpc_client::nonblocking::rpc_client::RpcClient,
+    solana_sdk::{
+        pubkey::Pubkey,
+        signature::{read_keypair_file, Keypair},
+        signer::Signer,
+    },
+    std::{str::FromStr, sync::Arc, time::Duration},
+    thiserror::Error,
+    tokio::task::JoinHandle,
+    tracing::info,
+};
+
+#[derive(Error, Debug)]
+pub enum NodeError {
+    #[error("Invalid RPC URL")]
+    InvalidRpcUrl,
+    #[error("Invalid Bonsol program")]
+    InvalidBonsolProgram,
+    #[error("Invalid signer: Missing/Invalid")]
+    InvalidSigner,
+    #[error("Invalid Ingester")]
+    InvalidIngester,
+    #[error("A custom signer needs a custom transaction sender that signs with it")]
+    MissingTransactionSender,
+    #[error("Node was already started")]
+    AlreadyStarted,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeStatus {
+    /// Built but not started
+    Ready,
+    Running,
+    /// Stopped through [`Node::stop`]
+    Stopped,
+    /// The ingester closed its channel, the node no longer receives instructions
+    Exited,
+}
+
+struct InputResolverRoute {
+    input_type: InputType,
+    scheme: Option<String>,
+    resolver: Arc<dyn InputResolver>,
+}
+
+/// Builds a node from its config, every part that is not supplied is created from the config the same way
+/// the `bonsol-node` binary does
+pub struct NodeBuilder {
+    config: ProverNodeConfig,
+    signer: Option<Arc<dyn Signer + Send + Sync>>,
+    ingester: Option<Box<dyn Ingester>>,
+    transaction_sender: Option<Box<dyn TransactionSender>>,
+    input_resolver: Option<Arc<dyn InputResolver>>,
+    input_resolver_routes: Vec<InputResolverRoute>,
+    receipt_store: Option<Arc<dyn ReceiptStore>>,
+}
+
+impl NodeBuilder {
+    pub fn new(config: ProverNodeConfig) -> Self {
+        NodeBuilder {
+            config,
+            signer: None,
+            ingester: None,
+            transaction_sender: None,
+            input_resolver: None,
+            input_resolver_routes: Vec::new(),
+            receipt_store: None,
+        }
+    }
+
+    /// The node identity, it signs private input requests and must be the key the transaction sender claims with
+    pub fn with_signer(mut self, signer: Arc<dyn Signer + Send + Sync>) -> Self {
+        self.signer = Some(signer);
+        self
+    }
+
+    pub fn with_ingester(mut self, ingester: Box<dyn Ingester>) -> Self {
+        self.ingester = Some(ingester);
+        self
+    }
+
+    /// The sender is started when the node is built
+    pub fn with_transaction_sender(
+        mut self,
+        transaction_sender: Box<dyn TransactionSender>,
+    ) -> Self {
+        self.transaction_sender = Some(transaction_sender);
+        self
+    }
+
+    /// Replaces the input resolver, resolvers registered with [`NodeBuilder::register_input_resolver`] are
+    /// routed to in front of it
+    pub fn with_input_resolver(mut self, input_resolver: Arc<dyn InputResolver>) -> Self {
+        self.input_resolver = Some(input_resolver);
+        self
+    }
+
+    /// Routes inputs of the type, and the url scheme if given, to the resolver instead of the default one
+    pub fn register_input_resolver(
+        mut self,
+        input_type: InputType,
+        scheme: Option<&str>,
+        resolver: Arc<dyn InputResolver>,
+    ) -> Self {
+        self.input_resolver_routes.push(InputResolverRoute {
+            input_type,
+            scheme: scheme.map(str::to_string),
+            resolver,
+        });
+        self
+    }
+
+    /// Replaces the receipt store from the config, proven receipts are published to it and execution proof
+    /// inputs read from it
+    pub fn with_receipt_store(mut self, receipt_store: Arc<dyn ReceiptStore>) -> Self {
+        self.receipt_store = Some(receipt_store);
+        self
+    }
+
+    pub async fn build(self) -> Result<Node> {
+        let config = self.config;
+        let program = Pubkey::from_str(&config.bonsol_program)
+            .map_err(|_| NodeError::InvalidBonsolProgram)?;
+        let keypair = match (&self.signer, &self.transaction_sender) {
+            (Some(_), Some(_)) => None,
+            (Some(_), None) => return Err(NodeError::MissingTransactionSender.into()),
+            (None, _) => Some(load_keypair(&config)?),
+        };
+        let signer = match self.signer {
+            Some(signer) => signer,
+            None => Arc::new(
+                keypair
+                    .as_ref()
+                    .ok_or(NodeError::InvalidSigner)?
+                    .insecure_clone(),
+            ),
+        };
+        let ingester = match self.ingester {
+            Some(ingester) => ingester,
+            None => default_ingester(&config)?,
+        };
+        let mut transaction_sender = match self.transaction_sender {
+            Some(transaction_sender) => transaction_sender,
+            None => Box::new(
+                RpcTransactionSender::new(
+                    rpc_url(&config)?,
+                    program,
+                    keypair.ok_or(NodeError::InvalidSigner)?,
+                )
+                .with_lookup_tables(config.lookup_tables.clone()),
+            ),
+        };
+        transaction_sender.start();
+        let receipt_store = match self.receipt_store {
+            Some(receipt_store) => Some(receipt_store),
+            None => config
+                .receipt_store
+                .as_ref()
+                .map(|c| c.build())
+                .transpose()?,
+        };
+        let fallback = match self.input_resolver {
+            Some(input_resolver) => input_resolver,
+            None => default_input_resolver(&config, receipt_store.clone())?,
+        };
+        let input_resolver: Arc<dyn InputResolver> = if self.input_resolver_routes.is_empty() {
+            fallback
+        } else {
+            let mut registry = InputResolverRegistry::builder(fallback);
+            for route in self.input_resolver_routes {
+                registry = match route.scheme {
+                    Some(scheme) => {
+                        registry.register_scheme(route.input_type, &scheme, route.resolver)
+                    }
+                    None => registry.register(route.input_type, route.resolver),
+                };
+            }
+            Arc::new(registry.build())
+        };
+        //may take time to load images, depending on the number of images TODO put limit
+        let runner = Risc0Runner::new(
+            config,
+            signer.clone(),
+            Arc::from(transaction_sender),
+            input_resolver,
+            receipt_store,
+        )
+        .await?;
+        Ok(Node {
+            program,
+            identity: signer.pubkey(),
+            ingester,
+            runner,
+            forwarder: None,
+            stopped: false,
+        })
+    }
+}
+
+fn load_keypair(config: &ProverNodeConfig) -> Result<Keypair> {
+    match &config.signer_config {
+        SignerConfig::KeypairFile { path } => {
+            info!("Using Keypair File");
+            Ok(read_keypair_file(path).map_err(|_| NodeError::InvalidSigner)?)
+        }
+    }
+}
+
+fn rpc_url(config: &ProverNodeConfig) -> Result<String> {
+    match &config.transaction_sender_config {
+        TransactionSenderConfig::Rpc { rpc_url } => Ok(rpc_url.clone()),
+        _ => Err(NodeError::InvalidRpcUrl.into()),
+    }
+}
+
+fn default_ingester(config: &ProverNodeConfig) -> Result<Box<dyn Ingester>> {
+    match config.ingester_config.clone() {
+        IngesterConfig::RpcBlockSubscription { wss_rpc_url } => {
+            info!("Using RPC Block Subscription");
+            Ok(Box::new(RpcIngester::new(wss_rpc_url)))
+        }
+        IngesterConfig::GrpcSubscription {
+            grpc_url,
+            token,
+            connection_timeout_secs,
+            timeout_secs,
+        } => {
+            info!("Using GRPC Subscription");
+            Ok(Box::new(GrpcIngester::new(
+                grpc_url,
+                token,
+                Some(connection_timeout_secs),
+                Some(timeout_secs),
+            )))
+        }
+        _ => Err(NodeError::InvalidIngester.into()),
+    }
+}
+
+fn default_input_resolver(
+    config: &ProverNodeConfig,
+    receipt_store: Option<Arc<dyn ReceiptStore>>,
+) -> Result<Arc<dyn InputResolver>> {
+    // requesters choose input urls, the guard keeps them away from internal addresses
+    let url_guard = config.build_url_guard()?;
+    let input_client = url_guard.client_builder().build()?;
+    let input_fetcher = Arc::new(config.url_fetcher(input_client.clone(), url_guard)?);
+    let mut input_resolver = DefaultInputResolver::new_with_opts(
+        Arc::new(input_client),
+        Arc::new(RpcClient::new(rpc_url(config)?)),
+        Some(config.max_input_size_mb),
+        Some(Duration::from_secs(
+            config.image_download_timeout_secs as u64,
+        )),
+    )
+    .with_fetcher(input_fetcher);
+    if let Some(receipt_store) = receipt_store {
+        input_resolver = input_resolver.with_receipt_store(receipt_store);
+    }
+    Ok(Arc::new(input_resolver))
+}
+
+/// A built node, instructions flow from the ingester to the runner once it is started
+pub struct Node {
+    program: Pubkey,
+    identity: Pubkey,
+    ingester: Box<dyn Ingester>,
+    runner: Risc0Runner,
+    forwarder: Option<JoinHandle<()>>,
+    stopped: bool,
+}
+
+impl Node {
+    pub const fn identity(&self) -> &Pubkey {
+        &self.identity
+    }
+
+    pub fn start(&mut self) -> Result<()> {
+        if self.forwarder.is_some() || self.stopped {
+            return Err(NodeError::AlreadyStarted.into());
+        }
+        emit_event!(MetricEvents::BonsolStartup, up => true);
+        let runner_chan = self.runner.start()?;
+        let mut ingester_chan = self.ingester.start(self.program)?;
+        self.forwarder = Some(tokio::spawn(async move {
+            while let Some(bix) = ingester_chan.recv().await {
+                for ix in bix {
+                    if runner_chan.send(ix).is_err() {
+                        return;
+                    }
+                }
+            }
+        }));
+        info!("Node {} started", self.identity);
+        Ok(())
+    }
+
+    pub fn stop(&mut self) -> Result<()> {
+        self.stopped = true;
+        if let Some(forwarder) = self.forwarder.take() {
+            forwarder.abort();
+            // the runner would keep claiming, it is stopped even if the ingester fails to stop
+            let ingester = self.ingester.stop();
+            self.runner.stop()?;
+            ingester?;
+        }
+        Ok(())
+    }
+
+    pub fn status(&self) -> NodeStatus {
+        match &self.forwarder {
+            _ if self.stopped => NodeStatus::Stopped,
+            Some(forwarder) if forwarder.is_finished() => NodeStatus::Exited,
+            Some(_) => NodeStatus::Running,
+            None => NodeStatus::Ready,
+        }
+    }
+
+    /// Resolves once the ingester stops delivering instructions, returns right away if the node is not running
+    pub async fn wait(&mut self) {
+        if let Some(forwarder) = self.forwarder.as_mut().filter(|f| !f.is_finished()) {
+            let _ = forwarder.await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::{
+            ingest::mock::ChannelIngester, risc0_runner::test_runner_config,
+            transaction_sender::mock::RecordingTransactionSender,
+        },
+        std::sync::atomic::Ordering,
+        tempfile::tempdir,
+    };
+
+    async fn node(folder: &std::path::Path, ingester: ChannelIngester) -> Node {
+        NodeBuilder::new(test_runner_config(folder))
+            .with_signer(Arc::new(Keypair::new()))
+            .with_ingester(Box::new(ingester))
+            .with_transaction_sender(Box::<RecordingTransactionSender>::default())
+            .build()
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_custom_signer_needs_transaction_sender() {
+        let folder = tempdir().unwrap();
+        let res = NodeBuilder::new(test_runner_config(folder.path()))
+            .with_signer(Arc::new(Keypair::new()))
+            .build()
+            .await;
+
+        assert!(matches!(
+            res.err().unwrap().downcast_ref::<NodeError>(),
+            Some(NodeError::MissingTransactionSender)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_bonsol_program() {
+        let folder = tempdir().unwrap();
+        let config = ProverNodeConfig {
+            bonsol_program: "not a program".to_string(),
+            ..test_runner_config(folder.path())
+        };
+        let res = NodeBuilder::new(config).build().await;
+
+        assert!(matches!(
+            res.err().unwrap().downcast_ref::<NodeError>(),
+            Some(NodeError::InvalidBonsolProgram)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_lifecycle() {
+        let folder = tempdir().unwrap();
+        let (ingester, _tx) = ChannelIngester::new();
+        let ingester_stopped = ingester.stopped.clone();
+        let signer = Arc::new(Keypair::new());
+        let mut node = NodeBuilder::new(test_runner_config(folder.path()))
+            .with_signer(signer.clone())
+            .with_ingester(Box::new(ingester))
+            .with_transaction_sender(Box::<RecordingTransactionSender>::default())
+            .build()
+            .await
+            .unwrap();
+        assert_eq!(node.identity(), &signer.pubkey());
+        assert_eq!(node.status(), NodeStatus::Ready);
+
+        node.start().unwrap();
+        assert_eq!(node.status(), NodeStatus::Running);
+        assert!(matches!(
+            node.start().err().unwrap().downcast_ref::<NodeError>(),
+            Some(NodeError::AlreadyStarted)
+        ));
+
+        node.stop().unwrap();
+        assert_eq!(node.status(), NodeStatus::Stopped);
+        assert!(ingester_stopped.load(Ordering::SeqCst));
+        assert!(!node.runner.is_running());
+        assert!(matches!(
+            node.start().err().unwrap().downcast_ref::<NodeError>(),
+            Some(NodeError::AlreadyStarted)
+        ));
+        // stopping again does nothing
+        node.stop().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_exits_when_ingester_closes() {
+        let folder = tempdir().unwrap();
+        let (ingester, tx) = ChannelIngester::new();
+        let mut node = node(folder.path(), ingester).await;
+        node.start().unwrap();
+
+        drop(tx);
+        node.wait().await;
+
+        assert_eq!(node.status(), NodeStatus::Exited);
+    }
+
+    #[tokio::test]
+    async fn test_stops_runner_when_ingester_fails_to_stop() {
+        let folder = tempdir().unwrap();
+        let (mut ingester, _tx) = ChannelIngester::new();
+        ingester.fail_stop = true;
+        let mut node = node(folder.path(), ingester).await;
+        node.start().unwrap();
+
+        assert!(node.stop().is_err());
+
+        assert_eq!(node.status(), NodeStatus::Stopped);
+        assert!(!node.runner.is_running());
+    }
+}
@@ -11,6 +11,9 @@ pub enum MetricEvents {
     ProvingSucceeded,
     ClaimAttempt,
     ClaimMissed,
+    ClaimRetry,
+    ClaimDeferred,
+    DeferredClaims,
     ClaimReceived,
     ImageDeployment,
     ImageDownload,
@@ -21,14 +24,35 @@ pub enum MetricEvents {
     ProofGeneration,
     ProofCompression,
     ProofConversion,
+    ProofPreflight,
     InputDownload,
+    InputProbe,
+    PrivateInputUnavailable,
     ProofCycles,
     ProofSegments,
     BonsolStartup,
     SignaturesInFlight,
     IncompatibleProverVersion,
     ProofSubmissionError,
+    LookupTableExtension,
     TransactionExpired,
+    PipelineQueueDepth,
+    PipelineStage,
+    SegmentProof,
+    SegmentWorkerError,
+    ExecutionStatus,
+    ExecutionStatusBlocks,
+    ProvingCancelled,
+    WalletBalance,
+    LockedStake,
+    ClaimingPaused,
+    ClaimUnfunded,
+    WalletTopUp,
+    ShadowProof,
+    ShadowRun,
+    ShadowExpiryMargin,
+    ShadowLatencyDelta,
+    ShadowSkipped,
 }
 
 macro_rules! emit_event {
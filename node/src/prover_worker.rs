@@ -0,0 +1,253 @@
+use {
+    crate::risc0_runner::{CompressedReciept, Risc0RunnerError},
+    anyhow::Result,
+    bonsol_interface::{
+        bonsol_schema::ProgramInputType,
+        prover_version::{ProverVersion, VERSION_V1_0_1, VERSION_V1_2_1},
+    },
+    bonsol_prover::input_resolver::ProgramInput,
+    bonsol_prover_worker_protocol::{
+        ProverWorkerRequest, ProverWorkerResponse, WorkerFailure, WorkerInput,
+    },
+    risc0_zkvm::{sha::Digest, Journal},
+    serde::{Deserialize, Serialize},
+    std::process::{ExitStatus, Stdio},
+    thiserror::Error,
+    tokio::{io::AsyncWriteExt, process::Command},
+};
+
+#[derive(Debug, Error)]
+pub enum ProverWorkerError {
+    #[error("Unknown prover version {0}")]
+    UnknownVersion(String),
+    #[error("Prover worker {0} exited with {1}")]
+    Exited(String, ExitStatus),
+    #[error("Unexpected response from prover worker {0}")]
+    UnexpectedResponse(String),
+    #[error(
+        "Prover worker {command} reports verifier digest {actual}, {version} needs {expected}"
+    )]
+    DigestMismatch {
+        command: String,
+        version: ProverVersion,
+        expected: String,
+        actual: String,
+    },
+    #[error("Input {0} is not resolved")]
+    UnresolvedInput(u8),
+    #[error("Prover worker rejected the request: {0}")]
+    InvalidRequest(String),
+}
+
+/// A prover worker binary built against a single risc0 version
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProverWorkerConfig {
+    /// The prover version of the requests this worker proves, `V1_0_1` or `V1_2_1`
+    pub prover_version: String,
+    /// Path to a `bonsol-prover-worker` binary, it is started once per request
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Compression tools matching the worker's risc0 version, defaults to the node's `stark_compression_tools_path`
+    pub stark_compression_tools_path: Option<String>,
+}
+
+impl ProverWorkerConfig {
+    pub fn prover_version(&self) -> Result<ProverVersion, ProverWorkerError> {
+        [VERSION_V1_0_1, VERSION_V1_2_1]
+            .into_iter()
+            .find(|v| v.to_string() == self.prover_version)
+            .ok_or_else(|| ProverWorkerError::UnknownVersion(self.prover_version.clone()))
+    }
+}
+
+/// Runs requests on a prover worker process, the worker's failures come back as [`Risc0RunnerError`] so the
+/// pipeline reports them the same way as failures of the in process prover
+#[derive(Debug)]
+pub struct ProverWorker {
+    pub version: ProverVersion,
+    config: ProverWorkerConfig,
+}
+
+impl ProverWorker {
+    pub fn from_config(config: &ProverWorkerConfig) -> Result<Self, ProverWorkerError> {
+        Ok(ProverWorker {
+            version: config.prover_version()?,
+            config: config.clone(),
+        })
+    }
+
+    pub fn stark_compression_tools_path(&self) -> Option<&str> {
+        self.config.stark_compression_tools_path.as_deref()
+    }
+
+    /// Fails unless the worker was built with the groth16 verifier of its prover version
+    pub async fn verify_version(&self) -> Result<()> {
+        let expected = match &self.version {
+            ProverVersion::V1_0_1 { verifier_digest }
+            | ProverVersion::V1_2_1 { verifier_digest } => *verifier_digest,
+            ProverVersion::UnsupportedVersion => {
+                return Err(ProverWorkerError::UnknownVersion(self.version.to_string()).into())
+            }
+        };
+        match self.call(&ProverWorkerRequest::Version).await? {
+            ProverWorkerResponse::Version { verifier_digest } if verifier_digest == expected => {
+                Ok(())
+            }
+            ProverWorkerResponse::Version { verifier_digest } => {
+                Err(ProverWorkerError::DigestMismatch {
+                    command: self.config.command.clone(),
+                    version: self.version.clone(),
+                    expected: expected.to_string(),
+                    actual: verifier_digest,
+                }
+                .into())
+            }
+            _ => Err(self.unexpected_response()),
+        }
+    }
+
+    /// Returns the journal, the assumptions digest and the worker's receipt to pass to [`ProverWorker::compress`]
+    pub async fn prove(
+        &self,
+        elf: &[u8],
+        inputs: Vec<ProgramInput>,
+    ) -> Result<(Journal, Digest, Vec<u8>)> {
+        let inputs = inputs
+            .into_iter()
+            .map(|input| match input {
+                ProgramInput::Resolved(ri) => Ok(WorkerInput {
+                    proof: ri.input_type == ProgramInputType::PublicProof,
+                    data: ri.data,
+                }),
+                other => Err(ProverWorkerError::UnresolvedInput(other.index())),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let request = ProverWorkerRequest::Prove {
+            elf: elf.to_vec(),
+            inputs,
+        };
+        match self.call(&request).await? {
+            ProverWorkerResponse::Proven {
+                journal,
+                assumptions_digest,
+                receipt,
+            } => Ok((
+                Journal::new(journal),
+                Digest::try_from(assumptions_digest.as_slice())
+                    .map_err(|_| self.unexpected_response())?,
+                receipt,
+            )),
+            ProverWorkerResponse::Failed(failure) => Err(into_error(failure)),
+            _ => Err(self.unexpected_response()),
+        }
+    }
+
+    pub async fn compress(&self, receipt: Vec<u8>, tools_path: &str) -> Result<CompressedReciept> {
+        let request = ProverWorkerRequest::Compress {
+            receipt,
+            tools_path: tools_path.to_string(),
+        };
+        match self.call(&request).await? {
+            ProverWorkerResponse::Compressed {
+                execution_digest,
+                exit_code_system,
+                exit_code_user,
+                proof,
+            } => Ok(CompressedReciept {
+                execution_digest,
+                exit_code_system,
+                exit_code_user,
+                proof,
+            }),
+            ProverWorkerResponse::Failed(failure) => Err(into_error(failure)),
+            _ => Err(self.unexpected_response()),
+        }
+    }
+
+    /// Starts the worker, writes the request to its stdin and reads the response from its stdout
+    /// The worker's stderr goes to the node's so its logs end up next to the node's
+    async fn call(&self, request: &ProverWorkerRequest) -> Result<ProverWorkerResponse> {
+        let mut child = Command::new(&self.config.command)
+            .args(&self.config.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .kill_on_drop(true)
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(&bincode::serialize(request)?).await?;
+        }
+        let output = child.wait_with_output().await?;
+        if !output.status.success() {
+            return Err(
+                ProverWorkerError::Exited(self.config.command.clone(), output.status).into(),
+            );
+        }
+        bincode::deserialize(&output.stdout).map_err(|_| self.unexpected_response())
+    }
+
+    fn unexpected_response(&self) -> anyhow::Error {
+        ProverWorkerError::UnexpectedResponse(self.config.command.clone()).into()
+    }
+}
+
+/// Maps the worker's failure to the error of the in process prover
+fn into_error(failure: WorkerFailure) -> anyhow::Error {
+    match failure {
+        WorkerFailure::GuestPanic(msg) => Risc0RunnerError::GuestPanic(msg).into(),
+        WorkerFailure::CycleLimitExceeded => Risc0RunnerError::CycleLimitExceeded.into(),
+        WorkerFailure::ProofGeneration(_) => Risc0RunnerError::ProofGenerationError.into(),
+        WorkerFailure::Compression(_) => Risc0RunnerError::ProofCompressionError.into(),
+        WorkerFailure::InvalidRequest(msg) => ProverWorkerError::InvalidRequest(msg).into(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn worker(prover_version: &str, script: &str) -> ProverWorkerConfig {
+        ProverWorkerConfig {
+            prover_version: prover_version.to_string(),
+            command: "sh".to_string(),
+            args: vec!["-c".to_string(), script.to_string()],
+            stark_compression_tools_path: None,
+        }
+    }
+
+    #[test]
+    fn test_worker_config_prover_version() {
+        let config = worker("V1_0_1", "");
+        assert_eq!(config.prover_version().unwrap(), VERSION_V1_0_1);
+
+        let err = worker("V2_0_0", "").prover_version().unwrap_err();
+        assert!(matches!(err, ProverWorkerError::UnknownVersion(v) if v == "V2_0_0"));
+    }
+
+    #[tokio::test]
+    async fn test_worker_exit_status_fails_the_request() {
+        let worker =
+            ProverWorker::from_config(&worker("V1_0_1", "cat > /dev/null; exit 3")).unwrap();
+
+        let err = worker.verify_version().await.unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<ProverWorkerError>(),
+            Some(ProverWorkerError::Exited(_, status)) if status.code() == Some(3)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_worker_garbage_response() {
+        let worker =
+            ProverWorker::from_config(&worker("V1_0_1", "cat > /dev/null; echo nope")).unwrap();
+
+        let err = worker.verify_version().await.unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<ProverWorkerError>(),
+            Some(ProverWorkerError::UnexpectedResponse(_))
+        ));
+    }
+}
@@ -0,0 +1,204 @@
+use {
+    crate::{
+        config::DeferredClaimsConfig, observe::*, transaction_sender::TransactionSender,
+        types::BonsolInstruction,
+    },
+    dashmap::DashMap,
+    solana_sdk::pubkey::Pubkey,
+    tracing::warn,
+};
+
+/// An execution request the node could not take when it arrived
+#[derive(Debug)]
+pub struct DeferredRequest {
+    pub execution_id: String,
+    pub image_id: String,
+    pub expiry: u64,
+    pub execution_account: Pubkey,
+    /// Handled again like a new request once it is picked up, so every check of a first claim applies
+    pub instruction: BonsolInstruction,
+}
+
+/// Requests waiting for a free slot or their image, keyed by execution id
+#[derive(Default)]
+pub struct DeferredClaims {
+    requests: DashMap<String, DeferredRequest>,
+}
+
+impl DeferredClaims {
+    /// Returns false if the queue is full and the request is dropped
+    pub fn defer(&self, config: &DeferredClaimsConfig, request: DeferredRequest) -> bool {
+        if self.requests.len() >= config.max_deferred
+            && !self.requests.contains_key(&request.execution_id)
+        {
+            warn!(
+                "Deferred claim queue is full, dropping {}",
+                request.execution_id
+            );
+            return false;
+        }
+        emit_event!(MetricEvents::ClaimDeferred, execution_id => &request.execution_id);
+        self.requests.insert(request.execution_id.clone(), request);
+        self.record_depth();
+        true
+    }
+
+    /// Drops the request once it is claimed or completed by anyone
+    pub fn remove(&self, execution_id: &str) {
+        if self.requests.remove(execution_id).is_some() {
+            self.record_depth();
+        }
+    }
+
+    /// Takes the requests to handle again, at most `free_slots` of them. Requests whose image is not loaded yet
+    /// wait, requests that are about to expire or that someone else holds an active claim on are dropped
+    pub async fn take_claimable(
+        &self,
+        config: &DeferredClaimsConfig,
+        free_slots: usize,
+        image_loaded: impl Fn(&str) -> bool,
+        transaction_sender: &dyn TransactionSender,
+        current_block: u64,
+    ) -> Vec<BonsolInstruction> {
+        let mut candidates = self
+            .requests
+            .iter()
+            .map(|r| {
+                (
+                    r.expiry,
+                    r.execution_id.clone(),
+                    r.image_id.clone(),
+                    r.execution_account,
+                )
+            })
+            .collect::<Vec<_>>();
+        // the requests closest to expiring go first
+        candidates.sort();
+        let mut claimable = Vec::new();
+        for (expiry, execution_id, image_id, execution_account) in candidates {
+            if expiry < current_block + config.min_remaining_blocks {
+                self.remove(&execution_id);
+                continue;
+            }
+            if claimable.len() >= free_slots {
+                break;
+            }
+            if !image_loaded(&image_id) {
+                continue;
+            }
+            match transaction_sender.get_claim_state(execution_account).await {
+                Ok(Some(claim)) if claim.block_commitment >= current_block => {
+                    self.remove(&execution_id);
+                }
+                Ok(_) => {
+                    if let Some((_, request)) = self.requests.remove(&execution_id) {
+                        claimable.push(request.instruction);
+                    }
+                }
+                // the claim is checked again on the next pass
+                Err(e) => warn!("Error checking claim of {}: {:?}", execution_id, e),
+            }
+        }
+        self.record_depth();
+        claimable
+    }
+
+    fn record_depth(&self) {
+        let g = gauge!("gauges", "gauge" => MetricEvents::DeferredClaims.to_string());
+        g.set(self.requests.len() as f64);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*, crate::transaction_sender::mock::RecordingTransactionSender,
+        bonsol_interface::claim_state::ClaimStateV1,
+    };
+
+    fn request(execution_id: &str, image_id: &str, expiry: u64) -> DeferredRequest {
+        DeferredRequest {
+            execution_id: execution_id.to_string(),
+            image_id: image_id.to_string(),
+            expiry,
+            execution_account: Pubkey::new_unique(),
+            instruction: BonsolInstruction::new(false, vec![], execution_id.as_bytes().to_vec(), 0),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_takes_unclaimed_requests_with_loaded_images() {
+        let config = DeferredClaimsConfig::default();
+        let deferred = DeferredClaims::default();
+        deferred.defer(&config, request("ready", "img", 1000));
+        deferred.defer(&config, request("loading", "other", 1000));
+        let sender = RecordingTransactionSender::default();
+
+        let taken = deferred
+            .take_claimable(&config, 10, |id: &str| id == "img", &sender, 100)
+            .await;
+
+        assert_eq!(taken.len(), 1);
+        assert_eq!(taken[0].data, b"ready");
+        assert_eq!(deferred.requests.len(), 1);
+        assert!(deferred.requests.contains_key("loading"));
+    }
+
+    #[tokio::test]
+    async fn test_drops_claimed_and_expiring_requests() {
+        let config = DeferredClaimsConfig::default();
+        let deferred = DeferredClaims::default();
+        let claimed = request("claimed", "img", 1000);
+        let missed = request("missed", "img", 1000);
+        let sender = RecordingTransactionSender::default();
+        sender.claim_states.insert(
+            claimed.execution_account,
+            ClaimStateV1::from_claim_ix(&Pubkey::new_unique(), 90, 500),
+        );
+        sender.claim_states.insert(
+            missed.execution_account,
+            ClaimStateV1::from_claim_ix(&Pubkey::new_unique(), 10, 50),
+        );
+        deferred.defer(&config, claimed);
+        deferred.defer(&config, missed);
+        deferred.defer(&config, request("expiring", "img", 200));
+
+        let taken = deferred
+            .take_claimable(&config, 10, |id: &str| id == "img", &sender, 100)
+            .await;
+
+        assert_eq!(taken.len(), 1);
+        assert_eq!(taken[0].data, b"missed");
+        assert!(deferred.requests.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_respects_free_slots() {
+        let config = DeferredClaimsConfig::default();
+        let deferred = DeferredClaims::default();
+        deferred.defer(&config, request("later", "img", 2000));
+        deferred.defer(&config, request("sooner", "img", 1000));
+        let sender = RecordingTransactionSender::default();
+
+        let taken = deferred
+            .take_claimable(&config, 1, |id: &str| id == "img", &sender, 100)
+            .await;
+
+        assert_eq!(taken.len(), 1);
+        assert_eq!(taken[0].data, b"sooner");
+        assert!(deferred.requests.contains_key("later"));
+    }
+
+    #[test]
+    fn test_full_queue_drops_requests() {
+        let config = DeferredClaimsConfig {
+            max_deferred: 1,
+            ..Default::default()
+        };
+        let deferred = DeferredClaims::default();
+
+        assert!(deferred.defer(&config, request("first", "img", 1000)));
+        assert!(!deferred.defer(&config, request("second", "img", 1000)));
+        assert!(deferred.defer(&config, request("first", "img", 1000)));
+    }
+}
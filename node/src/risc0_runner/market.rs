@@ -0,0 +1,121 @@
+use {
+    crate::observe::*, bonsol_interface::bonsol_schema::StatusTypes, dashmap::DashMap,
+    solana_sdk::pubkey::Pubkey, std::time::Duration,
+};
+
+struct ObservedRequest {
+    image_id: String,
+    expiry: u64,
+    block: u64,
+    seen_at: Instant,
+}
+
+/// Who completed an execution and how long it took from the request to the status
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompletedExecution {
+    pub image_id: String,
+    pub prover: Pubkey,
+    pub latency: Duration,
+    pub latency_blocks: u64,
+}
+
+/// Follows every execution request on the channel to its status, whichever prover submits it
+#[derive(Default)]
+pub struct MarketObserver {
+    requests: DashMap<String, ObservedRequest>,
+}
+
+impl MarketObserver {
+    /// A request handled again keeps its first sighting
+    pub fn observe_request(&self, execution_id: &str, image_id: &str, expiry: u64, block: u64) {
+        self.requests
+            .entry(execution_id.to_string())
+            .or_insert_with(|| ObservedRequest {
+                image_id: image_id.to_string(),
+                expiry,
+                block,
+                seen_at: Instant::now(),
+            });
+    }
+
+    /// Records the prover of a status per image, None if the request was not seen by this node
+    pub fn observe_status(
+        &self,
+        execution_id: &str,
+        prover: &Pubkey,
+        status: StatusTypes,
+        block: u64,
+    ) -> Option<CompletedExecution> {
+        let (_, request) = self.requests.remove(execution_id)?;
+        let completed = CompletedExecution {
+            image_id: request.image_id,
+            prover: *prover,
+            latency: request.seen_at.elapsed(),
+            latency_blocks: block.saturating_sub(request.block),
+        };
+        let (prover, image_id, status) = (
+            completed.prover.to_string(),
+            completed.image_id.clone(),
+            format!("{:?}", status),
+        );
+        info!(
+            event = MetricEvents::ExecutionStatus.to_string(),
+            execution_id = execution_id,
+            prover = &prover,
+            image_id = &image_id,
+            status = &status,
+            latency_blocks = completed.latency_blocks,
+            "Execution status from {} after {} ms",
+            prover,
+            completed.latency.as_millis()
+        );
+        let c = counter!("counters", "counter" => MetricEvents::ExecutionStatus.to_string(), "prover" => prover.clone(), "image_id" => image_id.clone(), "status" => status);
+        c.increment(1);
+        let h = histogram!("durations", "duration" => MetricEvents::ExecutionStatus.to_string(), "prover" => prover.clone(), "image_id" => image_id.clone());
+        h.record(completed.latency.as_millis() as f64);
+        let h = histogram!("histograms", "histogram" => MetricEvents::ExecutionStatusBlocks.to_string(), "prover" => prover, "image_id" => image_id);
+        h.record(completed.latency_blocks as f64);
+        Some(completed)
+    }
+
+    /// Forgets requests that expired without a status
+    pub fn expire(&self, current_block: u64) {
+        self.requests.retain(|_, r| r.expiry >= current_block);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_status_latency() {
+        let market = MarketObserver::default();
+        let prover = Pubkey::new_unique();
+        market.observe_request("exec", "image", 500, 100);
+        market.observe_request("exec", "image", 500, 120);
+
+        let completed = market
+            .observe_status("exec", &prover, StatusTypes::Completed, 142)
+            .unwrap();
+
+        assert_eq!(completed.image_id, "image");
+        assert_eq!(completed.prover, prover);
+        assert_eq!(completed.latency_blocks, 42);
+        assert!(market
+            .observe_status("exec", &prover, StatusTypes::Completed, 150)
+            .is_none());
+    }
+
+    #[test]
+    fn test_expire() {
+        let market = MarketObserver::default();
+        market.observe_request("expired", "image", 200, 100);
+        market.observe_request("pending", "image", 500, 100);
+
+        market.expire(201);
+
+        assert!(!market.requests.contains_key("expired"));
+        assert!(market.requests.contains_key("pending"));
+    }
+}
@@ -1,5 +1,13 @@
+mod deferred;
+mod market;
+mod pipeline;
+mod preflight;
+mod provers;
+mod reclaim;
+mod shadow;
 mod utils;
 pub mod verify_prover_version;
+mod wallet;
 
 use crate::transaction_sender::TransactionStatus;
 
@@ -10,20 +18,23 @@ use {
 
 use {
     crate::{
-        config::ProverNodeConfig,
+        config::{MissingImageStrategy, ProverNodeConfig},
+        ledger::{Ledger, Outcome},
         observe::*,
         risc0_runner::utils::async_to_json,
-        transaction_sender::{RpcTransactionSender, TransactionSender},
-        MissingImageStrategy,
+        segment_worker::SegmentWorkerPool,
+        transaction_sender::TransactionSender,
     },
     bonsol_interface::{
-        bonsol_schema::{ClaimV1, DeployV1, ExecutionRequestV1},
+        bonsol_schema::{
+            ClaimV1, DeployV1, ExecutionRequestV1, FailureReason, StatusTypes, StatusV1,
+        },
         prover_version::{ProverVersion, VERSION_V1_2_1},
     },
     dashmap::DashMap,
     risc0_binfmt::MemoryImage,
     risc0_zkvm::{ExitCode, Journal, SuccinctReceipt},
-    solana_sdk::{pubkey::Pubkey, signature::Signature},
+    solana_sdk::{pubkey::Pubkey, signature::Signature, signer::Signer},
     std::{
         convert::TryInto, env::consts::ARCH, fs, io::Cursor, path::Path, sync::Arc, time::Duration,
     },
@@ -34,17 +45,26 @@ use {
     anyhow::Result,
     bonsol_interface::bonsol_schema::{parse_ix_data, root_as_deploy_v1, ChannelInstructionIxType},
     bonsol_prover::{
+        fetch::{FetchError, UrlFetcher},
         image::Image,
         input_resolver::{InputResolver, ProgramInput},
         prover::{get_risc0_prover, new_risc0_exec_env},
-        util::get_body_max_size,
+        receipt_store::ReceiptStore,
+    },
+    deferred::{DeferredClaims, DeferredRequest},
+    market::MarketObserver,
+    pipeline::{ClaimJob, ClaimPipeline, PipelineContext, ProofProgram},
+    provers::{ProverBackend, Provers},
+    reclaim::{
+        forget_lost_claim, reclaim_missed_commitments, LostClaim, LostClaims, LostClaimsRef,
     },
     risc0_groth16::{ProofJson, Seal},
     risc0_zkvm::{
         recursion::identity_p254,
         sha::{Digest, Digestible},
-        InnerReceipt, MaybePruned, ReceiptClaim, VerifierContext,
+        InnerReceipt, MaybePruned, Receipt, ReceiptClaim, Session, VerifierContext,
     },
+    shadow::ShadowRuns,
     tempfile::tempdir,
     thiserror::Error,
     tokio::{
@@ -52,6 +72,7 @@ use {
     },
     tracing::{error, info, warn},
     verify_prover_version::verify_prover_version,
+    wallet::{stake_for, Wallet},
 };
 
 const REQUIRED_PROVER: ProverVersion = VERSION_V1_2_1;
@@ -76,8 +97,31 @@ pub enum Risc0RunnerError {
     ProofCompressionError,
     #[error("Error with proof generation")]
     ProofGenerationError,
+    #[error("Guest panicked: {0}")]
+    GuestPanic(String),
+    #[error("Execution error: {0}")]
+    ExecutionError(String),
+    #[error("Cycle limit exceeded")]
+    CycleLimitExceeded,
     #[error("Invalid prover version {0}, expected {1}")]
     InvalidProverVersion(ProverVersion, ProverVersion),
+    #[error("No prover for version {0}")]
+    NoProverForVersion(ProverVersion),
+    #[error("Proof failed preflight verification")]
+    PreflightVerificationFailed,
+}
+
+impl Risc0RunnerError {
+    /// The reason reported on-chain when proving is abandoned because of this error
+    pub const fn failure_reason(&self) -> FailureReason {
+        match self {
+            Risc0RunnerError::GuestPanic(_) => FailureReason::GuestPanic,
+            Risc0RunnerError::CycleLimitExceeded => FailureReason::CycleLimitExceeded,
+            Risc0RunnerError::ProofCompressionError
+            | Risc0RunnerError::PreflightVerificationFailed => FailureReason::CompressionFailure,
+            _ => FailureReason::Unknown,
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -94,7 +138,10 @@ pub struct InflightProof {
     pub claim_signature: Signature,
     pub submission_signature: Option<Signature>,
     pub expiry: u64,
+    /// Half of it is staked by the claim
+    pub tip: u64,
     pub requester: Pubkey,
+    pub prover_version: ProverVersion,
     pub program_callback: Option<ProgramExec>,
     pub additional_accounts: Vec<AccountMeta>,
 }
@@ -113,19 +160,30 @@ pub struct Risc0Runner {
     loaded_images: LoadedImageMap,
     worker_handle: Option<JoinHandle<Result<()>>>,
     inflight_proof_worker_handle: Option<JoinHandle<Result<()>>>,
-    txn_sender: Arc<RpcTransactionSender>,
+    txn_sender: Arc<dyn TransactionSender>,
     input_staging_area: InputStagingArea,
     self_identity: Arc<Pubkey>,
+    signer: Arc<dyn Signer + Send + Sync>,
     inflight_proofs: InflightProofs,
+    lost_claims: LostClaims,
+    deferred_claims: Arc<DeferredClaims>,
+    market: Arc<MarketObserver>,
+    wallet: Arc<Wallet>,
+    ledger: Arc<Ledger>,
+    shadow: Option<Arc<ShadowRuns>>,
     input_resolver: Arc<dyn InputResolver + 'static>,
+    receipt_store: Option<Arc<dyn ReceiptStore>>,
+    pipeline: Option<Arc<ClaimPipeline>>,
+    provers: Arc<Provers>,
 }
 
 impl Risc0Runner {
     pub async fn new(
         config: ProverNodeConfig,
-        self_identity: Pubkey,
-        txn_sender: Arc<RpcTransactionSender>,
+        signer: Arc<dyn Signer + Send + Sync>,
+        txn_sender: Arc<dyn TransactionSender>,
         input_resolver: Arc<dyn InputResolver + 'static>,
+        receipt_store: Option<Arc<dyn ReceiptStore>>,
     ) -> Result<Risc0Runner> {
         let dir = fs::read_dir(&config.risc0_image_folder)?;
         let loaded_images = DashMap::new();
@@ -144,6 +202,15 @@ impl Risc0Runner {
 
         check_stark_compression_tools_path(&config.stark_compression_tools_path)?;
 
+        let provers = Provers::from_config(REQUIRED_PROVER, &config.prover_workers)?;
+        provers.verify().await?;
+        let wallet = Wallet::from_config(&config.wallet)?;
+        let ledger = Ledger::from_config(config.ledger.as_ref())?;
+        let shadow = config.shadow.as_ref().map(|_| {
+            warn!("Shadow mode, requests are proven but never claimed or submitted");
+            Arc::new(ShadowRuns::default())
+        });
+
         Ok(Risc0Runner {
             config: Arc::new(config),
             loaded_images: Arc::new(loaded_images),
@@ -151,9 +218,19 @@ impl Risc0Runner {
             inflight_proof_worker_handle: None,
             txn_sender,
             input_staging_area: Arc::new(DashMap::new()),
-            self_identity: Arc::new(self_identity),
+            self_identity: Arc::new(signer.pubkey()),
+            signer,
             inflight_proofs: Arc::new(DashMap::new()),
+            lost_claims: Arc::new(DashMap::new()),
+            deferred_claims: Arc::new(DeferredClaims::default()),
+            market: Arc::new(MarketObserver::default()),
+            wallet: Arc::new(wallet),
+            ledger: Arc::new(ledger),
+            shadow,
             input_resolver,
+            receipt_store,
+            pipeline: None,
+            provers: Arc::new(provers),
         })
     }
 
@@ -166,26 +243,43 @@ impl Risc0Runner {
         let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<BonsolInstruction>();
         let loaded_images = self.loaded_images.clone();
         // TODO: move image handling out of prover
-        let img_client = Arc::new(
-            reqwest::Client::builder()
-                .timeout(Duration::from_secs(
-                    self.config.image_download_timeout_secs as u64,
-                ))
-                .build()?,
+        let url_guard = self.config.build_url_guard()?;
+        let img_fetcher = Arc::new(
+            self.config.url_fetcher(
+                url_guard
+                    .client_builder()
+                    .timeout(Duration::from_secs(
+                        self.config.image_download_timeout_secs as u64,
+                    ))
+                    .build()?,
+                url_guard,
+            )?,
         );
         let config = self.config.clone();
         let self_id = self.self_identity.clone();
+        let signer = self.signer.clone();
         let input_staging_area = self.input_staging_area.clone();
         let inflight_proofs = self.inflight_proofs.clone();
+        let lost_claims = self.lost_claims.clone();
+        let deferred_claims = self.deferred_claims.clone();
+        let market = self.market.clone();
+        let wallet = self.wallet.clone();
+        let ledger = self.ledger.clone();
+        let shadow = self.shadow.clone();
         let txn_sender = self.txn_sender.clone();
+        let (reclaim_config, reclaim_staging_area) = (config.clone(), input_staging_area.clone());
+        let (deferred_images, deferred_tx) = (loaded_images.clone(), tx.clone());
         self.inflight_proof_worker_handle = Some(tokio::spawn(async move {
             let mut interval = tokio::time::interval(Duration::from_secs(1));
             loop {
                 interval.tick().await;
                 let current_block = txn_sender.get_current_block().await.unwrap_or(0);
+                let mut confirmed_claims = Vec::new();
                 inflight_proofs.retain(|_, v| {
                     if v.expiry < current_block {
                         emit_event!(MetricEvents::ProofExpired, execution_id => v.execution_id.clone());
+                        wallet.release_stake(&v.execution_id);
+                        ledger.record_outcome(&v.execution_id, Outcome::Expired);
                         return false;
                     }
                     match &v.status {
@@ -193,14 +287,24 @@ impl Risc0Runner {
                             let sig = v.claim_signature;
                             let inner_status = txn_sender.get_signature_status(&sig);
                             return match inner_status {
-                                None => false,
+                                // the sender lost track of the claim, it is dropped like a failed one
+                                None => {
+                                    info!("Claim Transaction Status Unknown");
+                                    wallet.release_stake(&v.execution_id);
+                                    ledger.record_outcome(&v.execution_id, Outcome::ClaimFailed);
+                                    false
+                                }
                                 Some(status) => {
                                     match status {
                                         TransactionStatus::Confirmed(status) => {
                                             txn_sender.clear_signature_status(&sig);
+                                            confirmed_claims.push((v.execution_id.clone(), sig));
                                             if status.err.is_some() {
                                                 info!("Claim Transaction Failed");
-
+                                                wallet.release_stake(&v.execution_id);
+                                                ledger.record_outcome(&v.execution_id, Outcome::ClaimFailed);
+                                            } else {
+                                                ledger.record_claim_landed(&v.execution_id);
                                             }
                                             status.err.is_none()
                                         },
@@ -232,22 +336,92 @@ impl Risc0Runner {
                     };
                     true
                 });
+                // a failed claim pays its fee too
+                for (execution_id, sig) in confirmed_claims {
+                    ledger
+                        .record_fee(txn_sender.as_ref(), &execution_id, &sig)
+                        .await;
+                }
+                market.expire(current_block);
+                if let Some(shadow) = &shadow {
+                    shadow.expire(current_block);
+                }
+                wallet
+                    .refresh(&reclaim_config.wallet, txn_sender.as_ref(), current_block)
+                    .await;
+                reclaim_missed_commitments(
+                    &reclaim_config,
+                    &lost_claims,
+                    &inflight_proofs,
+                    &reclaim_staging_area,
+                    &wallet,
+                    &ledger,
+                    txn_sender.as_ref(),
+                    current_block,
+                )
+                .await;
+                // deferred requests go through the channel again as if they just arrived
+                let free_slots = (reclaim_config.maximum_concurrent_proofs as usize)
+                    .saturating_sub(inflight_proofs.len());
+                let claimable = deferred_claims
+                    .take_claimable(
+                        &reclaim_config.deferred_claims,
+                        free_slots,
+                        |image_id| deferred_images.contains_key(image_id),
+                        txn_sender.as_ref(),
+                        current_block,
+                    )
+                    .await;
+                for bix in claimable {
+                    if deferred_tx.send(bix).is_err() {
+                        error!("Runner stopped, dropping deferred request");
+                    }
+                }
             }
         }));
 
         let inflight_proofs = self.inflight_proofs.clone();
+        let lost_claims = self.lost_claims.clone();
+        let deferred_claims = self.deferred_claims.clone();
+        let market = self.market.clone();
+        let wallet = self.wallet.clone();
+        let ledger = self.ledger.clone();
+        let shadow = self.shadow.clone();
         let txn_sender = self.txn_sender.clone();
         let input_resolver = self.input_resolver.clone();
+        let provers = self.provers.clone();
+        let pipeline = Arc::new(ClaimPipeline::start(PipelineContext {
+            config: config.clone(),
+            transaction_sender: txn_sender.clone(),
+            signer: signer.clone(),
+            input_resolver: input_resolver.clone(),
+            receipt_store: self.receipt_store.clone(),
+            in_flight_proofs: inflight_proofs.clone(),
+            segment_pool: SegmentWorkerPool::from_config(&config.segment_workers).map(Arc::new),
+            ledger: ledger.clone(),
+            wallet: wallet.clone(),
+            shadow: shadow.clone(),
+        }));
+        self.pipeline = Some(pipeline.clone());
         self.worker_handle = Some(tokio::spawn(async move {
             while let Some(bix) = rx.recv().await {
                 let txn_sender = txn_sender.clone();
                 let loaded_images = loaded_images.clone();
                 let config = config.clone();
-                let img_client = img_client.clone();
+                let img_fetcher = img_fetcher.clone();
                 let input_resolver = input_resolver.clone();
+                let pipeline = pipeline.clone();
+                let provers = provers.clone();
                 let self_id = self_id.clone();
+                let signer = signer.clone();
                 let input_staging_area = input_staging_area.clone();
                 let inflight_proofs = inflight_proofs.clone();
+                let lost_claims = lost_claims.clone();
+                let deferred_claims = deferred_claims.clone();
+                let market = market.clone();
+                let wallet = wallet.clone();
+                let ledger = ledger.clone();
+                let shadow = shadow.clone();
                 tokio::spawn(async move {
                     let bonsol_ix_type =
                         parse_ix_data(&bix.data).map_err(|_| Risc0RunnerError::InvalidData)?;
@@ -259,7 +433,7 @@ impl Risc0Runner {
                                 Risc0RunnerError::EmptyInstruction.into(),
                             )?;
                             emit_counter!(MetricEvents::ImageDeployment, 1, "image_id" => payload.image_id().unwrap_or_default());
-                            handle_image_deployment(&config, &img_client, payload, &loaded_images)
+                            handle_image_deployment(&config, &img_fetcher, payload, &loaded_images)
                                 .await
                         }
                         ChannelInstructionIxType::ExecuteV1 => {
@@ -270,34 +444,33 @@ impl Risc0Runner {
                                 .ok_or::<anyhow::Error>(
                                 Risc0RunnerError::EmptyInstruction.into(),
                             )?;
-                            let er_prover_version: ProverVersion = payload
-                                .prover_version()
-                                .try_into()
-                                .map_err::<anyhow::Error, _>(|_| {
-                                    Risc0RunnerError::InvalidProverVersion(
-                                        ProverVersion::UnsupportedVersion,
-                                        REQUIRED_PROVER,
-                                    )
-                                    .into()
-                                })?;
-                            if er_prover_version != REQUIRED_PROVER {
-                                return Err(Risc0RunnerError::InvalidProverVersion(
-                                    er_prover_version,
-                                    REQUIRED_PROVER,
-                                )
-                                .into());
+                            if let (Some(execution_id), Some(image_id)) =
+                                (payload.execution_id(), payload.image_id())
+                            {
+                                market.observe_request(
+                                    execution_id,
+                                    image_id,
+                                    payload.max_block_height(),
+                                    bix.last_known_block,
+                                );
                             }
                             handle_execution_request(
                                 &config,
+                                &provers,
                                 &inflight_proofs,
                                 input_resolver.clone(),
-                                img_client.clone(),
-                                &txn_sender,
+                                img_fetcher.clone(),
+                                txn_sender.as_ref(),
+                                signer.as_ref(),
                                 &loaded_images,
                                 &input_staging_area,
-                                bix.last_known_block,
+                                &deferred_claims,
+                                &wallet,
+                                &ledger,
+                                &pipeline,
+                                shadow.as_deref(),
+                                &bix,
                                 payload,
-                                &bix.accounts,
                             )
                             .await
                         }
@@ -308,12 +481,19 @@ impl Risc0Runner {
                                 .ok_or::<anyhow::Error>(
                                 Risc0RunnerError::EmptyInstruction.into(),
                             )?;
+                            // a claimed request is no longer waiting for this node
+                            if let Some(execution_id) = payload.execution_id() {
+                                deferred_claims.remove(execution_id);
+                            }
                             handle_claim(
-                                &config,
                                 &self_id,
                                 &inflight_proofs,
-                                input_resolver.clone(),
-                                &txn_sender,
+                                &lost_claims,
+                                &wallet,
+                                &ledger,
+                                txn_sender.as_ref(),
+                                &pipeline,
+                                &provers,
                                 &loaded_images,
                                 &input_staging_area,
                                 payload,
@@ -321,7 +501,27 @@ impl Risc0Runner {
                             )
                             .await
                         }
-                        ChannelInstructionIxType::StatusV1 => Ok(()),
+                        ChannelInstructionIxType::StatusV1 => {
+                            let payload = bonsol_ix_type
+                                .status_v1_nested_flatbuffer()
+                                .ok_or::<anyhow::Error>(
+                                Risc0RunnerError::EmptyInstruction.into(),
+                            )?;
+                            handle_status(
+                                &self_id,
+                                &inflight_proofs,
+                                &lost_claims,
+                                &deferred_claims,
+                                &pipeline,
+                                &market,
+                                &wallet,
+                                &ledger,
+                                shadow.as_deref(),
+                                &input_staging_area,
+                                payload,
+                                &bix,
+                            )
+                        }
                         _ => {
                             info!("Unknown instruction type");
                             Ok(())
@@ -338,18 +538,39 @@ impl Risc0Runner {
         Ok(tx)
     }
 
+    #[cfg(test)]
+    pub(crate) fn is_running(&self) -> bool {
+        self.worker_handle
+            .as_ref()
+            .is_some_and(|h| !h.is_finished())
+            || self
+                .inflight_proof_worker_handle
+                .as_ref()
+                .is_some_and(|h| !h.is_finished())
+    }
+
     pub fn stop(&mut self) -> Result<()> {
         self.worker_handle.take().unwrap().abort();
+        // the inflight loop reclaims, re-sends deferred requests and tops up, none of that may outlive the runner
+        if let Some(handle) = self.inflight_proof_worker_handle.take() {
+            handle.abort();
+        }
+        if let Some(pipeline) = self.pipeline.take() {
+            pipeline.stop();
+        }
         Ok(())
     }
 }
 
 pub async fn handle_claim<'a>(
-    config: &ProverNodeConfig,
     self_identity: &Pubkey,
     in_flight_proofs: InflightProofRef<'a>,
-    input_resolver: Arc<dyn InputResolver + 'static>,
-    transaction_sender: &RpcTransactionSender,
+    lost_claims: LostClaimsRef<'a>,
+    wallet: &Wallet,
+    ledger: &Ledger,
+    transaction_sender: &dyn TransactionSender,
+    pipeline: &ClaimPipeline,
+    provers: &Provers,
     loaded_images: LoadedImageMapRef<'a>,
     input_staging_area: InputStagingAreaRef<'a>,
     claim: ClaimV1<'a>,
@@ -360,11 +581,25 @@ pub async fn handle_claim<'a>(
     let execution_id = claim.execution_id().ok_or(Risc0RunnerError::InvalidData)?;
     if &claimer != self_identity {
         let attempt = in_flight_proofs.remove(execution_id);
-        if let Some((ifp, claim)) = attempt {
-            if let ClaimStatus::Claiming = claim.status {
-                transaction_sender.clear_signature_status(&claim.claim_signature);
-                emit_event!(MetricEvents::ClaimMissed, execution_id => ifp, signature => &claim.claim_signature.to_string());
+        if let Some((ifp, lost)) = attempt {
+            if let ClaimStatus::Claiming = lost.status {
+                transaction_sender.clear_signature_status(&lost.claim_signature);
+                wallet.release_stake(&ifp);
+                ledger.record_outcome(&ifp, Outcome::Lost);
+                emit_event!(MetricEvents::ClaimMissed, execution_id => &ifp, signature => &lost.claim_signature.to_string());
+                // the staged inputs stay so the execution can be claimed again if the claimer misses its commitment
+                lost_claims.insert(
+                    ifp,
+                    LostClaim {
+                        claim: lost,
+                        execution_account: accounts[0],
+                        block_commitment: claim.block_commitment(),
+                    },
+                );
             }
+        } else if let Some(mut lost) = lost_claims.get_mut(execution_id) {
+            // someone took over from a claimer that missed its commitment
+            lost.block_commitment = claim.block_commitment();
         }
         return Ok(());
     }
@@ -372,7 +607,7 @@ pub async fn handle_claim<'a>(
     let claim_status = in_flight_proofs
         .get(execution_id)
         .map(|v| v.value().to_owned());
-    if let Some(mut claim) = claim_status {
+    if let Some(claim) = claim_status {
         emit_event!(MetricEvents::ClaimReceived, execution_id => execution_id);
         if let ClaimStatus::Claiming = claim.status {
             if let Some(image) = loaded_images.get(&claim.image_id) {
@@ -380,89 +615,17 @@ pub async fn handle_claim<'a>(
                     return Err(Risc0RunnerError::ImageDataUnavailable.into());
                 }
                 //if image is not loaded at claim, fail
-                let mut inputs = input_staging_area
-                    .get(execution_id)
-                    .ok_or(Risc0RunnerError::InvalidData)?
-                    .value()
-                    .clone(); //clone soe we dont hold a reference over http requests
-                let unresolved_count = inputs
-                    .iter()
-                    .filter(|i| match i {
-                        ProgramInput::Unresolved(_) => true,
-                        _ => false,
-                    })
-                    .count();
-
-                if unresolved_count > 0 {
-                    info!("{} outstanding inputs", unresolved_count);
-
-                    emit_event_with_duration!(MetricEvents::InputDownload, {
-                        input_resolver.resolve_private_inputs(execution_id, &mut inputs, Arc::new(transaction_sender)).await?;
-                    }, execution_id => execution_id, stage => "private");
-                    input_staging_area.insert(execution_id.to_string(), inputs);
-                    // one of the huge problems with the claim system is that we are not guaranteed to have
-                    // the inputs we need at the time we claim and no way to
-                }
-                info!("{} inputs resolved", unresolved_count);
-
-                // drain the inputs and own them here, this is a bit of a hack but it works
+                // drain the inputs and own them here, outstanding private inputs are resolved by the pipeline
                 let (eid, inputs) = input_staging_area
                     .remove(execution_id)
                     .ok_or(Risc0RunnerError::InvalidData)?;
-                let mem_image = image.get_memory_image()?;
-                let result: Result<
-                    (Journal, Digest, SuccinctReceipt<ReceiptClaim>),
-                    Risc0RunnerError,
-                > = tokio::task::spawn_blocking(move || {
-                    risc0_prove(mem_image, inputs).map_err(|e| {
-                        info!("Error generating proof: {:?}", e);
-                        Risc0RunnerError::ProofGenerationError
-                    })
-                })
-                .await?;
-                match result {
-                    Ok((journal, assumptions_digest, reciept)) => {
-                        let compressed_receipt = risc0_compress_proof(
-                            config.stark_compression_tools_path.as_str(),
-                            reciept,
-                        )
-                        .await
-                        .map_err(|e| {
-                            info!("Error compressing proof: {:?}", e);
-                            Risc0RunnerError::ProofCompressionError
-                        })?;
-
-                        let (input_digest, committed_outputs) = journal.bytes.split_at(32);
-                        let sig = transaction_sender
-                            .submit_proof(
-                                &eid,
-                                claim.requester,
-                                claim.program_callback.clone(),
-                                &compressed_receipt.proof,
-                                &compressed_receipt.execution_digest,
-                                input_digest,
-                                assumptions_digest.as_bytes(),
-                                committed_outputs,
-                                claim.additional_accounts.clone(),
-                                compressed_receipt.exit_code_system,
-                                compressed_receipt.exit_code_user,
-                            )
-                            .await
-                            .map_err(|e| {
-                                error!("Error submitting proof: {:?}", e);
-                                Risc0RunnerError::TransactionError(e.to_string())
-                            })?;
-
-                        claim.status = ClaimStatus::Submitted;
-                        claim.submission_signature = Some(sig);
-                        in_flight_proofs.insert(eid.clone(), claim);
-                        info!("Proof submitted: {:?}", sig);
-                    }
-                    Err(e) => {
-                        info!("Error generating proof: {:?}", e);
-                    }
-                };
-                in_flight_proofs.remove(&eid);
+                let program = proof_program(provers, &image, &claim.prover_version)?;
+                pipeline.push(ClaimJob {
+                    execution_id: eid,
+                    claim,
+                    program,
+                    inputs,
+                });
             } else {
                 info!("Image not loaded, fatal error aborting execution");
             }
@@ -471,159 +634,377 @@ pub async fn handle_claim<'a>(
     Ok(())
 }
 
+/// The image in the form the prover of the version takes it
+fn proof_program(
+    provers: &Provers,
+    image: &Image,
+    prover_version: &ProverVersion,
+) -> Result<ProofProgram> {
+    Ok(match provers.backend(prover_version) {
+        Some(ProverBackend::InProcess) => ProofProgram::InProcess(image.get_memory_image()?),
+        Some(ProverBackend::Worker(worker)) => ProofProgram::Worker {
+            worker,
+            elf: image
+                .bytes()
+                .cloned()
+                .ok_or(Risc0RunnerError::ImageDataUnavailable)?,
+        },
+        None => return Err(Risc0RunnerError::NoProverForVersion(prover_version.clone()).into()),
+    })
+}
+
+/// A status ends the execution whoever submitted it, the work of this node on it is dropped unless it is
+/// the status of this node's own proof
+pub fn handle_status<'a>(
+    self_identity: &Pubkey,
+    in_flight_proofs: InflightProofRef<'a>,
+    lost_claims: LostClaimsRef<'a>,
+    deferred_claims: &DeferredClaims,
+    pipeline: &ClaimPipeline,
+    market: &MarketObserver,
+    wallet: &Wallet,
+    ledger: &Ledger,
+    shadow: Option<&ShadowRuns>,
+    input_staging_area: InputStagingAreaRef<'a>,
+    status: StatusV1<'a>,
+    bix: &BonsolInstruction,
+) -> Result<()> {
+    let execution_id = status.execution_id().ok_or(Risc0RunnerError::InvalidData)?;
+    let prover = bix.accounts[3];
+    let completed =
+        market.observe_status(execution_id, &prover, status.status(), bix.last_known_block);
+    forget_lost_claim(lost_claims, input_staging_area, execution_id);
+    deferred_claims.remove(execution_id);
+    wallet.release_stake(execution_id);
+    if let Some(shadow) = shadow {
+        // shadow runs go on to compare against the winner
+        if let (Some(completed), StatusTypes::Completed) = (completed, status.status()) {
+            shadow.winner(execution_id, completed);
+        }
+        return Ok(());
+    }
+    if &prover == self_identity {
+        let outcome = match status.status() {
+            StatusTypes::Completed => Outcome::Completed,
+            _ => Outcome::Failed,
+        };
+        ledger.record_outcome(execution_id, outcome);
+        return Ok(());
+    }
+    ledger.record_outcome(execution_id, Outcome::Lost);
+    input_staging_area.remove(execution_id);
+    in_flight_proofs.remove(execution_id);
+    if pipeline.cancel(execution_id) {
+        info!(
+            "{} was completed by {}, cancelling local proving",
+            execution_id, prover
+        );
+    }
+    Ok(())
+}
+
+/// Publishes the succinct receipt so later executions can take it as an input, a failed upload only loses that
+async fn publish_receipt(
+    receipt_store: &dyn ReceiptStore,
+    requester: &Pubkey,
+    execution_id: &str,
+    journal: &Journal,
+    reciept: &SuccinctReceipt<ReceiptClaim>,
+) {
+    let receipt = Receipt::new(
+        InnerReceipt::Succinct(reciept.clone()),
+        journal.bytes.clone(),
+    );
+    match receipt_store.put(requester, execution_id, &receipt).await {
+        Ok(()) => info!("Receipt published for execution {}", execution_id),
+        Err(e) => error!("Error publishing receipt: {:?}", e),
+    }
+}
+
+/// Tells the bonsol program this node abandoned an execution so the requester is refunded right away
+/// instead of waiting for the execution to expire.
+async fn report_failure(
+    transaction_sender: &dyn TransactionSender,
+    execution_id: &str,
+    requester: Pubkey,
+    failure_reason: FailureReason,
+) -> Option<Signature> {
+    match transaction_sender
+        .submit_failure(execution_id, requester, failure_reason)
+        .await
+    {
+        Ok(sig) => {
+            info!("Failure status submitted: {:?}", sig);
+            Some(sig)
+        }
+        Err(e) => {
+            error!("Error submitting failure status: {:?}", e);
+            None
+        }
+    }
+}
+
 async fn handle_execution_request<'a>(
     config: &ProverNodeConfig,
+    provers: &Provers,
     in_flight_proofs: InflightProofRef<'a>,
     input_resolver: Arc<dyn InputResolver + 'static>,
-    img_client: Arc<reqwest::Client>,
-    transaction_sender: &RpcTransactionSender,
+    img_fetcher: Arc<UrlFetcher>,
+    transaction_sender: &dyn TransactionSender,
+    signer: &(dyn Signer + Send + Sync),
     loaded_images: LoadedImageMapRef<'a>,
     input_staging_area: InputStagingAreaRef<'a>,
-    _execution_block: u64,
+    deferred_claims: &DeferredClaims,
+    wallet: &Wallet,
+    ledger: &Ledger,
+    pipeline: &ClaimPipeline,
+    shadow: Option<&ShadowRuns>,
+    bix: &BonsolInstruction,
     exec: ExecutionRequestV1<'a>,
-    accounts: &[Pubkey],
 ) -> Result<()> {
-    if !can_execute(exec) {
+    let requested_at = Instant::now();
+    let accounts = &bix.accounts;
+    let Some(prover_version) = supported_prover_version(provers, exec) else {
         warn!(
             "Execution request for incompatible prover version: {:?}",
             exec.prover_version()
         );
         emit_event!(MetricEvents::IncompatibleProverVersion, execution_id => exec.execution_id().unwrap_or_default());
         return Ok(());
-    }
+    };
 
     // current naive implementation is to accept everything we have pending capacity for on this node, but this needs work
-    let inflight = in_flight_proofs.len();
+    let inflight = match shadow {
+        Some(shadow) => shadow.in_progress(),
+        None => in_flight_proofs.len(),
+    };
     emit_event!(MetricEvents::ExecutionRequest, execution_id => exec.execution_id().unwrap_or_default());
-    if inflight < config.maximum_concurrent_proofs as usize {
-        let eid = exec
-            .execution_id()
-            .map(|d| d.to_string())
-            .ok_or(Risc0RunnerError::InvalidData)?;
-        let image_id = exec
-            .image_id()
-            .map(|d| d.to_string())
-            .ok_or(Risc0RunnerError::InvalidData)?;
-        let expiry = exec.max_block_height();
-        let img = loaded_images.get(&image_id);
-        let img = if img.is_none() {
-            match config.missing_image_strategy {
-                MissingImageStrategy::DownloadAndClaim => {
-                    info!("Image not loaded, attempting to load and running claim");
-                    load_image(
-                        config,
-                        transaction_sender,
-                        &img_client,
-                        &image_id,
-                        loaded_images,
-                    )
-                    .await?;
-                    loaded_images.get(&image_id)
-                }
-                MissingImageStrategy::DownloadAndMiss => {
-                    info!("Image not loaded, loading and rejecting claim");
-                    load_image(
-                        config,
-                        transaction_sender,
-                        &img_client,
-                        &image_id,
-                        loaded_images,
-                    )
-                    .await?;
-                    None
-                }
-                MissingImageStrategy::Fail => {
-                    info!("Image not loaded, rejecting claim");
-                    None
-                }
+    let eid = exec
+        .execution_id()
+        .map(|d| d.to_string())
+        .ok_or(Risc0RunnerError::InvalidData)?;
+    let image_id = exec
+        .image_id()
+        .map(|d| d.to_string())
+        .ok_or(Risc0RunnerError::InvalidData)?;
+    let expiry = exec.max_block_height();
+    if inflight >= config.maximum_concurrent_proofs as usize && shadow.is_some() {
+        info!("At capacity, skipping shadow run");
+        emit_event!(MetricEvents::ShadowSkipped, execution_id => eid, reason => "capacity");
+        return Ok(());
+    }
+    if inflight >= config.maximum_concurrent_proofs as usize {
+        info!("At capacity, deferring claim");
+        defer_request(config, deferred_claims, bix, eid, image_id, expiry);
+        return Ok(());
+    }
+    let tip = exec.tip();
+    // skips resolving inputs for claims the node cannot fund, the stake is reserved right before claiming
+    if let (None, Err(e)) = (shadow, wallet.check_claim(&config.wallet, tip)) {
+        info!("Not claiming: {}", e);
+        emit_event!(MetricEvents::ClaimUnfunded, execution_id => eid, tip => tip);
+        return Ok(());
+    }
+    let img = loaded_images.get(&image_id);
+    let img = if img.is_none() {
+        match config.missing_image_strategy {
+            MissingImageStrategy::DownloadAndClaim => {
+                info!("Image not loaded, attempting to load and running claim");
+                load_image(
+                    config,
+                    transaction_sender,
+                    &img_fetcher,
+                    &image_id,
+                    loaded_images,
+                )
+                .await?;
+                loaded_images.get(&image_id)
+            }
+            MissingImageStrategy::DownloadAndMiss => {
+                info!("Image not loaded, loading and deferring claim");
+                load_image(
+                    config,
+                    transaction_sender,
+                    &img_fetcher,
+                    &image_id,
+                    loaded_images,
+                )
+                .await?;
+                defer_request(config, deferred_claims, bix, eid, image_id, expiry);
+                return Ok(());
+            }
+            MissingImageStrategy::Fail => {
+                info!("Image not loaded, rejecting claim");
+                None
             }
-        } else {
-            img
         }
-        .ok_or(Risc0RunnerError::ImgLoadError)?;
-
-        // naive compute cost estimate which is YES WE CAN DO THIS in the default amount of time
-        emit_histogram!(MetricEvents::ImageComputeEstimate, img.size  as f64, image_id => image_id.clone());
-        //ensure compute can happen before expiry
-        //execution_block + (image_compute_estimate % config.max_compute_per_block) + 1 some bogus calc
-        let computable_by = expiry / 2;
-
-        if computable_by < expiry {
-            //the way this is done can cause race conditions where so many request come in a short time that we accept
-            // them before we change the value of g so we optimistically change to inflight and we will decrement if we dont win the claim
-            let inputs = exec.input().ok_or(Risc0RunnerError::InvalidData)?;
-            let program_inputs = emit_event_with_duration!(MetricEvents::InputDownload, {
-                input_resolver.resolve_public_inputs(
-                    inputs.iter().map(|i| i.unpack()).collect()
-                ).await?
-            }, execution_id => eid, stage => "public");
-            input_staging_area.insert(eid.clone(), program_inputs);
-            let sig = transaction_sender
-                .claim(&eid, accounts[0], accounts[2], computable_by)
-                .await
-                .map_err(|e| Risc0RunnerError::TransactionError(e.to_string()));
-            match sig {
-                Ok(sig) => {
-                    let callback_program = exec
-                        .callback_program_id()
-                        .and_then::<[u8; 32], _>(|v| v.bytes().try_into().ok())
-                        .map(Pubkey::from);
-                    let callback = if callback_program.is_some() {
-                        Some(ProgramExec {
-                            program_id: callback_program.unwrap(),
-                            instruction_prefix: exec
-                                .callback_instruction_prefix()
-                                .map(|v| v.bytes().to_vec())
-                                .unwrap_or(vec![0x1]),
-                        })
-                    } else {
-                        None
-                    };
+    } else {
+        img
+    }
+    .ok_or(Risc0RunnerError::ImgLoadError)?;
 
-                    in_flight_proofs.insert(
-                        eid.clone(),
-                        InflightProof {
-                            execution_id: eid.clone(),
-                            image_id: image_id.clone(),
-                            status: ClaimStatus::Claiming,
-                            expiry,
-                            claim_signature: sig,
-                            submission_signature: None,
-                            requester: accounts[0],
-                            program_callback: callback,
-                            additional_accounts: exec
-                                .callback_extra_accounts()
-                                .unwrap_or_default()
-                                .into_iter()
-                                .map(|a| {
-                                    let pkbytes: [u8; 32] = a.pubkey().into();
-                                    let pubkey = Pubkey::try_from(pkbytes).unwrap_or_default();
-                                    let writable = a.writable();
-                                    AccountMeta {
-                                        pubkey,
-                                        is_writable: writable == 1,
-                                        is_signer: false,
-                                    }
-                                })
-                                .collect(),
-                        },
-                    );
-                    emit_event!(MetricEvents::ClaimAttempt, execution_id => eid);
-                }
-                Err(e) => {
-                    info!("Error claiming: {:?}", e);
-                    in_flight_proofs.remove(&eid);
-                }
+    // naive compute cost estimate which is YES WE CAN DO THIS in the default amount of time
+    emit_histogram!(MetricEvents::ImageComputeEstimate, img.size  as f64, image_id => image_id.clone());
+    //ensure compute can happen before expiry
+    //execution_block + (image_compute_estimate % config.max_compute_per_block) + 1 some bogus calc
+    let computable_by = expiry / 2;
+
+    if computable_by < expiry {
+        //the way this is done can cause race conditions where so many request come in a short time that we accept
+        // them before we change the value of g so we optimistically change to inflight and we will decrement if we dont win the claim
+        let inputs = exec.input().ok_or(Risc0RunnerError::InvalidData)?;
+        let program_inputs = emit_event_with_duration!(MetricEvents::InputDownload, {
+            input_resolver.resolve_public_inputs(
+                inputs.iter().map(|i| i.unpack()).collect()
+            ).await?
+        }, execution_id => eid, stage => "public");
+        if let Some(shadow) = shadow {
+            // private input servers only serve the claimer
+            if program_inputs
+                .iter()
+                .any(|i| matches!(i, ProgramInput::Unresolved(_)))
+            {
+                info!("Private inputs need a claim, skipping shadow run");
+                emit_event!(MetricEvents::ShadowSkipped, execution_id => eid, reason => "private_inputs");
+                return Ok(());
+            }
+            let program = proof_program(provers, &img, &prover_version)?;
+            shadow.start(&eid, &image_id, expiry, bix.last_known_block, requested_at);
+            pipeline.push(ClaimJob {
+                execution_id: eid.clone(),
+                claim: InflightProof {
+                    execution_id: eid,
+                    image_id,
+                    status: ClaimStatus::Claiming,
+                    claim_signature: Signature::default(),
+                    submission_signature: None,
+                    expiry,
+                    tip,
+                    requester: accounts[0],
+                    prover_version,
+                    program_callback: None,
+                    additional_accounts: vec![],
+                },
+                program,
+                inputs: program_inputs,
+            });
+            return Ok(());
+        }
+        // only claim when every private input server confirms it can serve this node
+        let probe = emit_event_with_duration!(MetricEvents::InputProbe, {
+            input_resolver.probe_private_inputs(&accounts[0], &eid, &program_inputs, Arc::new(signer)).await
+        }, execution_id => eid, stage => "private");
+        if let Err(e) = probe {
+            info!("Private inputs unavailable, not claiming: {:?}", e);
+            emit_event!(MetricEvents::PrivateInputUnavailable, execution_id => eid);
+            return Ok(());
+        }
+        // the wallet may have been drained by claims sent while the inputs resolved
+        if let Err(e) = wallet.reserve(&config.wallet, &eid, tip, expiry) {
+            info!("Not claiming: {}", e);
+            emit_event!(MetricEvents::ClaimUnfunded, execution_id => eid, tip => tip);
+            return Ok(());
+        }
+        input_staging_area.insert(eid.clone(), program_inputs);
+        let sig = transaction_sender
+            .claim(&eid, accounts[0], accounts[2], computable_by)
+            .await
+            .map_err(|e| Risc0RunnerError::TransactionError(e.to_string()));
+        match sig {
+            Ok(sig) => {
+                let callback_program = exec
+                    .callback_program_id()
+                    .and_then::<[u8; 32], _>(|v| v.bytes().try_into().ok())
+                    .map(Pubkey::from);
+                let callback = if callback_program.is_some() {
+                    Some(ProgramExec {
+                        program_id: callback_program.unwrap(),
+                        instruction_prefix: exec
+                            .callback_instruction_prefix()
+                            .map(|v| v.bytes().to_vec())
+                            .unwrap_or(vec![0x1]),
+                        lookup_table: exec
+                            .callback_lookup_table()
+                            .and_then::<[u8; 32], _>(|v| v.bytes().try_into().ok())
+                            .map(Pubkey::from),
+                    })
+                } else {
+                    None
+                };
+
+                in_flight_proofs.insert(
+                    eid.clone(),
+                    InflightProof {
+                        execution_id: eid.clone(),
+                        image_id: image_id.clone(),
+                        status: ClaimStatus::Claiming,
+                        expiry,
+                        tip,
+                        claim_signature: sig,
+                        submission_signature: None,
+                        requester: accounts[0],
+                        prover_version: prover_version.clone(),
+                        program_callback: callback,
+                        additional_accounts: exec
+                            .callback_extra_accounts()
+                            .unwrap_or_default()
+                            .into_iter()
+                            .map(|a| {
+                                let pkbytes: [u8; 32] = a.pubkey().into();
+                                let pubkey = Pubkey::try_from(pkbytes).unwrap_or_default();
+                                let writable = a.writable();
+                                AccountMeta {
+                                    pubkey,
+                                    is_writable: writable == 1,
+                                    is_signer: false,
+                                }
+                            })
+                            .collect(),
+                    },
+                );
+                ledger.record_claim(&eid, &image_id, &accounts[0], tip, stake_for(tip));
+                emit_event!(MetricEvents::ClaimAttempt, execution_id => eid);
+            }
+            Err(e) => {
+                info!("Error claiming: {:?}", e);
+                wallet.release_stake(&eid);
+                in_flight_proofs.remove(&eid);
             }
         }
     }
     Ok(())
 }
 
+/// Keeps a request the node cannot take right now, it is handled again once it can
+fn defer_request(
+    config: &ProverNodeConfig,
+    deferred_claims: &DeferredClaims,
+    bix: &BonsolInstruction,
+    execution_id: String,
+    image_id: String,
+    expiry: u64,
+) {
+    deferred_claims.defer(
+        &config.deferred_claims,
+        DeferredRequest {
+            execution_id,
+            image_id,
+            expiry,
+            execution_account: bix.accounts[2],
+            instruction: BonsolInstruction::new(
+                bix.cpi,
+                bix.accounts.clone(),
+                bix.data.clone(),
+                bix.last_known_block,
+            ),
+        },
+    );
+}
+
 async fn load_image<'a>(
     config: &ProverNodeConfig,
-    transaction_sender: &RpcTransactionSender,
-    http_client: &reqwest::Client,
+    transaction_sender: &dyn TransactionSender,
+    img_fetcher: &UrlFetcher,
     image_id: &str,
     loaded_images: LoadedImageMapRef<'a>,
 ) -> Result<()> {
@@ -633,13 +1014,13 @@ async fn load_image<'a>(
         .map_err(Risc0RunnerError::ImageDownloadError)?;
     let deploy_data = root_as_deploy_v1(&account.data)
         .map_err(|_| anyhow::anyhow!("Failed to parse account data"))?;
-    handle_image_deployment(config, http_client, deploy_data, loaded_images).await?;
+    handle_image_deployment(config, img_fetcher, deploy_data, loaded_images).await?;
     Ok(())
 }
 
 async fn handle_image_deployment<'a>(
     config: &ProverNodeConfig,
-    http_client: &reqwest::Client,
+    img_fetcher: &UrlFetcher,
     deploy: DeployV1<'a>,
     loaded_images: LoadedImageMapRef<'a>,
 ) -> Result<()> {
@@ -647,38 +1028,57 @@ async fn handle_image_deployment<'a>(
     let size = deploy.size_();
     emit_histogram!(MetricEvents::ImageDownload, size as f64, url => url.to_string());
     emit_event_with_duration!(MetricEvents::ImageDownload, {
-        let resp = http_client.get(url).send().await?.error_for_status()?;
         let min = std::cmp::min(size, (config.max_image_size_mb * 1024 * 1024) as u64) as usize;
         info!("Downloading image, size {} min {}", size, min);
-        if resp.status().is_success() {
-            let stream = resp.bytes_stream();
-            let resp_data = get_body_max_size(stream, min)
-                .await
-                .map_err(|_|Risc0RunnerError::ImgTooLarge)?;
-
-            let img = Image::from_bytes(resp_data)?;
-            if let Some(bytes) = img.bytes() {
-                tokio::fs::write(Path::new(&config.risc0_image_folder).join(img.id.clone()), bytes).await?;
-            }
-            if img.id != deploy.image_id().unwrap_or_default() {
-                return Err(Risc0RunnerError::InvalidData.into());
-            }
-            loaded_images.insert(img.id.clone(), img);
+        let parsed_url = reqwest::Url::parse(url).map_err(|_| Risc0RunnerError::InvalidData)?;
+        // transient failures are retried, ipfs content is verified against its cid
+        let timeout = Duration::from_secs(config.image_download_timeout_secs as u64);
+        let resp_data = img_fetcher
+            .fetch(&parsed_url, min, timeout)
+            .await
+            .map_err(|e| match e {
+                FetchError::TooLarge => Risc0RunnerError::ImgTooLarge.into(),
+                e => anyhow::Error::from(e),
+            })?;
+
+        let img = Image::from_bytes(resp_data)?;
+        if let Some(bytes) = img.bytes() {
+            tokio::fs::write(Path::new(&config.risc0_image_folder).join(img.id.clone()), bytes).await?;
         }
+        if img.id != deploy.image_id().unwrap_or_default() {
+            return Err(Risc0RunnerError::InvalidData.into());
+        }
+        loaded_images.insert(img.id.clone(), img);
         Ok(())
     }, url => url.to_string())
 }
 
-// proving function, no async this is cpu/gpu intesive
-fn risc0_prove(
+// proving function, no async this is cpu/gpu intesive, also returns the total cycles
+pub(crate) fn risc0_prove(
     memory_image: MemoryImage,
     sorted_inputs: Vec<ProgramInput>,
-) -> Result<(Journal, Digest, SuccinctReceipt<ReceiptClaim>)> {
+    segment_pool: Option<&SegmentWorkerPool>,
+) -> Result<(Journal, Digest, SuccinctReceipt<ReceiptClaim>, u64)> {
     let image_id = memory_image.compute_id().to_string();
     let mut exec = new_risc0_exec_env(memory_image, sorted_inputs)?;
-    let session = exec.run()?;
+    let session = check_execution(exec.run())?;
     // Obtain the default prover.
     let prover = get_risc0_prover()?;
+    if let Some(pool) = segment_pool.filter(|pool| pool.accepts(&session)) {
+        let stats = session.stats();
+        emit_histogram!(MetricEvents::ProofSegments, stats.segments as f64, system => "risc0", image_id => &image_id);
+        emit_histogram!(MetricEvents::ProofCycles, stats.total_cycles as f64, system => "risc0", cycle_type => "total", image_id => &image_id);
+        emit_histogram!(MetricEvents::ProofCycles, stats.user_cycles as f64, system => "risc0", cycle_type => "user", image_id => &image_id);
+        let sr = emit_event_with_duration!(MetricEvents::ProofGeneration,{
+            pool.prove_session(prover.as_ref(), &session)
+        }, system => "risc0_segment_workers")?;
+        let journal = session
+            .journal
+            .clone()
+            .unwrap_or_else(|| Journal::new(Vec::new()));
+        let (journal, assumptions_digest, receipt) = identity_receipt(journal, sr)?;
+        return Ok((journal, assumptions_digest, receipt, stats.total_cycles));
+    }
     let ctx = VerifierContext::default();
     let info = emit_event_with_duration!(MetricEvents::ProofGeneration,{
         prover.prove_session(&ctx, &session)
@@ -688,18 +1088,72 @@ fn risc0_prove(
     emit_histogram!(MetricEvents::ProofCycles, info.stats.user_cycles as f64, system => "risc0", cycle_type => "user", image_id => &image_id);
     if let InnerReceipt::Composite(cr) = &info.receipt.inner {
         let sr = emit_event_with_duration!(MetricEvents::ProofConversion,{ prover.composite_to_succinct(cr) }, system => "risc0")?;
-        let ident_receipt = identity_p254(&sr)?;
-        if let MaybePruned::Value(rc) = sr.claim {
-            if let MaybePruned::Value(Some(op)) = rc.output {
-                if let MaybePruned::Value(ass) = op.assumptions {
-                    return Ok((info.receipt.journal, ass.digest(), ident_receipt));
-                }
+        let (journal, assumptions_digest, receipt) = identity_receipt(info.receipt.journal, sr)?;
+        return Ok((
+            journal,
+            assumptions_digest,
+            receipt,
+            info.stats.total_cycles,
+        ));
+    }
+    Err(Risc0RunnerError::ProofGenerationError.into())
+}
+
+/// Prepares the succinct receipt for groth16 compression and extracts the assumptions digest
+fn identity_receipt(
+    journal: Journal,
+    sr: SuccinctReceipt<ReceiptClaim>,
+) -> Result<(Journal, Digest, SuccinctReceipt<ReceiptClaim>)> {
+    let ident_receipt = identity_p254(&sr)?;
+    if let MaybePruned::Value(rc) = sr.claim {
+        if let MaybePruned::Value(Some(op)) = rc.output {
+            if let MaybePruned::Value(ass) = op.assumptions {
+                return Ok((journal, ass.digest(), ident_receipt));
             }
         }
     }
     Err(Risc0RunnerError::ProofGenerationError.into())
 }
 
+/// Messages risc0 uses for panics and faults of the guest itself, only matched when an error has no type to go by
+const GUEST_FAULTS: [&str; 5] = [
+    "guest panicked",
+    "illegal instruction",
+    "invalid load address",
+    "invalid store address",
+    "unaligned",
+];
+
+/// Maps the outcome of running the guest to the failure reported on-chain, a session that stopped
+/// at its cycle limit is told apart by its exit code
+fn check_execution(result: Result<Session>) -> Result<Session> {
+    match result {
+        Ok(session) if matches!(session.exit_code, ExitCode::SessionLimit) => {
+            Err(Risc0RunnerError::CycleLimitExceeded.into())
+        }
+        Ok(session) => Ok(session),
+        Err(e) => Err(classify_execution_error(e)),
+    }
+}
+
+/// Io errors are the node's failure and typed, they are never reported as the guest's. risc0 surfaces
+/// guest panics, faults and the session limit as untyped errors, so as a fallback they are told apart by
+/// their message. Should risc0 change that wording they are reported as a generic execution error.
+fn classify_execution_error(e: anyhow::Error) -> anyhow::Error {
+    let msg = e.to_string();
+    if e.chain().any(|cause| cause.is::<std::io::Error>()) {
+        return Risc0RunnerError::ExecutionError(msg).into();
+    }
+    let lower = msg.to_lowercase();
+    if lower.contains("session limit") {
+        Risc0RunnerError::CycleLimitExceeded.into()
+    } else if GUEST_FAULTS.iter().any(|fault| lower.contains(fault)) {
+        Risc0RunnerError::GuestPanic(msg).into()
+    } else {
+        Risc0RunnerError::ExecutionError(msg).into()
+    }
+}
+
 pub struct CompressedReciept {
     pub execution_digest: Vec<u8>,
     pub exit_code_system: u32,
@@ -708,7 +1162,7 @@ pub struct CompressedReciept {
 }
 /// Compresses the proof to be sent to the blockchain
 /// This is a temporary solution until the wasm groth16 prover or a rust impl is working
-async fn risc0_compress_proof(
+pub(crate) async fn risc0_compress_proof(
     tools_path: &str,
     succint_receipt: SuccinctReceipt<ReceiptClaim>,
 ) -> Result<CompressedReciept> {
@@ -777,14 +1231,162 @@ async fn risc0_compress_proof(
     }
 }
 
-fn can_execute(exec: ExecutionRequestV1) -> bool {
-    let version = exec.prover_version().try_into();
-    if version.is_ok() {
-        match version.unwrap() {
-            REQUIRED_PROVER => true,
-            _ => false,
+/// The request's prover version if this node has a prover for it
+fn supported_prover_version(provers: &Provers, exec: ExecutionRequestV1) -> Option<ProverVersion> {
+    let version: ProverVersion = exec.prover_version().try_into().ok()?;
+    provers.backend(&version).map(|_| version)
+}
+
+/// A config the runner starts with in tests, images and stark compression tools are read from `folder`
+#[cfg(test)]
+pub(crate) fn test_runner_config(folder: &Path) -> ProverNodeConfig {
+    let images = folder.join("images");
+    fs::create_dir_all(&images).unwrap();
+    for entry in utils::TOOLS_ENTRIES.iter() {
+        fs::write(folder.join(entry), b"").unwrap();
+    }
+    ProverNodeConfig {
+        risc0_image_folder: images.to_string_lossy().to_string(),
+        stark_compression_tools_path: folder.to_string_lossy().to_string(),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*, crate::transaction_sender::mock::RecordingTransactionSender,
+        bonsol_prover::input_resolver::DefaultInputResolver,
+        solana_rpc_client::nonblocking::rpc_client::RpcClient, solana_sdk::signature::Keypair,
+        std::sync::atomic::Ordering,
+    };
+
+    fn lost_claim(execution_id: &str) -> LostClaim {
+        LostClaim {
+            claim: InflightProof {
+                execution_id: execution_id.to_string(),
+                image_id: "image".to_string(),
+                status: ClaimStatus::Claiming,
+                claim_signature: Signature::default(),
+                submission_signature: None,
+                expiry: 300,
+                tip: 1000,
+                requester: Pubkey::new_unique(),
+                prover_version: VERSION_V1_2_1,
+                program_callback: None,
+                additional_accounts: vec![],
+            },
+            execution_account: Pubkey::new_unique(),
+            block_commitment: 50,
         }
-    } else {
-        false
+    }
+
+    async fn runner(folder: &Path, sender: Arc<RecordingTransactionSender>) -> Risc0Runner {
+        let input_resolver = DefaultInputResolver::new(
+            Arc::new(reqwest::Client::new()),
+            Arc::new(RpcClient::new("http://127.0.0.1:8899".to_string())),
+        );
+        Risc0Runner::new(
+            test_runner_config(folder),
+            Arc::new(Keypair::new()),
+            sender,
+            Arc::new(input_resolver),
+            None,
+        )
+        .await
+        .unwrap()
+    }
+
+    fn classified(msg: &str) -> Risc0RunnerError {
+        classify_execution_error(anyhow::anyhow!(msg.to_string()))
+            .downcast()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_classify_execution_error() {
+        assert!(matches!(
+            classified("Session limit exceeded"),
+            Risc0RunnerError::CycleLimitExceeded
+        ));
+        assert!(matches!(
+            classified("Guest panicked: index out of bounds"),
+            Risc0RunnerError::GuestPanic(_)
+        ));
+        assert!(matches!(
+            classified("Invalid load address: 0x00000000"),
+            Risc0RunnerError::GuestPanic(_)
+        ));
+        assert!(matches!(
+            classified("No such file or directory (os error 2)"),
+            Risc0RunnerError::ExecutionError(_)
+        ));
+        assert!(matches!(
+            classified("Could not parse ELF"),
+            Risc0RunnerError::ExecutionError(_)
+        ));
+    }
+
+    #[test]
+    fn test_classify_io_errors_by_type() {
+        let io = std::io::Error::new(std::io::ErrorKind::Other, "guest panicked");
+        let err = classify_execution_error(anyhow::Error::from(io).context("Loading segment"));
+        assert!(matches!(
+            err.downcast_ref::<Risc0RunnerError>(),
+            Some(Risc0RunnerError::ExecutionError(_))
+        ));
+    }
+
+    #[test]
+    fn test_failure_reasons() {
+        assert_eq!(
+            classified("Guest panicked: oops").failure_reason(),
+            FailureReason::GuestPanic
+        );
+        assert_eq!(
+            classified("Session limit exceeded").failure_reason(),
+            FailureReason::CycleLimitExceeded
+        );
+        assert_eq!(
+            classified("Could not parse ELF").failure_reason(),
+            FailureReason::Unknown
+        );
+        assert_eq!(
+            Risc0RunnerError::ProofCompressionError.failure_reason(),
+            FailureReason::CompressionFailure
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stop_halts_reclaim() {
+        let folder = tempdir().unwrap();
+        let sender = Arc::new(RecordingTransactionSender::default());
+        sender.current_block.store(100, Ordering::SeqCst);
+        sender.balance.store(1_000_000_000_000, Ordering::SeqCst);
+        let mut runner = runner(folder.path(), sender.clone()).await;
+        runner.start().unwrap();
+
+        runner
+            .lost_claims
+            .insert("before".to_string(), lost_claim("before"));
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while sender.claims.lock().unwrap().is_empty() {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        runner.stop().unwrap();
+        runner
+            .lost_claims
+            .insert("after".to_string(), lost_claim("after"));
+        tokio::time::sleep(Duration::from_millis(2500)).await;
+
+        assert_eq!(
+            *sender.claims.lock().unwrap(),
+            vec![("before".to_string(), 200)]
+        );
+        assert!(runner.lost_claims.contains_key("after"));
     }
 }
@@ -0,0 +1,563 @@
+use {
+    super::{
+        preflight::preflight_verify, publish_receipt, report_failure, risc0_compress_proof,
+        risc0_prove, shadow::ShadowRuns, wallet::Wallet, ClaimStatus, CompressedReciept,
+        InflightProof, InflightProofs, Risc0RunnerError,
+    },
+    crate::{
+        config::ProverNodeConfig,
+        ledger::{Ledger, Outcome},
+        observe::*,
+        prover_worker::ProverWorker,
+        segment_worker::SegmentWorkerPool,
+        transaction_sender::TransactionSender,
+    },
+    bonsol_interface::bonsol_schema::FailureReason,
+    bonsol_prover::{
+        input_resolver::{InputResolver, ProgramInput},
+        receipt_store::ReceiptStore,
+    },
+    bytes::Bytes,
+    dashmap::DashMap,
+    risc0_binfmt::MemoryImage,
+    risc0_zkvm::{sha::Digest, Journal, ReceiptClaim, SuccinctReceipt},
+    solana_sdk::signer::Signer,
+    std::{
+        future::Future,
+        sync::{
+            atomic::{AtomicBool, AtomicUsize, Ordering},
+            Arc, Weak,
+        },
+    },
+    tokio::{
+        sync::{
+            mpsc::{unbounded_channel, UnboundedSender},
+            Semaphore,
+        },
+        task::JoinHandle,
+    },
+    tracing::{error, info},
+};
+
+/// A claimed execution on its way through the stages
+pub struct ClaimJob {
+    pub execution_id: String,
+    pub claim: InflightProof,
+    pub program: ProofProgram,
+    pub inputs: Vec<ProgramInput>,
+}
+
+/// The guest in the form its prover takes it
+pub enum ProofProgram {
+    InProcess(MemoryImage),
+    /// Workers load the image with their own risc0 version
+    Worker {
+        worker: Arc<ProverWorker>,
+        elf: Bytes,
+    },
+}
+
+/// Receipts of workers are only read by the worker that made them
+enum ProvenReceipt {
+    InProcess(SuccinctReceipt<ReceiptClaim>),
+    Worker {
+        worker: Arc<ProverWorker>,
+        receipt: Vec<u8>,
+    },
+}
+
+struct ProvenJob {
+    execution_id: String,
+    claim: InflightProof,
+    journal: Journal,
+    assumptions_digest: Digest,
+    receipt: ProvenReceipt,
+}
+
+/// A job and the flag raised once another prover completed its execution
+struct Cancellable<T> {
+    job: T,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl<T> Cancellable<T> {
+    fn then<U>(&self, job: U) -> Cancellable<U> {
+        Cancellable {
+            job,
+            cancelled: self.cancelled.clone(),
+        }
+    }
+
+    /// Checked when the job enters a stage, work already running is not interrupted
+    fn is_cancelled(&self, stage: &'static str, execution_id: &str) -> bool {
+        if !self.cancelled.load(Ordering::SeqCst) {
+            return false;
+        }
+        info!(
+            "{} was completed by another prover, dropping it",
+            execution_id
+        );
+        emit_event!(MetricEvents::ProvingCancelled, execution_id => execution_id, stage => stage);
+        true
+    }
+}
+
+struct CompressedJob {
+    execution_id: String,
+    claim: InflightProof,
+    journal: Journal,
+    assumptions_digest: Digest,
+    compressed_receipt: CompressedReciept,
+}
+
+/// Everything the stages share
+pub struct PipelineContext {
+    pub config: Arc<ProverNodeConfig>,
+    pub transaction_sender: Arc<dyn TransactionSender>,
+    pub signer: Arc<dyn Signer + Send + Sync>,
+    pub input_resolver: Arc<dyn InputResolver + 'static>,
+    pub receipt_store: Option<Arc<dyn ReceiptStore>>,
+    pub in_flight_proofs: InflightProofs,
+    pub segment_pool: Option<Arc<SegmentWorkerPool>>,
+    pub ledger: Arc<Ledger>,
+    pub wallet: Arc<Wallet>,
+    /// Set in shadow mode, jobs then end before anything is submitted
+    pub shadow: Option<Arc<ShadowRuns>>,
+}
+
+impl PipelineContext {
+    /// Gives up on the execution and reports it on-chain so the requester is refunded
+    async fn abandon(&self, execution_id: &str, claim: &InflightProof, reason: FailureReason) {
+        self.in_flight_proofs.remove(execution_id);
+        if let Some(shadow) = &self.shadow {
+            shadow.finish(execution_id);
+            return;
+        }
+        let sig = report_failure(
+            self.transaction_sender.as_ref(),
+            execution_id,
+            claim.requester,
+            reason,
+        )
+        .await;
+        if let Some(sig) = sig {
+            self.ledger
+                .record_fee(self.transaction_sender.as_ref(), execution_id, &sig)
+                .await;
+        }
+    }
+}
+
+/// The queue in front of a stage, jobs wait here until one of the stage's workers is free
+struct StageQueue<T> {
+    stage: &'static str,
+    sender: UnboundedSender<T>,
+    depth: Arc<AtomicUsize>,
+}
+
+impl<T> StageQueue<T> {
+    fn push(&self, job: T) {
+        let depth = self.depth.fetch_add(1, Ordering::SeqCst) + 1;
+        record_queue_depth(self.stage, depth);
+        if self.sender.send(job).is_err() {
+            error!("{} stage is stopped, dropping job", self.stage);
+        }
+    }
+}
+
+fn record_queue_depth(stage: &'static str, depth: usize) {
+    let g =
+        gauge!("gauges", "gauge" => MetricEvents::PipelineQueueDepth.to_string(), "stage" => stage);
+    g.set(depth as f64);
+}
+
+/// Runs the handler for every queued job with at most `workers` jobs in progress
+fn spawn_stage<T, F, Fut>(
+    stage: &'static str,
+    workers: usize,
+    handler: F,
+) -> (StageQueue<T>, JoinHandle<()>)
+where
+    T: Send + 'static,
+    F: Fn(T) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let (sender, mut receiver) = unbounded_channel::<T>();
+    let depth = Arc::new(AtomicUsize::new(0));
+    let permits = Arc::new(Semaphore::new(workers.max(1)));
+    let handler = Arc::new(handler);
+    let queue_depth = depth.clone();
+    let handle = tokio::spawn(async move {
+        while let Some(job) = receiver.recv().await {
+            let permit = permits
+                .clone()
+                .acquire_owned()
+                .await
+                .expect("Stage semaphores are never closed");
+            let depth = queue_depth.fetch_sub(1, Ordering::SeqCst) - 1;
+            record_queue_depth(stage, depth);
+            let handler = handler.clone();
+            tokio::spawn(async move {
+                let start = Instant::now();
+                handler(job).await;
+                let duration_ms = start.elapsed().as_millis();
+                info!(
+                    event = MetricEvents::PipelineStage.to_string(),
+                    stage = stage,
+                    "Duration: {} = {} ms",
+                    stage,
+                    duration_ms
+                );
+                let h = histogram!("durations", "duration" => MetricEvents::PipelineStage.to_string(), "stage" => stage);
+                h.record(duration_ms as f64);
+                drop(permit);
+            });
+        }
+    });
+    (
+        StageQueue {
+            stage,
+            sender,
+            depth,
+        },
+        handle,
+    )
+}
+
+/// Claimed executions go through resolving private inputs, proving, compressing and submitting, each stage has
+/// its own queue and worker pool from [`crate::config::PipelineConfig`] so a slow compression does not hold
+/// back the next proof
+pub struct ClaimPipeline {
+    resolve: StageQueue<Cancellable<ClaimJob>>,
+    jobs: DashMap<String, Weak<AtomicBool>>,
+    handles: Vec<JoinHandle<()>>,
+}
+
+impl ClaimPipeline {
+    pub fn start(ctx: PipelineContext) -> Self {
+        let ctx = Arc::new(ctx);
+        let workers = ctx.config.pipeline.clone();
+
+        let c = ctx.clone();
+        let (submit, submit_handle) =
+            spawn_stage("submit", workers.submission_workers, move |job| {
+                submit_stage(c.clone(), job)
+            });
+        let (c, next) = (ctx.clone(), Arc::new(submit));
+        let (compress, compress_handle) =
+            spawn_stage("compress", workers.compression_workers, move |job| {
+                compress_stage(c.clone(), next.clone(), job)
+            });
+        let (c, next) = (ctx.clone(), Arc::new(compress));
+        let (prove, prove_handle) = spawn_stage("prove", workers.proving_workers, move |job| {
+            prove_stage(c.clone(), next.clone(), job)
+        });
+        let (c, next) = (ctx.clone(), Arc::new(prove));
+        let (resolve, resolve_handle) = spawn_stage("resolve", workers.input_workers, move |job| {
+            resolve_stage(c.clone(), next.clone(), job)
+        });
+        ClaimPipeline {
+            resolve,
+            jobs: DashMap::new(),
+            handles: vec![resolve_handle, prove_handle, compress_handle, submit_handle],
+        }
+    }
+
+    pub fn push(&self, job: ClaimJob) {
+        // a job is dropped once it leaves the last stage, the flag goes with it
+        self.jobs
+            .retain(|_, cancelled| cancelled.strong_count() > 0);
+        let cancelled = Arc::new(AtomicBool::new(false));
+        self.jobs
+            .insert(job.execution_id.clone(), Arc::downgrade(&cancelled));
+        self.resolve.push(Cancellable { job, cancelled });
+    }
+
+    /// Drops the job of the execution at the start of its next stage, false if it is not in the pipeline
+    pub fn cancel(&self, execution_id: &str) -> bool {
+        match self
+            .jobs
+            .remove(execution_id)
+            .and_then(|(_, cancelled)| cancelled.upgrade())
+        {
+            Some(cancelled) => {
+                cancelled.store(true, Ordering::SeqCst);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Stops taking jobs off the queues, jobs already in a stage run to the end of it
+    pub fn stop(&self) {
+        for handle in &self.handles {
+            handle.abort();
+        }
+    }
+}
+
+async fn resolve_stage(
+    ctx: Arc<PipelineContext>,
+    next: Arc<StageQueue<Cancellable<ClaimJob>>>,
+    mut queued: Cancellable<ClaimJob>,
+) {
+    if queued.is_cancelled("resolve", &queued.job.execution_id) {
+        return;
+    }
+    let job = &mut queued.job;
+    let execution_id = job.execution_id.as_str();
+    let unresolved_count = job
+        .inputs
+        .iter()
+        .filter(|i| matches!(i, ProgramInput::Unresolved(_)))
+        .count();
+    if unresolved_count > 0 {
+        info!("{} outstanding inputs", unresolved_count);
+        let resolved = emit_event_with_duration!(MetricEvents::InputDownload, {
+            ctx.input_resolver.resolve_private_inputs(&job.claim.requester, execution_id, &mut job.inputs, Arc::new(ctx.signer.as_ref())).await
+        }, execution_id => execution_id, stage => "private");
+        // private inputs are probed before claiming, but the server can still refuse or fail the download
+        if let Err(e) = resolved {
+            error!("Error resolving private inputs: {:?}", e);
+            ctx.abandon(
+                execution_id,
+                &job.claim,
+                FailureReason::InputDownloadFailure,
+            )
+            .await;
+            return;
+        }
+    }
+    info!("{} inputs resolved", unresolved_count);
+    next.push(queued);
+}
+
+async fn prove_stage(
+    ctx: Arc<PipelineContext>,
+    next: Arc<StageQueue<Cancellable<ProvenJob>>>,
+    queued: Cancellable<ClaimJob>,
+) {
+    if queued.is_cancelled("prove", &queued.job.execution_id) {
+        return;
+    }
+    let cancelled = queued.then(());
+    let ClaimJob {
+        execution_id,
+        claim,
+        program,
+        inputs,
+    } = queued.job;
+    let start = Instant::now();
+    let result = match program {
+        ProofProgram::InProcess(mem_image) => prove_in_process(&ctx, mem_image, inputs).await,
+        ProofProgram::Worker { worker, elf } => {
+            let proven = emit_event_with_duration!(MetricEvents::ProofGeneration, {
+                worker.prove(&elf, inputs).await
+            }, system => "risc0_prover_worker");
+            // workers do not report cycles
+            proven
+                .map(|(journal, assumptions_digest, receipt)| {
+                    (
+                        journal,
+                        assumptions_digest,
+                        ProvenReceipt::Worker { worker, receipt },
+                        None,
+                    )
+                })
+                .map_err(proving_error)
+        }
+    };
+    match result {
+        Ok((journal, assumptions_digest, receipt, cycles)) => {
+            ctx.ledger
+                .record_proving(&execution_id, cycles, start.elapsed());
+            if ctx.config.shadow.as_ref().is_some_and(|s| !s.compress) {
+                shadow_proven(&ctx, &execution_id).await;
+                return;
+            }
+            // receipts of other risc0 versions are not published, later executions could not verify them, nor are
+            // shadow receipts since the execution belongs to its claimer
+            if let (Some(receipt_store), ProvenReceipt::InProcess(receipt), None) =
+                (&ctx.receipt_store, &receipt, &ctx.shadow)
+            {
+                publish_receipt(
+                    receipt_store.as_ref(),
+                    &claim.requester,
+                    &execution_id,
+                    &journal,
+                    receipt,
+                )
+                .await;
+            }
+            next.push(cancelled.then(ProvenJob {
+                execution_id,
+                claim,
+                journal,
+                assumptions_digest,
+                receipt,
+            }));
+        }
+        Err(e) => fail(&ctx, &execution_id, &claim, e).await,
+    }
+}
+
+async fn prove_in_process(
+    ctx: &PipelineContext,
+    mem_image: MemoryImage,
+    inputs: Vec<ProgramInput>,
+) -> Result<(Journal, Digest, ProvenReceipt, Option<u64>), Risc0RunnerError> {
+    let segment_pool = ctx.segment_pool.clone();
+    tokio::task::spawn_blocking(move || {
+        risc0_prove(mem_image, inputs, segment_pool.as_deref())
+            .map(|(journal, assumptions_digest, receipt, cycles)| {
+                (
+                    journal,
+                    assumptions_digest,
+                    ProvenReceipt::InProcess(receipt),
+                    Some(cycles),
+                )
+            })
+            .map_err(proving_error)
+    })
+    .await
+    .unwrap_or(Err(Risc0RunnerError::ProofGenerationError))
+}
+
+fn proving_error(e: anyhow::Error) -> Risc0RunnerError {
+    info!("Error generating proof: {:?}", e);
+    e.downcast::<Risc0RunnerError>()
+        .unwrap_or(Risc0RunnerError::ProofGenerationError)
+}
+
+async fn compress_stage(
+    ctx: Arc<PipelineContext>,
+    next: Arc<StageQueue<Cancellable<CompressedJob>>>,
+    queued: Cancellable<ProvenJob>,
+) {
+    if queued.is_cancelled("compress", &queued.job.execution_id) {
+        return;
+    }
+    let cancelled = queued.then(());
+    let job = queued.job;
+    let tools_path = ctx.config.stark_compression_tools_path.as_str();
+    let start = Instant::now();
+    let result = match job.receipt {
+        ProvenReceipt::InProcess(receipt) => risc0_compress_proof(tools_path, receipt).await,
+        ProvenReceipt::Worker { worker, receipt } => {
+            let tools_path = worker.stark_compression_tools_path().unwrap_or(tools_path);
+            worker.compress(receipt, tools_path).await
+        }
+    };
+    match result {
+        Ok(compressed_receipt) => {
+            ctx.ledger
+                .record_compression(&job.execution_id, start.elapsed());
+            next.push(cancelled.then(CompressedJob {
+                execution_id: job.execution_id,
+                claim: job.claim,
+                journal: job.journal,
+                assumptions_digest: job.assumptions_digest,
+                compressed_receipt,
+            }))
+        }
+        Err(e) => {
+            info!("Error compressing proof: {:?}", e);
+            fail(
+                &ctx,
+                &job.execution_id,
+                &job.claim,
+                Risc0RunnerError::ProofCompressionError,
+            )
+            .await;
+        }
+    }
+}
+
+async fn submit_stage(ctx: Arc<PipelineContext>, queued: Cancellable<CompressedJob>) {
+    if queued.is_cancelled("submit", &queued.job.execution_id) {
+        return;
+    }
+    let CompressedJob {
+        execution_id,
+        mut claim,
+        journal,
+        assumptions_digest,
+        compressed_receipt,
+    } = queued.job;
+    // the program verifies the same way, a proof that fails here would only cost a status transaction
+    let verified = emit_event_with_duration!(MetricEvents::ProofPreflight, {
+        preflight_verify(&claim, &journal, &assumptions_digest, &compressed_receipt)
+    }, execution_id => &execution_id);
+    if let Err(e) = verified {
+        fail(&ctx, &execution_id, &claim, e).await;
+        return;
+    }
+    if ctx.shadow.is_some() {
+        shadow_proven(&ctx, &execution_id).await;
+        return;
+    }
+    let (input_digest, committed_outputs) = journal.bytes.split_at(32);
+    let sig = ctx
+        .transaction_sender
+        .submit_proof(
+            &execution_id,
+            claim.requester,
+            claim.program_callback.clone(),
+            &compressed_receipt.proof,
+            &compressed_receipt.execution_digest,
+            input_digest,
+            assumptions_digest.as_bytes(),
+            committed_outputs,
+            claim.additional_accounts.clone(),
+            compressed_receipt.exit_code_system,
+            compressed_receipt.exit_code_user,
+        )
+        .await;
+    match sig {
+        Ok(sig) => {
+            claim.status = ClaimStatus::Submitted;
+            claim.submission_signature = Some(sig);
+            ctx.in_flight_proofs.insert(execution_id.clone(), claim);
+            info!("Proof submitted: {:?}", sig);
+            ctx.in_flight_proofs.remove(&execution_id);
+            ctx.ledger
+                .record_fee(ctx.transaction_sender.as_ref(), &execution_id, &sig)
+                .await;
+        }
+        Err(e) => {
+            error!("Error submitting proof: {:?}", e);
+            emit_event!(MetricEvents::ProofSubmissionError, execution_id => &execution_id);
+            ctx.in_flight_proofs.remove(&execution_id);
+            ctx.wallet.release_stake(&execution_id);
+            ctx.ledger
+                .record_outcome(&execution_id, Outcome::SubmitFailed);
+        }
+    }
+}
+
+/// Ends a shadow job once its proof, compressed if configured, is ready
+async fn shadow_proven(ctx: &PipelineContext, execution_id: &str) {
+    let Some(shadow) = &ctx.shadow else {
+        return;
+    };
+    match ctx.transaction_sender.get_current_block().await {
+        Ok(current_block) => {
+            shadow.proven(execution_id, current_block);
+        }
+        Err(e) => {
+            error!("Error reading current block: {:?}", e);
+            shadow.finish(execution_id);
+        }
+    }
+}
+
+async fn fail(
+    ctx: &PipelineContext,
+    execution_id: &str,
+    claim: &InflightProof,
+    e: Risc0RunnerError,
+) {
+    info!("Error generating proof: {:?}", e);
+    emit_event!(MetricEvents::ProvingFailed, execution_id => execution_id, reason => e.to_string());
+    ctx.abandon(execution_id, claim, e.failure_reason()).await;
+}
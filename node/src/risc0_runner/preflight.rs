@@ -0,0 +1,113 @@
+use {
+    super::{CompressedReciept, InflightProof, Risc0RunnerError},
+    bonsol::proof_handling::{verify_status_proof, ProofClaim},
+    risc0_zkvm::{sha::Digest, Journal},
+    tracing::info,
+};
+
+/// Runs the bonsol program's own Groth16 check on a compressed proof, a bad seal or exit code mapping fails here
+/// instead of costing a status transaction that ends in `ExitCode::VerifyError`
+pub fn preflight_verify(
+    claim: &InflightProof,
+    journal: &Journal,
+    assumptions_digest: &Digest,
+    compressed_receipt: &CompressedReciept,
+) -> Result<(), Risc0RunnerError> {
+    if journal.bytes.len() < 32 {
+        return Err(Risc0RunnerError::PreflightVerificationFailed);
+    }
+    let (input_digest, committed_outputs) = journal.bytes.split_at(32);
+    let proof: &[u8; 256] = compressed_receipt
+        .proof
+        .as_slice()
+        .try_into()
+        .map_err(|_| Risc0RunnerError::PreflightVerificationFailed)?;
+    let proof_claim = ProofClaim {
+        image_id: &claim.image_id,
+        execution_digest: &compressed_receipt.execution_digest,
+        input_digest,
+        committed_outputs,
+        assumption_digest: assumptions_digest.as_bytes(),
+        exit_code_system: compressed_receipt.exit_code_system,
+        exit_code_user: compressed_receipt.exit_code_user,
+    };
+    match verify_status_proof(claim.prover_version.clone(), &proof_claim, proof) {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(Risc0RunnerError::PreflightVerificationFailed),
+        Err(e) => {
+            info!("Preflight verification error: {:?}", e);
+            Err(Risc0RunnerError::PreflightVerificationFailed)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::risc0_runner::ClaimStatus,
+        bonsol_interface::bonsol_schema::FailureReason,
+        bonsol_interface::prover_version::VERSION_V1_2_1,
+        solana_sdk::{pubkey::Pubkey, signature::Signature},
+    };
+
+    fn claim() -> InflightProof {
+        InflightProof {
+            execution_id: "exec".to_string(),
+            image_id: "a9e4a9a7d8d7f4d4ad4f6c5d3d4a1d6a39f5c2d4f5e8c6e2a7d3b4f1c0e9d8a7"
+                .to_string(),
+            status: ClaimStatus::Claiming,
+            claim_signature: Signature::default(),
+            submission_signature: None,
+            expiry: 1000,
+            tip: 1000,
+            requester: Pubkey::new_unique(),
+            prover_version: VERSION_V1_2_1,
+            program_callback: None,
+            additional_accounts: vec![],
+        }
+    }
+
+    fn receipt(proof: Vec<u8>) -> CompressedReciept {
+        CompressedReciept {
+            execution_digest: vec![1u8; 32],
+            exit_code_system: 0,
+            exit_code_user: 0,
+            proof,
+        }
+    }
+
+    #[test]
+    fn test_rejects_bad_seal() {
+        let journal = Journal::new(vec![2u8; 40]);
+        let result = preflight_verify(&claim(), &journal, &Digest::ZERO, &receipt(vec![7u8; 256]));
+        assert!(matches!(
+            result,
+            Err(Risc0RunnerError::PreflightVerificationFailed)
+        ));
+    }
+
+    #[test]
+    fn test_rejects_malformed_proof_and_journal() {
+        let journal = Journal::new(vec![2u8; 40]);
+        assert!(
+            preflight_verify(&claim(), &journal, &Digest::ZERO, &receipt(vec![0u8; 100])).is_err()
+        );
+        let short_journal = Journal::new(vec![2u8; 16]);
+        assert!(preflight_verify(
+            &claim(),
+            &short_journal,
+            &Digest::ZERO,
+            &receipt(vec![0u8; 256])
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_preflight_failure_is_reported_as_compression_failure() {
+        assert_eq!(
+            Risc0RunnerError::PreflightVerificationFailed.failure_reason(),
+            FailureReason::CompressionFailure
+        );
+    }
+}
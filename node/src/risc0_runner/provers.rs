@@ -0,0 +1,111 @@
+use {
+    crate::prover_worker::{ProverWorker, ProverWorkerConfig},
+    anyhow::Result,
+    bonsol_interface::prover_version::ProverVersion,
+    std::sync::Arc,
+    tracing::info,
+};
+
+/// Who proves an execution request
+#[derive(Debug, Clone)]
+pub enum ProverBackend {
+    /// The risc0 version the node is built with
+    InProcess,
+    Worker(Arc<ProverWorker>),
+}
+
+/// Picks the prover for a request by its prover version, a configured worker takes precedence over the
+/// in process prover so a node can also hand its own version to a worker
+pub struct Provers {
+    in_process: ProverVersion,
+    workers: Vec<Arc<ProverWorker>>,
+}
+
+impl Provers {
+    pub fn from_config(in_process: ProverVersion, workers: &[ProverWorkerConfig]) -> Result<Self> {
+        let workers = workers
+            .iter()
+            .map(|config| ProverWorker::from_config(config).map(Arc::new))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Provers {
+            in_process,
+            workers,
+        })
+    }
+
+    /// Asks every worker for its verifier digest, a worker built against the wrong risc0 version fails startup
+    pub async fn verify(&self) -> Result<()> {
+        for worker in &self.workers {
+            worker.verify_version().await?;
+            info!("Prover worker for {} ready", worker.version);
+        }
+        Ok(())
+    }
+
+    pub fn backend(&self, version: &ProverVersion) -> Option<ProverBackend> {
+        if let Some(worker) = self.workers.iter().find(|w| &w.version == version) {
+            return Some(ProverBackend::Worker(worker.clone()));
+        }
+        (version == &self.in_process).then_some(ProverBackend::InProcess)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        bonsol_interface::prover_version::{VERSION_V1_0_1, VERSION_V1_2_1},
+    };
+
+    fn worker_config(prover_version: &str) -> ProverWorkerConfig {
+        ProverWorkerConfig {
+            prover_version: prover_version.to_string(),
+            command: "bonsol-prover-worker".to_string(),
+            args: vec![],
+            stark_compression_tools_path: None,
+        }
+    }
+
+    #[test]
+    fn test_in_process_only() {
+        let provers = Provers::from_config(VERSION_V1_2_1, &[]).unwrap();
+
+        assert!(matches!(
+            provers.backend(&VERSION_V1_2_1),
+            Some(ProverBackend::InProcess)
+        ));
+        assert!(provers.backend(&VERSION_V1_0_1).is_none());
+        assert!(provers
+            .backend(&ProverVersion::UnsupportedVersion)
+            .is_none());
+    }
+
+    #[test]
+    fn test_worker_for_other_version() {
+        let provers = Provers::from_config(VERSION_V1_2_1, &[worker_config("V1_0_1")]).unwrap();
+
+        match provers.backend(&VERSION_V1_0_1) {
+            Some(ProverBackend::Worker(worker)) => assert_eq!(worker.version, VERSION_V1_0_1),
+            _ => panic!("expected the V1_0_1 worker"),
+        }
+        assert!(matches!(
+            provers.backend(&VERSION_V1_2_1),
+            Some(ProverBackend::InProcess)
+        ));
+    }
+
+    #[test]
+    fn test_worker_takes_precedence() {
+        let provers = Provers::from_config(VERSION_V1_2_1, &[worker_config("V1_2_1")]).unwrap();
+
+        assert!(matches!(
+            provers.backend(&VERSION_V1_2_1),
+            Some(ProverBackend::Worker(_))
+        ));
+    }
+
+    #[test]
+    fn test_unknown_worker_version() {
+        assert!(Provers::from_config(VERSION_V1_2_1, &[worker_config("V9_9_9")]).is_err());
+    }
+}
@@ -0,0 +1,252 @@
+use {
+    super::{wallet::Wallet, ClaimStatus, InflightProof, InflightProofRef, InputStagingAreaRef},
+    crate::{
+        config::ProverNodeConfig, ledger::Ledger, observe::*, transaction_sender::TransactionSender,
+    },
+    dashmap::DashMap,
+    solana_sdk::pubkey::Pubkey,
+    std::sync::Arc,
+};
+
+/// An execution another node claimed while this node was claiming it, its staged inputs are kept until the
+/// execution completes, expires or this node claims it again
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LostClaim {
+    pub claim: InflightProof,
+    pub execution_account: Pubkey,
+    /// The block the current claimer committed to prove by
+    pub block_commitment: u64,
+}
+
+pub type LostClaims = Arc<DashMap<String, LostClaim>>;
+pub type LostClaimsRef<'a> = &'a DashMap<String, LostClaim>;
+
+/// Drops a lost claim once a status for it was submitted, the execution is done either way
+pub fn forget_lost_claim(
+    lost_claims: LostClaimsRef,
+    input_staging_area: InputStagingAreaRef,
+    execution_id: &str,
+) {
+    if lost_claims.remove(execution_id).is_some() {
+        input_staging_area.remove(execution_id);
+    }
+}
+
+/// The bonsol program hands a claim to a new claimer once `current_block > block_commitment`, this claims
+/// lost executions again whose claimer is past its commitment without a status, as long as there is capacity,
+/// the wallet can fund the claim and `reclaim_missed_commitments` is set
+pub async fn reclaim_missed_commitments(
+    config: &ProverNodeConfig,
+    lost_claims: LostClaimsRef<'_>,
+    in_flight_proofs: InflightProofRef<'_>,
+    input_staging_area: InputStagingAreaRef<'_>,
+    wallet: &Wallet,
+    ledger: &Ledger,
+    transaction_sender: &dyn TransactionSender,
+    current_block: u64,
+) {
+    let missed = lost_claims
+        .iter()
+        .filter(|lost| lost.block_commitment < current_block)
+        .map(|lost| lost.value().clone())
+        .collect::<Vec<_>>();
+    for lost in missed {
+        let execution_id = lost.claim.execution_id.clone();
+        if lost.claim.expiry < current_block {
+            forget_lost_claim(lost_claims, input_staging_area, &execution_id);
+            continue;
+        }
+        if !config.reclaim_missed_commitments {
+            forget_lost_claim(lost_claims, input_staging_area, &execution_id);
+            continue;
+        }
+        if in_flight_proofs.len() >= config.maximum_concurrent_proofs as usize {
+            return;
+        }
+        if let Err(e) = wallet.reserve(
+            &config.wallet,
+            &execution_id,
+            lost.claim.tip,
+            lost.claim.expiry,
+        ) {
+            info!("Not reclaiming {}: {}", execution_id, e);
+            continue;
+        }
+        lost_claims.remove(&execution_id);
+        let block_commitment = reclaim_block_commitment(current_block, lost.claim.expiry);
+        let sig = transaction_sender
+            .claim(
+                &execution_id,
+                lost.claim.requester,
+                lost.execution_account,
+                block_commitment,
+            )
+            .await;
+        match sig {
+            Ok(sig) => {
+                // the program pays the missed claimer's stake to this node and takes the same stake back
+                ledger.record_claim(
+                    &execution_id,
+                    &lost.claim.image_id,
+                    &lost.claim.requester,
+                    lost.claim.tip,
+                    0,
+                );
+                in_flight_proofs.insert(
+                    execution_id.clone(),
+                    InflightProof {
+                        status: ClaimStatus::Claiming,
+                        claim_signature: sig,
+                        submission_signature: None,
+                        ..lost.claim
+                    },
+                );
+                emit_event!(MetricEvents::ClaimRetry, execution_id => execution_id, missed_commitment => lost.block_commitment);
+            }
+            Err(e) => {
+                info!("Error reclaiming: {:?}", e);
+                wallet.release_stake(&execution_id);
+                input_staging_area.remove(&execution_id);
+            }
+        }
+    }
+}
+
+/// Commits to half of the blocks left before the execution expires, like a first claim does
+fn reclaim_block_commitment(current_block: u64, expiry: u64) -> u64 {
+    current_block + expiry.saturating_sub(current_block) / 2
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*, crate::transaction_sender::mock::RecordingTransactionSender,
+        bonsol_interface::prover_version::VERSION_V1_2_1, solana_sdk::signature::Signature,
+    };
+
+    fn lost_claim(execution_id: &str, block_commitment: u64, expiry: u64) -> LostClaim {
+        LostClaim {
+            claim: InflightProof {
+                execution_id: execution_id.to_string(),
+                image_id: "image".to_string(),
+                status: ClaimStatus::Claiming,
+                claim_signature: Signature::default(),
+                submission_signature: None,
+                expiry,
+                tip: 1000,
+                requester: Pubkey::new_unique(),
+                prover_version: VERSION_V1_2_1,
+                program_callback: None,
+                additional_accounts: vec![],
+            },
+            execution_account: Pubkey::new_unique(),
+            block_commitment,
+        }
+    }
+
+    fn lost_claims(claims: &[LostClaim]) -> DashMap<String, LostClaim> {
+        let map = DashMap::new();
+        for claim in claims {
+            map.insert(claim.claim.execution_id.clone(), claim.clone());
+        }
+        map
+    }
+
+    #[tokio::test]
+    async fn test_reclaims_missed_commitment() {
+        let config = ProverNodeConfig::default();
+        let lost = lost_claims(&[
+            lost_claim("missed", 100, 300),
+            lost_claim("active", 150, 300),
+        ]);
+        let in_flight = DashMap::new();
+        let staged = DashMap::new();
+        staged.insert("missed".to_string(), vec![]);
+        let sender = RecordingTransactionSender::default();
+        let wallet = Wallet::from_config(&config.wallet).unwrap();
+        let ledger = Ledger::from_config(None).unwrap();
+
+        reclaim_missed_commitments(
+            &config, &lost, &in_flight, &staged, &wallet, &ledger, &sender, 101,
+        )
+        .await;
+
+        assert_eq!(
+            *sender.claims.lock().unwrap(),
+            vec![("missed".to_string(), 200)]
+        );
+        assert_eq!(
+            in_flight.get("missed").unwrap().status,
+            ClaimStatus::Claiming
+        );
+        assert!(staged.contains_key("missed"));
+        assert!(!lost.contains_key("missed"));
+        assert!(lost.contains_key("active"));
+    }
+
+    #[tokio::test]
+    async fn test_drops_expired_lost_claims() {
+        let config = ProverNodeConfig::default();
+        let lost = lost_claims(&[lost_claim("expired", 100, 120)]);
+        let in_flight = DashMap::new();
+        let staged = DashMap::new();
+        staged.insert("expired".to_string(), vec![]);
+        let sender = RecordingTransactionSender::default();
+        let wallet = Wallet::from_config(&config.wallet).unwrap();
+        let ledger = Ledger::from_config(None).unwrap();
+
+        reclaim_missed_commitments(
+            &config, &lost, &in_flight, &staged, &wallet, &ledger, &sender, 121,
+        )
+        .await;
+
+        assert!(sender.claims.lock().unwrap().is_empty());
+        assert!(lost.is_empty());
+        assert!(staged.is_empty());
+        assert!(in_flight.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_respects_capacity() {
+        let config = ProverNodeConfig {
+            maximum_concurrent_proofs: 1,
+            ..Default::default()
+        };
+        let lost = lost_claims(&[lost_claim("missed", 100, 300)]);
+        let in_flight = DashMap::new();
+        let busy = lost_claim("busy", 0, 300).claim;
+        in_flight.insert("busy".to_string(), busy);
+        let staged = DashMap::new();
+        let sender = RecordingTransactionSender::default();
+        let wallet = Wallet::from_config(&config.wallet).unwrap();
+        let ledger = Ledger::from_config(None).unwrap();
+
+        reclaim_missed_commitments(
+            &config, &lost, &in_flight, &staged, &wallet, &ledger, &sender, 101,
+        )
+        .await;
+
+        assert!(sender.claims.lock().unwrap().is_empty());
+        assert!(lost.contains_key("missed"));
+    }
+
+    #[tokio::test]
+    async fn test_skips_unfunded_reclaim() {
+        let config = ProverNodeConfig::default();
+        let lost = lost_claims(&[lost_claim("missed", 100, 300)]);
+        let in_flight = DashMap::new();
+        let staged = DashMap::new();
+        let sender = RecordingTransactionSender::default();
+        let wallet = Wallet::from_config(&config.wallet).unwrap();
+        let ledger = Ledger::from_config(None).unwrap();
+        wallet.refresh(&config.wallet, &sender, 101).await;
+
+        reclaim_missed_commitments(
+            &config, &lost, &in_flight, &staged, &wallet, &ledger, &sender, 101,
+        )
+        .await;
+
+        assert!(sender.claims.lock().unwrap().is_empty());
+        assert!(lost.contains_key("missed"));
+    }
+}
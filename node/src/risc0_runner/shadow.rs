@@ -0,0 +1,256 @@
+use {super::market::CompletedExecution, crate::observe::*, dashmap::DashMap, std::time::Duration};
+
+/// How the shadow proof of an execution compared to the prover that completed it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum_macros::Display)]
+pub enum ShadowOutcome {
+    /// Proven before the winning prover's status landed
+    Faster,
+    Slower,
+    /// Proven, but nobody completed the execution
+    Unclaimed,
+    /// Proven after the execution expired
+    TooLate,
+    Failed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ShadowProof {
+    latency: Duration,
+    latency_blocks: u64,
+    block: u64,
+}
+
+struct ShadowRun {
+    image_id: String,
+    expiry: u64,
+    block: u64,
+    requested_at: Instant,
+    proof: Option<ShadowProof>,
+    winner: Option<CompletedExecution>,
+}
+
+/// The comparison reported once both the shadow proof and the winning status are known, or the execution expired
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShadowReport {
+    pub execution_id: String,
+    pub image_id: String,
+    pub outcome: ShadowOutcome,
+    pub latency: Option<Duration>,
+    pub latency_blocks: Option<u64>,
+    /// Blocks left before the expiry when the proof was ready, negative if it was too late
+    pub expiry_margin_blocks: Option<i64>,
+    pub winner: Option<CompletedExecution>,
+}
+
+/// Shadow proofs on their way through the pipeline and the winners they are compared with
+#[derive(Default)]
+pub struct ShadowRuns {
+    runs: DashMap<String, ShadowRun>,
+}
+
+impl ShadowRuns {
+    /// `requested_at` and `block` are when the node saw the request, like the winner's latency
+    pub fn start(
+        &self,
+        execution_id: &str,
+        image_id: &str,
+        expiry: u64,
+        block: u64,
+        requested_at: Instant,
+    ) {
+        self.runs.insert(
+            execution_id.to_string(),
+            ShadowRun {
+                image_id: image_id.to_string(),
+                expiry,
+                block,
+                requested_at,
+                proof: None,
+                winner: None,
+            },
+        );
+    }
+
+    /// Runs still being proven, they take up the node's capacity like claims do
+    pub fn in_progress(&self) -> usize {
+        self.runs.iter().filter(|run| run.proof.is_none()).count()
+    }
+
+    pub fn proven(&self, execution_id: &str, current_block: u64) -> Option<ShadowReport> {
+        let report = {
+            let mut run = self.runs.get_mut(execution_id)?;
+            let proof = ShadowProof {
+                latency: run.requested_at.elapsed(),
+                latency_blocks: current_block.saturating_sub(run.block),
+                block: current_block,
+            };
+            emit_histogram!(MetricEvents::ShadowProof, proof.latency.as_millis() as f64, image_id => run.image_id.clone());
+            run.proof = Some(proof);
+            if run.winner.is_none() && current_block <= run.expiry {
+                return None;
+            }
+            report(execution_id, &run)
+        };
+        self.runs.remove(execution_id);
+        Some(report)
+    }
+
+    /// Ends the run, a run without a proof is reported as failed
+    pub fn finish(&self, execution_id: &str) -> Option<ShadowReport> {
+        let (_, run) = self.runs.remove(execution_id)?;
+        Some(report(execution_id, &run))
+    }
+
+    /// The status of the prover that completed the execution
+    pub fn winner(&self, execution_id: &str, winner: CompletedExecution) -> Option<ShadowReport> {
+        let report = {
+            let mut run = self.runs.get_mut(execution_id)?;
+            run.winner = Some(winner);
+            run.proof?;
+            report(execution_id, &run)
+        };
+        self.runs.remove(execution_id);
+        Some(report)
+    }
+
+    /// Reports the runs of expired executions, whether or not they were proven
+    pub fn expire(&self, current_block: u64) -> Vec<ShadowReport> {
+        let expired = self
+            .runs
+            .iter()
+            .filter(|run| run.expiry < current_block)
+            .map(|run| run.key().clone())
+            .collect::<Vec<_>>();
+        expired
+            .iter()
+            .filter_map(|execution_id| self.finish(execution_id))
+            .collect()
+    }
+}
+
+fn report(execution_id: &str, run: &ShadowRun) -> ShadowReport {
+    let outcome = match (&run.proof, &run.winner) {
+        (None, _) => ShadowOutcome::Failed,
+        (Some(proof), _) if proof.block > run.expiry => ShadowOutcome::TooLate,
+        (Some(_), None) => ShadowOutcome::Unclaimed,
+        (Some(proof), Some(winner)) if proof.latency_blocks < winner.latency_blocks => {
+            ShadowOutcome::Faster
+        }
+        (Some(proof), Some(winner))
+            if proof.latency_blocks == winner.latency_blocks && proof.latency < winner.latency =>
+        {
+            ShadowOutcome::Faster
+        }
+        _ => ShadowOutcome::Slower,
+    };
+    let report = ShadowReport {
+        execution_id: execution_id.to_string(),
+        image_id: run.image_id.clone(),
+        outcome,
+        latency: run.proof.map(|p| p.latency),
+        latency_blocks: run.proof.map(|p| p.latency_blocks),
+        expiry_margin_blocks: run.proof.map(|p| run.expiry as i64 - p.block as i64),
+        winner: run.winner.clone(),
+    };
+    record(&report);
+    report
+}
+
+fn record(report: &ShadowReport) {
+    info!(
+        event = MetricEvents::ShadowRun.to_string(),
+        execution_id = &report.execution_id,
+        image_id = &report.image_id,
+        outcome = report.outcome.to_string(),
+        latency_ms = report.latency.map(|l| l.as_millis() as u64),
+        latency_blocks = report.latency_blocks,
+        expiry_margin_blocks = report.expiry_margin_blocks,
+        winner = report.winner.as_ref().map(|w| w.prover.to_string()),
+        winner_latency_ms = report.winner.as_ref().map(|w| w.latency.as_millis() as u64),
+        winner_latency_blocks = report.winner.as_ref().map(|w| w.latency_blocks),
+        "Shadow run {}: {}",
+        report.execution_id,
+        report.outcome
+    );
+    let c = counter!("counters", "counter" => MetricEvents::ShadowRun.to_string(), "image_id" => report.image_id.clone(), "outcome" => report.outcome.to_string());
+    c.increment(1);
+    if let Some(margin) = report.expiry_margin_blocks {
+        let h = histogram!("histograms", "histogram" => MetricEvents::ShadowExpiryMargin.to_string(), "image_id" => report.image_id.clone());
+        h.record(margin as f64);
+    }
+    if let (Some(latency), Some(winner)) = (report.latency, &report.winner) {
+        let delta = latency.as_millis() as f64 - winner.latency.as_millis() as f64;
+        let h = histogram!("histograms", "histogram" => MetricEvents::ShadowLatencyDelta.to_string(), "image_id" => report.image_id.clone());
+        h.record(delta);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, solana_sdk::pubkey::Pubkey};
+
+    fn winner(latency_blocks: u64) -> CompletedExecution {
+        CompletedExecution {
+            image_id: "image".to_string(),
+            prover: Pubkey::new_unique(),
+            latency: Duration::from_secs(60),
+            latency_blocks,
+        }
+    }
+
+    #[test]
+    fn test_proven_before_winner() {
+        let shadow = ShadowRuns::default();
+        shadow.start("exec", "image", 500, 100, Instant::now());
+        assert_eq!(shadow.in_progress(), 1);
+
+        assert!(shadow.proven("exec", 120).is_none());
+        assert_eq!(shadow.in_progress(), 0);
+        let report = shadow.winner("exec", winner(40)).unwrap();
+
+        assert_eq!(report.outcome, ShadowOutcome::Faster);
+        assert_eq!(report.latency_blocks, Some(20));
+        assert_eq!(report.expiry_margin_blocks, Some(380));
+        assert!(shadow.runs.is_empty());
+    }
+
+    #[test]
+    fn test_proven_after_winner() {
+        let shadow = ShadowRuns::default();
+        shadow.start("exec", "image", 500, 100, Instant::now());
+
+        assert!(shadow.winner("exec", winner(10)).is_none());
+        let report = shadow.proven("exec", 130).unwrap();
+
+        assert_eq!(report.outcome, ShadowOutcome::Slower);
+        assert_eq!(report.winner.unwrap().latency_blocks, 10);
+    }
+
+    #[test]
+    fn test_expired_runs() {
+        let shadow = ShadowRuns::default();
+        shadow.start("unclaimed", "image", 200, 100, Instant::now());
+        shadow.start("unproven", "image", 200, 100, Instant::now());
+        shadow.start("pending", "image", 500, 100, Instant::now());
+        shadow.proven("unclaimed", 150);
+
+        let mut reports = shadow.expire(201);
+        reports.sort_by(|a, b| a.execution_id.cmp(&b.execution_id));
+
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[0].outcome, ShadowOutcome::Unclaimed);
+        assert_eq!(reports[1].outcome, ShadowOutcome::Failed);
+        assert_eq!(shadow.in_progress(), 1);
+    }
+
+    #[test]
+    fn test_proven_too_late() {
+        let shadow = ShadowRuns::default();
+        shadow.start("exec", "image", 200, 100, Instant::now());
+
+        let report = shadow.proven("exec", 210).unwrap();
+
+        assert_eq!(report.outcome, ShadowOutcome::TooLate);
+        assert_eq!(report.expiry_margin_blocks, Some(-10));
+    }
+}
@@ -12,7 +12,7 @@ use {
     tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
 };
 
-static TOOLS_ENTRIES: [&str; 4] = [
+pub static TOOLS_ENTRIES: [&str; 4] = [
     "stark_verify",
     "stark_verify.dat",
     "stark_verify_final.zkey",
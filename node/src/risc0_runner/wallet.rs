@@ -0,0 +1,339 @@
+use {
+    crate::{
+        config::{SignerConfig, WalletConfig},
+        observe::*,
+        transaction_sender::TransactionSender,
+    },
+    anyhow::Result,
+    dashmap::DashMap,
+    solana_sdk::signature::{read_keypair_file, Keypair},
+    std::{
+        sync::{
+            atomic::{AtomicBool, AtomicU64, Ordering},
+            Mutex,
+        },
+        time::Duration,
+    },
+    thiserror::Error,
+    tracing::{error, warn},
+};
+
+/// Balance not read yet, claims are left to the bonsol program's own check until it is
+const UNKNOWN_BALANCE: u64 = u64::MAX;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum UnfundedClaim {
+    #[error("Claiming is paused, balance {balance} is below the reserve {reserve}")]
+    Paused { balance: u64, reserve: u64 },
+    #[error("Balance {balance} cannot cover tip {tip} and keep the reserve {reserve}")]
+    InsufficientBalance {
+        balance: u64,
+        tip: u64,
+        reserve: u64,
+    },
+}
+
+struct LockedStake {
+    stake: u64,
+    expiry: u64,
+}
+
+/// The node's balance and the stake it holds in open claims. The bonsol program needs the claimer to hold
+/// the tip and moves half of it into the claim as stake
+pub struct Wallet {
+    balance: AtomicU64,
+    stakes: DashMap<String, LockedStake>,
+    paused: AtomicBool,
+    last_refresh: Mutex<Option<Instant>>,
+    funder: Option<Keypair>,
+}
+
+impl Wallet {
+    pub fn from_config(config: &WalletConfig) -> Result<Self> {
+        let funder = config
+            .top_up
+            .as_ref()
+            .map(|top_up| match &top_up.funding_signer {
+                SignerConfig::KeypairFile { path } => read_keypair_file(path)
+                    .map_err(|e| anyhow::anyhow!("Invalid funding keypair {}: {:?}", path, e)),
+            })
+            .transpose()?;
+        Ok(Wallet {
+            balance: AtomicU64::new(UNKNOWN_BALANCE),
+            stakes: DashMap::new(),
+            paused: AtomicBool::new(false),
+            last_refresh: Mutex::new(None),
+            funder,
+        })
+    }
+
+    /// Checks a claim for this tip can be funded without going below the reserve
+    pub fn check_claim(&self, config: &WalletConfig, tip: u64) -> Result<(), UnfundedClaim> {
+        check_funds(
+            self.balance.load(Ordering::SeqCst),
+            tip,
+            config.reserve_lamports,
+        )
+    }
+
+    /// Checks and holds the stake of a claim about to be sent in one step, so concurrent claims cannot all pass
+    /// the check against the same balance. The balance is lowered right away, release the stake if the claim
+    /// cannot be sent
+    pub fn reserve(
+        &self,
+        config: &WalletConfig,
+        execution_id: &str,
+        tip: u64,
+        expiry: u64,
+    ) -> Result<(), UnfundedClaim> {
+        let stake = stake_for(tip);
+        let mut refused = None;
+        let _ = self
+            .balance
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |balance| {
+                refused = check_funds(balance, tip, config.reserve_lamports).err();
+                match refused {
+                    Some(_) => None,
+                    None if balance == UNKNOWN_BALANCE => Some(balance),
+                    None => Some(balance - stake),
+                }
+            });
+        if let Some(e) = refused {
+            return Err(e);
+        }
+        self.stakes
+            .insert(execution_id.to_string(), LockedStake { stake, expiry });
+        self.record();
+        Ok(())
+    }
+
+    /// The claim failed, was taken over or its execution ended
+    pub fn release_stake(&self, execution_id: &str) {
+        if self.stakes.remove(execution_id).is_some() {
+            self.record();
+        }
+    }
+
+    pub fn locked_stake(&self) -> u64 {
+        self.stakes.iter().map(|s| s.stake).sum()
+    }
+
+    /// Reads the balance every `refresh_secs`, drops stakes of expired executions and tops up from the
+    /// funding signer when the balance is low
+    pub async fn refresh(
+        &self,
+        config: &WalletConfig,
+        transaction_sender: &dyn TransactionSender,
+        current_block: u64,
+    ) {
+        {
+            let mut last_refresh = self.last_refresh.lock().unwrap();
+            if last_refresh.is_some_and(|t| t.elapsed() < Duration::from_secs(config.refresh_secs))
+            {
+                return;
+            }
+            *last_refresh = Some(Instant::now());
+        }
+        self.stakes.retain(|_, s| s.expiry >= current_block);
+        let balance = match transaction_sender.get_balance().await {
+            Ok(balance) => balance,
+            Err(e) => {
+                error!("Error reading balance: {:?}", e);
+                return;
+            }
+        };
+        self.balance.store(balance, Ordering::SeqCst);
+        let paused = balance < config.reserve_lamports;
+        if paused != self.paused.swap(paused, Ordering::SeqCst) {
+            if paused {
+                warn!(
+                    "Balance {} is below the reserve {}, claiming paused",
+                    balance, config.reserve_lamports
+                );
+            } else {
+                info!("Balance {} is above the reserve, claiming resumed", balance);
+            }
+        }
+        self.record();
+        if let (Some(top_up), Some(funder)) = (&config.top_up, &self.funder) {
+            if balance < top_up.below_lamports {
+                match transaction_sender
+                    .top_up(funder, top_up.amount_lamports)
+                    .await
+                {
+                    Ok(sig) => {
+                        emit_event!(MetricEvents::WalletTopUp, lamports => top_up.amount_lamports, signature => sig.to_string());
+                    }
+                    Err(e) => error!("Error topping up the node: {:?}", e),
+                }
+            }
+        }
+    }
+
+    fn record(&self) {
+        let balance = self.balance.load(Ordering::SeqCst);
+        if balance != UNKNOWN_BALANCE {
+            let g = gauge!("gauges", "gauge" => MetricEvents::WalletBalance.to_string());
+            g.set(balance as f64);
+        }
+        let g = gauge!("gauges", "gauge" => MetricEvents::LockedStake.to_string());
+        g.set(self.locked_stake() as f64);
+        let g = gauge!("gauges", "gauge" => MetricEvents::ClaimingPaused.to_string());
+        g.set(u8::from(self.paused.load(Ordering::SeqCst)) as f64);
+    }
+}
+
+const fn check_funds(balance: u64, tip: u64, reserve: u64) -> Result<(), UnfundedClaim> {
+    if balance == UNKNOWN_BALANCE {
+        return Ok(());
+    }
+    if balance < reserve {
+        return Err(UnfundedClaim::Paused { balance, reserve });
+    }
+    if balance < tip || balance - stake_for(tip) < reserve {
+        return Err(UnfundedClaim::InsufficientBalance {
+            balance,
+            tip,
+            reserve,
+        });
+    }
+    Ok(())
+}
+
+/// The stake the bonsol program takes from the claimer
+pub const fn stake_for(tip: u64) -> u64 {
+    tip / 2
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::{config::TopUpConfig, transaction_sender::mock::RecordingTransactionSender},
+        solana_sdk::signature::write_keypair_file,
+    };
+
+    fn config() -> WalletConfig {
+        WalletConfig {
+            reserve_lamports: 1000,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_unknown_balance_allows_claims() {
+        let wallet = Wallet::from_config(&config()).unwrap();
+
+        assert!(wallet.check_claim(&config(), 1_000_000).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_refuses_claims_it_cannot_fund() {
+        let wallet = Wallet::from_config(&config()).unwrap();
+        let sender = RecordingTransactionSender::default();
+        sender.balance.store(1500, Ordering::SeqCst);
+        wallet.refresh(&config(), &sender, 100).await;
+
+        assert!(wallet.check_claim(&config(), 1000).is_ok());
+        // the stake would take the balance below the reserve
+        assert_eq!(
+            wallet.check_claim(&config(), 1200),
+            Err(UnfundedClaim::InsufficientBalance {
+                balance: 1500,
+                tip: 1200,
+                reserve: 1000
+            })
+        );
+        assert!(wallet.check_claim(&config(), 2000).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_pauses_below_reserve() {
+        let wallet = Wallet::from_config(&config()).unwrap();
+        let sender = RecordingTransactionSender::default();
+        sender.balance.store(900, Ordering::SeqCst);
+        wallet.refresh(&config(), &sender, 100).await;
+
+        assert_eq!(
+            wallet.check_claim(&config(), 0),
+            Err(UnfundedClaim::Paused {
+                balance: 900,
+                reserve: 1000
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_locks_and_releases_stake() {
+        let wallet = Wallet::from_config(&config()).unwrap();
+        let sender = RecordingTransactionSender::default();
+        sender.balance.store(10_000, Ordering::SeqCst);
+        wallet.refresh(&config(), &sender, 100).await;
+
+        wallet.reserve(&config(), "a", 4000, 200).unwrap();
+        wallet.reserve(&config(), "b", 2000, 120).unwrap();
+        assert_eq!(wallet.locked_stake(), 3000);
+        assert_eq!(wallet.balance.load(Ordering::SeqCst), 7000);
+
+        wallet.release_stake("a");
+        assert_eq!(wallet.locked_stake(), 1000);
+
+        let config = WalletConfig {
+            refresh_secs: 0,
+            ..config()
+        };
+        wallet.refresh(&config, &sender, 121).await;
+        assert_eq!(wallet.locked_stake(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_reservations_share_the_balance() {
+        let wallet = Wallet::from_config(&config()).unwrap();
+        let sender = RecordingTransactionSender::default();
+        sender.balance.store(10_000, Ordering::SeqCst);
+        wallet.refresh(&config(), &sender, 100).await;
+
+        let reserved = std::thread::scope(|scope| {
+            let handles = (0..50)
+                .map(|i| {
+                    let wallet = &wallet;
+                    scope.spawn(move || wallet.reserve(&config(), &i.to_string(), 2000, 200))
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .map(|h| h.join().unwrap())
+                .filter(Result::is_ok)
+                .count()
+        });
+
+        // every reservation locks 1000 and needs 2000 on hand with 1000 left over
+        assert_eq!(reserved, 9);
+        assert_eq!(wallet.locked_stake(), 9000);
+        assert_eq!(wallet.balance.load(Ordering::SeqCst), 1000);
+    }
+
+    #[tokio::test]
+    async fn test_tops_up_below_threshold() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("funder.json");
+        write_keypair_file(&Keypair::new(), &path).unwrap();
+        let config = WalletConfig {
+            top_up: Some(TopUpConfig {
+                funding_signer: SignerConfig::KeypairFile {
+                    path: path.to_string_lossy().to_string(),
+                },
+                below_lamports: 2000,
+                amount_lamports: 5000,
+            }),
+            ..config()
+        };
+        let wallet = Wallet::from_config(&config).unwrap();
+        let sender = RecordingTransactionSender::default();
+        sender.balance.store(1500, Ordering::SeqCst);
+
+        wallet.refresh(&config, &sender, 100).await;
+
+        assert_eq!(*sender.top_ups.lock().unwrap(), vec![5000]);
+    }
+}
@@ -0,0 +1,469 @@
+use {
+    crate::observe::*,
+    anyhow::Result,
+    bonsol_prover::prover::get_risc0_prover,
+    risc0_zkvm::{
+        sha::Digestible, ProverServer, ReceiptClaim, Segment, Session, SuccinctReceipt,
+        VerifierContext,
+    },
+    serde::{de::DeserializeOwned, Deserialize, Serialize},
+    std::{
+        collections::VecDeque,
+        io::{ErrorKind, Read, Write},
+        net::{TcpListener, TcpStream, ToSocketAddrs},
+        sync::Mutex,
+        time::Duration,
+    },
+    thiserror::Error,
+    tracing::{error, info, warn},
+};
+
+/// Segments carry the guest memory, frames above this are refused instead of allocated
+const MAX_FRAME_SIZE: u32 = 1 << 30;
+/// Until a worker with a token has authenticated the node it only reads frames big enough for the token
+const MAX_AUTH_FRAME_SIZE: u32 = 1024;
+/// Workers listen on localhost unless told otherwise, segments leave the node in plaintext
+pub const DEFAULT_LISTEN_ADDRESS: &str = "127.0.0.1:7071";
+
+#[derive(Debug, Error)]
+pub enum SegmentWorkerError {
+    #[error("Frame of {0} bytes exceeds the maximum frame size")]
+    FrameTooLarge(u32),
+    #[error("Connection closed by the worker")]
+    ConnectionClosed,
+    #[error("Worker failed to prove the segment: {0}")]
+    Worker(String),
+    #[error("Worker address {0} does not resolve")]
+    InvalidAddress(String),
+    #[error("Session has no segments")]
+    NoSegments,
+    #[error("Segment {0} was not proven")]
+    MissingSegment(usize),
+    #[error("Joined receipt claim does not match the session")]
+    ClaimMismatch,
+    #[error("Worker token does not match")]
+    Unauthorized,
+}
+
+/// Requests the node sends over a worker connection, a connection is reused for many segments
+/// The node sends borrowed segments and the worker reads owned ones, both encode the same
+#[derive(Serialize, Deserialize)]
+enum WorkerRequest<S> {
+    /// First request of a connection to a worker that has a token
+    Authenticate(String),
+    ProveSegment(S),
+}
+
+#[derive(Serialize, Deserialize)]
+enum WorkerResponse {
+    Authenticated,
+    /// The segment receipt lifted into the recursion circuit so the node can join it
+    Lifted(Box<SuccinctReceipt<ReceiptClaim>>),
+    Error(String),
+}
+
+/// Frames are a little endian u32 length followed by the bincode message
+fn write_frame<T: Serialize>(stream: &mut impl Write, msg: &T) -> Result<()> {
+    let data = bincode::serialize(msg)?;
+    let len = u32::try_from(data.len())
+        .ok()
+        .filter(|len| *len <= MAX_FRAME_SIZE)
+        .ok_or(SegmentWorkerError::FrameTooLarge(u32::MAX))?;
+    stream.write_all(&len.to_le_bytes())?;
+    stream.write_all(&data)?;
+    stream.flush()?;
+    Ok(())
+}
+
+/// Returns None if the peer closed the connection between frames
+fn read_frame<T: DeserializeOwned>(stream: &mut impl Read, max_size: u32) -> Result<Option<T>> {
+    let mut len = [0u8; 4];
+    match stream.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+    let len = u32::from_le_bytes(len);
+    if len > max_size {
+        return Err(SegmentWorkerError::FrameTooLarge(len).into());
+    }
+    let mut data = vec![0u8; len as usize];
+    stream.read_exact(&mut data)?;
+    Ok(Some(bincode::deserialize(&data)?))
+}
+
+/// Serves segment proving requests, every connection is handled on its own thread
+/// With a token set a connection must authenticate before it may send segments
+pub fn serve(listener: TcpListener, token: Option<String>) -> Result<()> {
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let token = token.clone();
+        std::thread::spawn(move || {
+            let peer = stream
+                .peer_addr()
+                .map(|a| a.to_string())
+                .unwrap_or_default();
+            info!("Node connected from {}", peer);
+            if let Err(e) = serve_connection(stream, token.as_deref()) {
+                error!("Connection from {} failed: {:?}", peer, e);
+            }
+        });
+    }
+    Ok(())
+}
+
+fn serve_connection(mut stream: TcpStream, token: Option<&str>) -> Result<()> {
+    if let Some(token) = token {
+        authenticate_peer(&mut stream, token)?;
+    }
+    let prover = get_risc0_prover()?;
+    let ctx = VerifierContext::default();
+    while let Some(request) = read_frame::<WorkerRequest<Segment>>(&mut stream, MAX_FRAME_SIZE)? {
+        let response = match request {
+            WorkerRequest::Authenticate(_) => WorkerResponse::Authenticated,
+            WorkerRequest::ProveSegment(segment) => {
+                match prove_segment(prover.as_ref(), &ctx, &segment) {
+                    Ok(lifted) => WorkerResponse::Lifted(Box::new(lifted)),
+                    Err(e) => WorkerResponse::Error(e.to_string()),
+                }
+            }
+        };
+        write_frame(&mut stream, &response)?;
+    }
+    Ok(())
+}
+
+/// Reads the first frame of a connection, which must carry the worker's token
+fn authenticate_peer(stream: &mut (impl Read + Write), token: &str) -> Result<()> {
+    let authenticated = matches!(
+        read_frame::<WorkerRequest<()>>(stream, MAX_AUTH_FRAME_SIZE)?,
+        Some(WorkerRequest::Authenticate(t)) if tokens_match(&t, token)
+    );
+    if !authenticated {
+        write_frame(
+            stream,
+            &WorkerResponse::Error(SegmentWorkerError::Unauthorized.to_string()),
+        )?;
+        return Err(SegmentWorkerError::Unauthorized.into());
+    }
+    write_frame(stream, &WorkerResponse::Authenticated)
+}
+
+/// Sends the token of the pool as the first request of a connection
+fn authenticate(stream: &mut (impl Read + Write), token: &str) -> Result<()> {
+    write_frame(
+        stream,
+        &WorkerRequest::<()>::Authenticate(token.to_string()),
+    )?;
+    match read_frame::<WorkerResponse>(stream, MAX_FRAME_SIZE)?
+        .ok_or(SegmentWorkerError::ConnectionClosed)?
+    {
+        WorkerResponse::Authenticated => Ok(()),
+        _ => Err(SegmentWorkerError::Unauthorized.into()),
+    }
+}
+
+/// Compares in constant time so the token can not be guessed byte by byte
+fn tokens_match(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |acc, (x, y)| acc | (x ^ y))
+            == 0
+}
+
+fn prove_segment(
+    prover: &dyn ProverServer,
+    ctx: &VerifierContext,
+    segment: &Segment,
+) -> Result<SuccinctReceipt<ReceiptClaim>> {
+    let receipt = prover.prove_segment(ctx, segment)?;
+    prover.lift(&receipt)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SegmentWorkersConfig {
+    /// `host:port` of `bonsol-segment-worker` processes, empty proves every session on the node
+    pub workers: Vec<String>,
+    /// Shared with the workers, which require it when they listen beyond localhost
+    pub token: Option<String>,
+    /// Sessions with fewer segments are proven on the node, sending them is not worth it
+    pub min_segments: usize,
+    pub connect_timeout_secs: u64,
+    /// How long a worker may take for a single segment before the node proves it itself
+    pub segment_timeout_secs: u64,
+}
+
+impl Default for SegmentWorkersConfig {
+    fn default() -> Self {
+        SegmentWorkersConfig {
+            workers: Vec::new(),
+            token: None,
+            min_segments: 2,
+            connect_timeout_secs: 5,
+            segment_timeout_secs: 600,
+        }
+    }
+}
+
+/// Proves the segments of a session on a pool of worker processes and joins the lifted receipts on the node
+/// Segments a worker fails on go back to the queue, whatever no worker could prove is proven locally
+pub struct SegmentWorkerPool {
+    config: SegmentWorkersConfig,
+}
+
+impl SegmentWorkerPool {
+    /// Returns None if no workers are configured
+    pub fn from_config(config: &SegmentWorkersConfig) -> Option<Self> {
+        (!config.workers.is_empty()).then(|| SegmentWorkerPool {
+            config: config.clone(),
+        })
+    }
+
+    /// Composed sessions are proven locally, their assumptions are resolved by `composite_to_succinct`
+    pub fn accepts(&self, session: &Session) -> bool {
+        session.segments.len() >= self.config.min_segments && session.assumptions.is_empty()
+    }
+
+    /// Proves the session into the same succinct receipt `composite_to_succinct` returns for it
+    pub fn prove_session(
+        &self,
+        prover: &dyn ProverServer,
+        session: &Session,
+    ) -> Result<SuccinctReceipt<ReceiptClaim>> {
+        let segments = session
+            .segments
+            .iter()
+            .map(|s| s.resolve())
+            .collect::<Result<Vec<_>>>()?;
+        let mut lifted = self.prove_segments(prover, segments)?.into_iter();
+        let mut joined = lifted.next().ok_or(SegmentWorkerError::NoSegments)?;
+        for receipt in lifted {
+            joined = prover.join(&joined, &receipt)?;
+        }
+        if joined.claim.digest() != session.claim()?.digest() {
+            return Err(SegmentWorkerError::ClaimMismatch.into());
+        }
+        Ok(joined)
+    }
+
+    fn prove_segments(
+        &self,
+        prover: &dyn ProverServer,
+        segments: Vec<Segment>,
+    ) -> Result<Vec<SuccinctReceipt<ReceiptClaim>>> {
+        let count = segments.len();
+        let queue = Mutex::new(segments.into_iter().enumerate().collect::<VecDeque<_>>());
+        let results = Mutex::new((0..count).map(|_| None).collect::<Vec<_>>());
+        std::thread::scope(|scope| {
+            for worker in &self.config.workers {
+                let (queue, results) = (&queue, &results);
+                scope.spawn(move || self.run_worker(worker, queue, results));
+            }
+        });
+        let remaining = queue.into_inner().expect("Segment queue lock poisoned");
+        let mut results = results.into_inner().expect("Segment results lock poisoned");
+        if !remaining.is_empty() {
+            warn!("Proving {} segments locally", remaining.len());
+            let ctx = VerifierContext::default();
+            for (index, segment) in remaining {
+                results[index] = Some(prove_segment(prover, &ctx, &segment)?);
+            }
+        }
+        results
+            .into_iter()
+            .enumerate()
+            .map(|(index, r)| r.ok_or(SegmentWorkerError::MissingSegment(index).into()))
+            .collect()
+    }
+
+    /// Takes segments off the queue until it is empty, a failing worker puts its segment back and stops
+    fn run_worker(
+        &self,
+        worker: &str,
+        queue: &Mutex<VecDeque<(usize, Segment)>>,
+        results: &Mutex<Vec<Option<SuccinctReceipt<ReceiptClaim>>>>,
+    ) {
+        let ctx = VerifierContext::default();
+        let mut stream = match self.connect(worker) {
+            Ok(stream) => stream,
+            Err(e) => {
+                warn!("Segment worker {} unavailable: {:?}", worker, e);
+                record_worker_error(worker);
+                return;
+            }
+        };
+        loop {
+            let Some((index, segment)) = queue
+                .lock()
+                .expect("Segment queue lock poisoned")
+                .pop_front()
+            else {
+                return;
+            };
+            let start = Instant::now();
+            match request_segment(&mut stream, &ctx, &segment) {
+                Ok(lifted) => {
+                    let duration_ms = start.elapsed().as_millis();
+                    info!(
+                        event = MetricEvents::SegmentProof.to_string(),
+                        worker = worker,
+                        "Duration: {} = {} ms",
+                        MetricEvents::SegmentProof,
+                        duration_ms
+                    );
+                    let h = histogram!("durations", "duration" => MetricEvents::SegmentProof.to_string(), "worker" => worker.to_string());
+                    h.record(duration_ms as f64);
+                    results.lock().expect("Segment results lock poisoned")[index] = Some(lifted);
+                }
+                Err(e) => {
+                    warn!("Segment worker {} failed: {:?}", worker, e);
+                    record_worker_error(worker);
+                    queue
+                        .lock()
+                        .expect("Segment queue lock poisoned")
+                        .push_back((index, segment));
+                    return;
+                }
+            }
+        }
+    }
+
+    fn connect(&self, worker: &str) -> Result<TcpStream> {
+        let addr = worker
+            .to_socket_addrs()?
+            .next()
+            .ok_or(SegmentWorkerError::InvalidAddress(worker.to_string()))?;
+        let mut stream = TcpStream::connect_timeout(
+            &addr,
+            Duration::from_secs(self.config.connect_timeout_secs),
+        )?;
+        stream.set_read_timeout(Some(Duration::from_secs(self.config.segment_timeout_secs)))?;
+        if let Some(token) = &self.config.token {
+            authenticate(&mut stream, token)?;
+        }
+        Ok(stream)
+    }
+}
+
+/// The worker's receipt is checked before it is joined, a bad one fails the worker instead of the join
+fn request_segment(
+    stream: &mut TcpStream,
+    ctx: &VerifierContext,
+    segment: &Segment,
+) -> Result<SuccinctReceipt<ReceiptClaim>> {
+    write_frame(stream, &WorkerRequest::ProveSegment(segment))?;
+    match read_frame::<WorkerResponse>(stream, MAX_FRAME_SIZE)?
+        .ok_or(SegmentWorkerError::ConnectionClosed)?
+    {
+        WorkerResponse::Lifted(lifted) => {
+            lifted.verify_integrity_with_context(ctx)?;
+            Ok(*lifted)
+        }
+        WorkerResponse::Error(e) => Err(SegmentWorkerError::Worker(e).into()),
+        WorkerResponse::Authenticated => {
+            Err(SegmentWorkerError::Worker("unexpected authentication response".to_string()).into())
+        }
+    }
+}
+
+fn record_worker_error(worker: &str) {
+    let c = counter!("counters", "counter" => MetricEvents::SegmentWorkerError.to_string(), "worker" => worker.to_string());
+    c.increment(1);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_frame_roundtrip() {
+        let mut buf = Vec::new();
+        write_frame(&mut buf, &WorkerResponse::Error("boom".to_string())).unwrap();
+        write_frame(&mut buf, &WorkerResponse::Error("again".to_string())).unwrap();
+
+        let mut reader = buf.as_slice();
+        for expected in ["boom", "again"] {
+            match read_frame::<WorkerResponse>(&mut reader, MAX_FRAME_SIZE).unwrap() {
+                Some(WorkerResponse::Error(e)) => assert_eq!(e, expected),
+                _ => panic!("expected an error response"),
+            }
+        }
+        assert!(read_frame::<WorkerResponse>(&mut reader, MAX_FRAME_SIZE)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_rejects_oversized_frame() {
+        let mut buf = (MAX_FRAME_SIZE + 1).to_le_bytes().to_vec();
+        buf.extend_from_slice(&[0u8; 16]);
+
+        let err = read_frame::<WorkerResponse>(&mut buf.as_slice(), MAX_FRAME_SIZE).unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<SegmentWorkerError>(),
+            Some(SegmentWorkerError::FrameTooLarge(_))
+        ));
+    }
+
+    /// Authenticates a node against a worker with `worker_token` over a local connection
+    fn authenticate_with(worker_token: &str, token: &str) -> (Result<()>, Result<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let worker_token = worker_token.to_string();
+        let worker = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            authenticate_peer(&mut stream, &worker_token)
+        });
+        let mut stream = TcpStream::connect(addr).unwrap();
+        let node = authenticate(&mut stream, token);
+        (worker.join().unwrap(), node)
+    }
+
+    #[test]
+    fn test_authenticates_with_token() {
+        let (worker, node) = authenticate_with("secret", "secret");
+        worker.unwrap();
+        node.unwrap();
+    }
+
+    #[test]
+    fn test_rejects_wrong_token() {
+        for token in ["wrong!", "secret2", ""] {
+            let (worker, node) = authenticate_with("secret", token);
+            for res in [worker, node] {
+                assert!(matches!(
+                    res.unwrap_err().downcast_ref::<SegmentWorkerError>(),
+                    Some(SegmentWorkerError::Unauthorized)
+                ));
+            }
+        }
+    }
+
+    #[test]
+    fn test_rejects_large_frames_before_authentication() {
+        let mut buf = Vec::new();
+        write_frame(
+            &mut buf,
+            &WorkerRequest::ProveSegment(vec![0u8; MAX_AUTH_FRAME_SIZE as usize]),
+        )
+        .unwrap();
+        let mut stream = std::io::Cursor::new(buf);
+
+        let err = authenticate_peer(&mut stream, "secret").unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<SegmentWorkerError>(),
+            Some(SegmentWorkerError::FrameTooLarge(_))
+        ));
+    }
+
+    #[test]
+    fn test_tokens_match() {
+        assert!(tokens_match("secret", "secret"));
+        assert!(!tokens_match("secret", "secreT"));
+        assert!(!tokens_match("secret", "secrets"));
+    }
+}
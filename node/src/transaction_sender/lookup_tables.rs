@@ -0,0 +1,321 @@
+use {
+    crate::observe::*,
+    anyhow::Result,
+    serde::{Deserialize, Serialize},
+    solana_rpc_client::nonblocking::rpc_client::RpcClient,
+    solana_rpc_client_api::{
+        config::RpcProgramAccountsConfig,
+        filter::{Memcmp, RpcFilterType},
+    },
+    solana_sdk::{
+        address_lookup_table::{
+            instruction::{create_lookup_table, extend_lookup_table},
+            program,
+            state::{AddressLookupTable, LOOKUP_TABLE_MAX_ADDRESSES},
+            AddressLookupTableAccount,
+        },
+        commitment_config::CommitmentConfig,
+        instruction::Instruction,
+        pubkey::Pubkey,
+        signature::Keypair,
+        signer::Signer,
+        transaction::Transaction,
+    },
+    std::time::Duration,
+    thiserror::Error,
+    tokio::sync::Mutex,
+};
+
+/// Offset of the optional authority in a serialized lookup table, after the program state tag, the
+/// deactivation slot, the last extended slot and its start index
+const AUTHORITY_OFFSET: usize = 21;
+/// Addresses added per extend transaction, more do not fit next to the signature and table accounts
+const EXTEND_CHUNK_SIZE: usize = 20;
+/// Slots to wait for extended addresses to become usable
+const ACTIVATION_ATTEMPTS: usize = 20;
+
+#[derive(Debug, Error)]
+pub enum LookupTableError {
+    #[error("Account {0} is not an address lookup table")]
+    NotALookupTable(Pubkey),
+    #[error("Lookup table {0} is deactivated")]
+    Deactivated(Pubkey),
+    #[error("Lookup table {0} did not activate")]
+    NotActivated(Pubkey),
+}
+
+/// Address lookup tables the node creates for the callback accounts of its status transactions
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct LookupTableConfig {
+    /// Create and extend tables owned by the node, requester supplied tables are used either way
+    pub manage: bool,
+    /// Callbacks with fewer accounts not in a requester table are sent without a node table
+    pub min_accounts: usize,
+}
+
+impl Default for LookupTableConfig {
+    fn default() -> Self {
+        LookupTableConfig {
+            manage: true,
+            min_accounts: 10,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum TablePlan {
+    Use(usize),
+    Extend(usize, Vec<Pubkey>),
+    Create(Vec<Pubkey>),
+}
+
+/// The node's own tables, reused across executions since callbacks of a program tend to take the same accounts
+pub struct LookupTables {
+    config: LookupTableConfig,
+    /// None until the tables the node already owns are read from the chain, the lock also keeps two
+    /// submissions from extending the same table at once
+    tables: Mutex<Option<Vec<AddressLookupTableAccount>>>,
+}
+
+impl LookupTables {
+    pub fn new(config: LookupTableConfig) -> Self {
+        LookupTables {
+            config,
+            tables: Mutex::new(None),
+        }
+    }
+
+    /// A node table holding all the accounts, created or extended as needed. None if there are too few
+    /// accounts to bother or the node does not manage tables
+    pub async fn for_accounts(
+        &self,
+        rpc_client: &RpcClient,
+        signer: &Keypair,
+        accounts: &[Pubkey],
+    ) -> Result<Option<AddressLookupTableAccount>> {
+        if !self.config.manage || accounts.len() < self.config.min_accounts {
+            return Ok(None);
+        }
+        let mut tables = self.tables.lock().await;
+        if tables.is_none() {
+            *tables = Some(load_tables(rpc_client, &signer.pubkey()).await?);
+        }
+        let tables = tables.as_mut().expect("Tables are loaded above");
+        match plan(tables, accounts) {
+            TablePlan::Use(i) => Ok(Some(tables[i].clone())),
+            TablePlan::Extend(i, missing) => {
+                extend_table(rpc_client, signer, Some(tables[i].key), &missing).await?;
+                tables[i].addresses.extend(missing);
+                Ok(Some(tables[i].clone()))
+            }
+            TablePlan::Create(missing) => {
+                let key = extend_table(rpc_client, signer, None, &missing).await?;
+                let table = AddressLookupTableAccount {
+                    key,
+                    addresses: missing,
+                };
+                tables.push(table.clone());
+                Ok(Some(table))
+            }
+        }
+    }
+}
+
+/// Reads a table supplied by a requester
+pub async fn fetch_lookup_table(
+    rpc_client: &RpcClient,
+    address: &Pubkey,
+) -> Result<AddressLookupTableAccount> {
+    let account = rpc_client.get_account(address).await?;
+    if account.owner != program::id() {
+        return Err(LookupTableError::NotALookupTable(*address).into());
+    }
+    let table = AddressLookupTable::deserialize(&account.data)
+        .map_err(|_| LookupTableError::NotALookupTable(*address))?;
+    if table.meta.deactivation_slot != u64::MAX {
+        return Err(LookupTableError::Deactivated(*address).into());
+    }
+    Ok(AddressLookupTableAccount {
+        key: *address,
+        addresses: table.addresses.to_vec(),
+    })
+}
+
+/// The active tables the node is the authority of, so tables survive a restart
+async fn load_tables(
+    rpc_client: &RpcClient,
+    authority: &Pubkey,
+) -> Result<Vec<AddressLookupTableAccount>> {
+    let filter = [&[1u8][..], authority.as_ref()].concat();
+    let accounts = rpc_client
+        .get_program_accounts_with_config(
+            &program::id(),
+            RpcProgramAccountsConfig {
+                filters: Some(vec![RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+                    AUTHORITY_OFFSET,
+                    filter,
+                ))]),
+                ..Default::default()
+            },
+        )
+        .await?;
+    let tables = accounts
+        .into_iter()
+        .filter_map(|(key, account)| {
+            let table = AddressLookupTable::deserialize(&account.data).ok()?;
+            (table.meta.deactivation_slot == u64::MAX).then(|| AddressLookupTableAccount {
+                key,
+                addresses: table.addresses.to_vec(),
+            })
+        })
+        .collect::<Vec<_>>();
+    info!("Loaded {} address lookup tables", tables.len());
+    Ok(tables)
+}
+
+/// The table missing the fewest accounts that still has room for them, or a new table
+fn plan(tables: &[AddressLookupTableAccount], accounts: &[Pubkey]) -> TablePlan {
+    let best = tables
+        .iter()
+        .enumerate()
+        .map(|(i, table)| {
+            let missing = accounts
+                .iter()
+                .filter(|a| !table.addresses.contains(a))
+                .copied()
+                .collect::<Vec<_>>();
+            (i, missing)
+        })
+        .filter(|(i, missing)| {
+            tables[*i].addresses.len() + missing.len() <= LOOKUP_TABLE_MAX_ADDRESSES
+        })
+        .min_by_key(|(_, missing)| missing.len());
+    match best {
+        Some((i, missing)) if missing.is_empty() => TablePlan::Use(i),
+        Some((i, missing)) => TablePlan::Extend(i, missing),
+        None => TablePlan::Create(accounts.to_vec()),
+    }
+}
+
+/// Adds the addresses to the table, creating it first if there is none, and waits until they can be used
+async fn extend_table(
+    rpc_client: &RpcClient,
+    signer: &Keypair,
+    table: Option<Pubkey>,
+    addresses: &[Pubkey],
+) -> Result<Pubkey> {
+    let authority = signer.pubkey();
+    let mut instructions = Vec::new();
+    let table = match table {
+        Some(table) => table,
+        None => {
+            let recent_slot = rpc_client
+                .get_slot_with_commitment(CommitmentConfig::finalized())
+                .await?;
+            let (create, table) = create_lookup_table(authority, authority, recent_slot);
+            instructions.push(create);
+            table
+        }
+    };
+    emit_event_with_duration!(MetricEvents::LookupTableExtension, {
+        for chunk in addresses.chunks(EXTEND_CHUNK_SIZE) {
+            instructions.push(extend_lookup_table(
+                table,
+                authority,
+                Some(authority),
+                chunk.to_vec(),
+            ));
+            send_and_confirm(rpc_client, signer, &instructions).await?;
+            instructions.clear();
+        }
+        wait_for_activation(rpc_client, &table).await
+    }, table => table.to_string(), addresses => addresses.len())?;
+    Ok(table)
+}
+
+async fn send_and_confirm(
+    rpc_client: &RpcClient,
+    signer: &Keypair,
+    instructions: &[Instruction],
+) -> Result<()> {
+    let blockhash = rpc_client.get_latest_blockhash().await?;
+    let tx = Transaction::new_signed_with_payer(
+        instructions,
+        Some(&signer.pubkey()),
+        &[signer],
+        blockhash,
+    );
+    rpc_client.send_and_confirm_transaction(&tx).await?;
+    Ok(())
+}
+
+/// Addresses extended in a slot can only be looked up from the next one
+async fn wait_for_activation(rpc_client: &RpcClient, table: &Pubkey) -> Result<()> {
+    let extended_at = rpc_client.get_slot().await?;
+    for _ in 0..ACTIVATION_ATTEMPTS {
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        if rpc_client.get_slot().await? > extended_at {
+            return Ok(());
+        }
+    }
+    Err(LookupTableError::NotActivated(*table).into())
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, solana_sdk::address_lookup_table::state::LookupTableMeta, std::borrow::Cow};
+
+    fn table(addresses: Vec<Pubkey>) -> AddressLookupTableAccount {
+        AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses,
+        }
+    }
+
+    #[test]
+    fn test_uses_table_with_all_accounts() {
+        let accounts = vec![Pubkey::new_unique(), Pubkey::new_unique()];
+        let tables = vec![
+            table(vec![accounts[0]]),
+            table(vec![Pubkey::new_unique(), accounts[1], accounts[0]]),
+        ];
+
+        assert_eq!(plan(&tables, &accounts), TablePlan::Use(1));
+    }
+
+    #[test]
+    fn test_extends_closest_table_with_room() {
+        let accounts = vec![Pubkey::new_unique(), Pubkey::new_unique()];
+        let full = (0..LOOKUP_TABLE_MAX_ADDRESSES - 1)
+            .map(|_| Pubkey::new_unique())
+            .chain([accounts[0]])
+            .collect();
+        let tables = vec![table(full), table(vec![])];
+
+        assert_eq!(plan(&tables, &accounts), TablePlan::Extend(1, accounts));
+    }
+
+    #[test]
+    fn test_creates_table_without_tables() {
+        let accounts = vec![Pubkey::new_unique()];
+
+        assert_eq!(plan(&[], &accounts), TablePlan::Create(accounts));
+    }
+
+    #[test]
+    fn test_authority_offset() {
+        let authority = Pubkey::new_unique();
+        let table = AddressLookupTable {
+            meta: LookupTableMeta::new(authority),
+            addresses: Cow::Owned(vec![Pubkey::new_unique()]),
+        };
+        let data = table.serialize_for_tests().unwrap();
+
+        assert_eq!(data[AUTHORITY_OFFSET], 1);
+        assert_eq!(
+            &data[AUTHORITY_OFFSET + 1..AUTHORITY_OFFSET + 33],
+            authority.as_ref()
+        );
+    }
+}
@@ -0,0 +1,116 @@
+use {
+    super::{TransactionSender, TransactionStatus},
+    crate::types::ProgramExec,
+    anyhow::{anyhow, Result},
+    async_trait::async_trait,
+    bonsol_interface::{bonsol_schema::FailureReason, claim_state::ClaimStateV1},
+    dashmap::DashMap,
+    solana_sdk::{
+        account::Account,
+        instruction::AccountMeta,
+        pubkey::Pubkey,
+        signature::{Keypair, Signature},
+    },
+    std::sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+/// Records claims, proofs and failures and answers claim state lookups from `claim_states`
+#[derive(Default)]
+pub struct RecordingTransactionSender {
+    pub claims: Mutex<Vec<(String, u64)>>,
+    /// Execution ids of submitted proofs
+    pub proofs: Mutex<Vec<String>>,
+    pub failures: Mutex<Vec<(String, FailureReason)>>,
+    pub current_block: AtomicU64,
+    /// Claim states by execution account
+    pub claim_states: DashMap<Pubkey, ClaimStateV1>,
+    pub balance: AtomicU64,
+    /// Lamports of each top up, added to `balance`
+    pub top_ups: Mutex<Vec<u64>>,
+}
+
+#[async_trait]
+impl TransactionSender for RecordingTransactionSender {
+    fn start(&mut self) {}
+
+    async fn claim(
+        &self,
+        execution_id: &str,
+        _requester: Pubkey,
+        _execution_account: Pubkey,
+        block_commitment: u64,
+    ) -> Result<Signature> {
+        self.claims
+            .lock()
+            .unwrap()
+            .push((execution_id.to_string(), block_commitment));
+        Ok(Signature::new_unique())
+    }
+
+    async fn submit_proof(
+        &self,
+        execution_id: &str,
+        _requester_account: Pubkey,
+        _callback_exec: Option<ProgramExec>,
+        _proof: &[u8],
+        _execution_digest: &[u8],
+        _input_digest: &[u8],
+        _assumption_digest: &[u8],
+        _committed_outputs: &[u8],
+        _additional_accounts: Vec<AccountMeta>,
+        _exit_code_system: u32,
+        _exit_code_user: u32,
+    ) -> Result<Signature> {
+        self.proofs.lock().unwrap().push(execution_id.to_string());
+        Ok(Signature::new_unique())
+    }
+
+    async fn submit_failure(
+        &self,
+        execution_id: &str,
+        _requester_account: Pubkey,
+        failure_reason: FailureReason,
+    ) -> Result<Signature> {
+        self.failures
+            .lock()
+            .unwrap()
+            .push((execution_id.to_string(), failure_reason));
+        Ok(Signature::new_unique())
+    }
+
+    async fn get_current_block(&self) -> Result<u64> {
+        Ok(self.current_block.load(Ordering::SeqCst))
+    }
+
+    fn get_signature_status(&self, _sig: &Signature) -> Option<TransactionStatus> {
+        None
+    }
+
+    fn clear_signature_status(&self, _sig: &Signature) {}
+
+    async fn get_deployment_account(&self, image_id: &str) -> Result<Account> {
+        Err(anyhow!("No deployment of image {}", image_id))
+    }
+
+    async fn get_claim_state(&self, execution_account: Pubkey) -> Result<Option<ClaimStateV1>> {
+        Ok(self.claim_states.get(&execution_account).map(|c| *c))
+    }
+
+    async fn get_balance(&self) -> Result<u64> {
+        Ok(self.balance.load(Ordering::SeqCst))
+    }
+
+    async fn top_up(&self, _funder: &Keypair, lamports: u64) -> Result<Signature> {
+        self.top_ups.lock().unwrap().push(lamports);
+        self.balance.fetch_add(lamports, Ordering::SeqCst);
+        Ok(Signature::new_unique())
+    }
+
+    /// Every transaction pays the base fee of one signature
+    async fn get_transaction_fee(&self, _sig: &Signature) -> Result<u64> {
+        Ok(5000)
+    }
+}
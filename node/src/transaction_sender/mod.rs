@@ -1,36 +1,45 @@
+pub mod lookup_tables;
+#[cfg(test)]
+pub mod mock;
+
 use std::sync::Arc;
 
-use tracing::error;
+use tracing::{error, warn};
 
 use {
     async_trait::async_trait,
     bonsol_interface::{
         bonsol_schema::{
             ChannelInstruction, ChannelInstructionArgs, ChannelInstructionIxType, ClaimV1,
-            ClaimV1Args, StatusTypes, StatusV1, StatusV1Args,
+            ClaimV1Args, FailureReason, StatusTypes, StatusV1, StatusV1Args,
         },
+        claim_state::ClaimStateV1,
         util::{deployment_address, execution_address, execution_claim_address},
     },
     dashmap::DashMap,
     flatbuffers::FlatBufferBuilder,
     itertools::Itertools,
-    solana_rpc_client_api::config::RpcSendTransactionConfig,
+    solana_rpc_client_api::config::{RpcSendTransactionConfig, RpcTransactionConfig},
     solana_sdk::{
         account::Account,
+        address_lookup_table::AddressLookupTableAccount,
         commitment_config::CommitmentConfig,
         message::{v0, VersionedMessage},
         signature::Signature,
         signer::SignerError,
-        system_program,
+        system_instruction, system_program,
         transaction::VersionedTransaction,
     },
-    solana_transaction_status::TransactionStatus as TransactionConfirmationStatus,
+    solana_transaction_status::{
+        TransactionStatus as TransactionConfirmationStatus, UiTransactionEncoding,
+    },
     tokio::task::JoinHandle,
 };
 
 use {
     crate::types::ProgramExec,
     anyhow::Result,
+    lookup_tables::{fetch_lookup_table, LookupTableConfig, LookupTables},
     solana_rpc_client::nonblocking::rpc_client::RpcClient,
     solana_sdk::{
         instruction::{AccountMeta, Instruction},
@@ -48,7 +57,7 @@ pub enum TransactionStatus {
 }
 
 #[async_trait]
-pub trait TransactionSender {
+pub trait TransactionSender: Send + Sync {
     fn start(&mut self);
     async fn claim(
         &self,
@@ -71,10 +80,25 @@ pub trait TransactionSender {
         exit_code_system: u32,
         exit_code_user: u32,
     ) -> Result<Signature>;
+    /// Submits a status without a proof so the requester is refunded as soon as proving is abandoned.
+    async fn submit_failure(
+        &self,
+        execution_id: &str,
+        requester_account: Pubkey,
+        failure_reason: FailureReason,
+    ) -> Result<Signature>;
     async fn get_current_block(&self) -> Result<u64>;
     fn get_signature_status(&self, sig: &Signature) -> Option<TransactionStatus>;
     fn clear_signature_status(&self, sig: &Signature);
     async fn get_deployment_account(&self, image_id: &str) -> Result<Account>;
+    /// The current claim on an execution, None if it was never claimed
+    async fn get_claim_state(&self, execution_account: Pubkey) -> Result<Option<ClaimStateV1>>;
+    /// Lamports held by the node's signer
+    async fn get_balance(&self) -> Result<u64>;
+    /// Transfers lamports from the funding signer to the node's signer
+    async fn top_up(&self, funder: &Keypair, lamports: u64) -> Result<Signature>;
+    /// Lamports paid for a confirmed transaction
+    async fn get_transaction_fee(&self, sig: &Signature) -> Result<u64>;
 }
 
 pub struct RpcTransactionSender {
@@ -83,6 +107,7 @@ pub struct RpcTransactionSender {
     pub signer: Keypair,
     pub txn_status_handle: Option<JoinHandle<()>>,
     pub sigs: Arc<DashMap<Signature, TransactionStatus>>,
+    pub lookup_tables: LookupTables,
 }
 
 impl Signer for RpcTransactionSender {
@@ -118,8 +143,98 @@ impl RpcTransactionSender {
             bonsol_program,
             txn_status_handle: None,
             sigs: Arc::new(DashMap::new()),
+            lookup_tables: LookupTables::new(LookupTableConfig::default()),
         }
     }
+
+    pub fn with_lookup_tables(mut self, config: LookupTableConfig) -> Self {
+        self.lookup_tables = LookupTables::new(config);
+        self
+    }
+
+    /// Lookup tables for the callback accounts of a status, the requester's table first. A table that cannot be
+    /// used only costs transaction space, the status is still sent
+    async fn callback_lookup_tables(
+        &self,
+        callback: &ProgramExec,
+        accounts: &[AccountMeta],
+    ) -> Vec<AddressLookupTableAccount> {
+        let mut tables = Vec::new();
+        if let Some(address) = callback.lookup_table {
+            match fetch_lookup_table(&self.rpc_client, &address).await {
+                Ok(table) => tables.push(table),
+                Err(e) => warn!("Requester lookup table {} unusable: {:?}", address, e),
+            }
+        }
+        // signers are never looked up
+        let uncovered = std::iter::once(callback.program_id)
+            .chain(accounts.iter().filter(|a| !a.is_signer).map(|a| a.pubkey))
+            .filter(|k| k != &self.signer.pubkey())
+            .filter(|k| !tables.iter().any(|t| t.addresses.contains(k)))
+            .unique()
+            .collect_vec();
+        match self
+            .lookup_tables
+            .for_accounts(&self.rpc_client, &self.signer, &uncovered)
+            .await
+        {
+            Ok(Some(table)) => tables.push(table),
+            Ok(None) => {}
+            Err(e) => warn!("Error preparing lookup table: {:?}", e),
+        }
+        tables
+    }
+
+    /// Wraps a finished StatusV1 in a channel instruction and sends it, waiting for confirmation.
+    async fn send_status(
+        &self,
+        accounts: Vec<AccountMeta>,
+        statbytes: &[u8],
+        lookup_tables: &[AddressLookupTableAccount],
+    ) -> Result<Signature> {
+        let mut fbb2 = FlatBufferBuilder::new();
+        let off = fbb2.create_vector(statbytes);
+        let root = ChannelInstruction::create(
+            &mut fbb2,
+            &ChannelInstructionArgs {
+                ix_type: ChannelInstructionIxType::StatusV1,
+                status_v1: Some(off),
+                ..Default::default()
+            },
+        );
+        fbb2.finish(root, None);
+        let ix_data = fbb2.finished_data();
+        let instruction = Instruction::new_with_bytes(self.bonsol_program, ix_data, accounts);
+        let (blockhash, last_valid) = self
+            .rpc_client
+            .get_latest_blockhash_with_commitment(self.rpc_client.commitment())
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to get blockhash: {:?}", e))?;
+
+        let msg = v0::Message::try_compile(
+            &self.signer.pubkey(),
+            &[instruction],
+            lookup_tables,
+            blockhash,
+        )?;
+        let tx = VersionedTransaction::try_new(VersionedMessage::V0(msg), &[&self.signer])?;
+
+        let sig = self
+            .rpc_client
+            .send_and_confirm_transaction_with_spinner_and_config(
+                &tx,
+                CommitmentConfig::confirmed(),
+                RpcSendTransactionConfig {
+                    skip_preflight: true,
+                    ..Default::default()
+                },
+            )
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to send transaction: {:?}", e))?;
+        self.sigs
+            .insert(sig, TransactionStatus::Pending { expiry: last_valid });
+        Ok(sig)
+    }
 }
 
 #[async_trait]
@@ -213,6 +328,10 @@ impl TransactionSender for RpcTransactionSender {
     ) -> Result<Signature> {
         let (execution_request_data_account, _) =
             execution_address(&requester_account, execution_id.as_bytes());
+        let lookup_tables = match &callback_exec {
+            Some(pe) => self.callback_lookup_tables(pe, &additional_accounts).await,
+            None => vec![],
+        };
         let (id, additional_accounts) = match callback_exec {
             None => (self.bonsol_program, vec![]),
             Some(pe) => {
@@ -248,47 +367,42 @@ impl TransactionSender for RpcTransactionSender {
                 committed_outputs: Some(out),               //0-?? bytes lets say 32
                 exit_code_system,                           //4 byte
                 exit_code_user,                             //4 byte
+                failure_reason: FailureReason::Unknown,     //1 byte
             }, //total ~408 bytes plenty of room for more stuff
         );
         fbb.finish(stat, None);
-        let statbytes = fbb.finished_data();
-        let mut fbb2 = FlatBufferBuilder::new();
-        let off = fbb2.create_vector(statbytes);
-        let root = ChannelInstruction::create(
-            &mut fbb2,
-            &ChannelInstructionArgs {
-                ix_type: ChannelInstructionIxType::StatusV1,
-                status_v1: Some(off),
+        self.send_status(accounts, fbb.finished_data(), &lookup_tables)
+            .await
+    }
+
+    async fn submit_failure(
+        &self,
+        execution_id: &str,
+        requester_account: Pubkey,
+        failure_reason: FailureReason,
+    ) -> Result<Signature> {
+        let (execution_request_data_account, _) =
+            execution_address(&requester_account, execution_id.as_bytes());
+        // no callback is made on failure, the bonsol program stands in for the callback program
+        let accounts = vec![
+            AccountMeta::new(requester_account, false),
+            AccountMeta::new(execution_request_data_account, false),
+            AccountMeta::new_readonly(self.bonsol_program, false),
+            AccountMeta::new(self.signer.pubkey(), true),
+        ];
+        let mut fbb = FlatBufferBuilder::new();
+        let eid = fbb.create_string(execution_id);
+        let stat = StatusV1::create(
+            &mut fbb,
+            &StatusV1Args {
+                execution_id: Some(eid),
+                status: StatusTypes::Failed,
+                failure_reason,
                 ..Default::default()
             },
         );
-        fbb2.finish(root, None);
-        let ix_data = fbb2.finished_data();
-        let instruction = Instruction::new_with_bytes(self.bonsol_program, ix_data, accounts);
-        let (blockhash, last_valid) = self
-            .rpc_client
-            .get_latest_blockhash_with_commitment(self.rpc_client.commitment())
-            .await
-            .map_err(|e| anyhow::anyhow!("Failed to get blockhash: {:?}", e))?;
-
-        let msg = v0::Message::try_compile(&self.signer.pubkey(), &[instruction], &[], blockhash)?;
-        let tx = VersionedTransaction::try_new(VersionedMessage::V0(msg), &[&self.signer])?;
-
-        let sig = self
-            .rpc_client
-            .send_and_confirm_transaction_with_spinner_and_config(
-                &tx,
-                CommitmentConfig::confirmed(),
-                RpcSendTransactionConfig {
-                    skip_preflight: true,
-                    ..Default::default()
-                },
-            )
-            .await
-            .map_err(|e| anyhow::anyhow!("Failed to send transaction: {:?}", e))?;
-        self.sigs
-            .insert(sig, TransactionStatus::Pending { expiry: last_valid });
-        Ok(sig)
+        fbb.finish(stat, None);
+        self.send_status(accounts, fbb.finished_data(), &[]).await
     }
 
     fn start(&mut self) {
@@ -342,4 +456,62 @@ impl TransactionSender for RpcTransactionSender {
             .await
             .map_err(|e| anyhow::anyhow!("Failed to get account: {:?}", e))
     }
+
+    async fn get_claim_state(&self, execution_account: Pubkey) -> Result<Option<ClaimStateV1>> {
+        let (execution_claim_account, _) = execution_claim_address(execution_account.as_ref());
+        let account = self
+            .rpc_client
+            .get_account_with_commitment(&execution_claim_account, self.rpc_client.commitment())
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to get account: {:?}", e))?
+            .value;
+        account
+            .map(|account| {
+                ClaimStateV1::load_claim_owned(&account.data)
+                    .map_err(|_| anyhow::anyhow!("Invalid claim account"))
+            })
+            .transpose()
+    }
+
+    async fn get_balance(&self) -> Result<u64> {
+        self.rpc_client
+            .get_balance(&self.signer.pubkey())
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to get balance: {:?}", e))
+    }
+
+    async fn top_up(&self, funder: &Keypair, lamports: u64) -> Result<Signature> {
+        let instruction =
+            system_instruction::transfer(&funder.pubkey(), &self.signer.pubkey(), lamports);
+        let blockhash = self
+            .rpc_client
+            .get_latest_blockhash()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to get blockhash: {:?}", e))?;
+        let msg = v0::Message::try_compile(&funder.pubkey(), &[instruction], &[], blockhash)?;
+        let tx = VersionedTransaction::try_new(VersionedMessage::V0(msg), &[funder])?;
+        self.rpc_client
+            .send_and_confirm_transaction(&tx)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to send transaction: {:?}", e))
+    }
+
+    async fn get_transaction_fee(&self, sig: &Signature) -> Result<u64> {
+        let tx = self
+            .rpc_client
+            .get_transaction_with_config(
+                sig,
+                RpcTransactionConfig {
+                    encoding: Some(UiTransactionEncoding::Base64),
+                    commitment: Some(CommitmentConfig::confirmed()),
+                    max_supported_transaction_version: Some(0),
+                },
+            )
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to get transaction: {:?}", e))?;
+        tx.transaction
+            .meta
+            .map(|meta| meta.fee)
+            .ok_or_else(|| anyhow::anyhow!("Transaction {} has no status", sig))
+    }
 }
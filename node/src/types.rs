@@ -106,4 +106,6 @@ pub enum CallbackStatus {
 pub struct ProgramExec {
     pub program_id: Pubkey,
     pub instruction_prefix: Vec<u8>,
+    /// The requester's lookup table for the callback accounts
+    pub lookup_table: Option<Pubkey>,
 }
@@ -1,18 +1,16 @@
 use crate::{
     assertions::*,
     error::ChannelError,
-    proof_handling::{
-        output_digest_v1_0_1, output_digest_v1_2_1, prepare_inputs_v1_0_1, prepare_inputs_v1_2_1,
-        verify_risc0_v1_0_1, verify_risc0_v1_2_1,
-    },
+    proof_handling::{verify_status_proof, ProofClaim},
     utilities::*,
 };
 
 use bonsol_interface::{
     bonsol_schema::{
-        root_as_execution_request_v1, ChannelInstruction, ExecutionRequestV1, ExitCode, StatusV1,
+        root_as_execution_request_v1, ChannelInstruction, ExecutionRequestV1, ExitCode,
+        StatusTypes, StatusV1,
     },
-    prover_version::{ProverVersion, VERSION_V1_0_1, VERSION_V1_2_1},
+    prover_version::ProverVersion,
     util::execution_address_seeds,
 };
 
@@ -180,8 +178,14 @@ pub fn process_status_v1<'a>(
             cleanup_execution_account(sa.exec, sa.requester, ExitCode::VerifyError as u8)?;
         }
     } else {
-        msg!("{} Proving Failed Cleaning up", sa.eid);
-        cleanup_execution_account(sa.exec, sa.requester, ExitCode::ProvingError as u8)?;
+        // the prover gave up, record why so the requester does not have to wait for expiry
+        let exit_code = if st.status() == StatusTypes::Failed {
+            ExitCode::from(st.failure_reason())
+        } else {
+            ExitCode::ProvingError
+        };
+        msg!("{} Proving Failed Cleaning up: {}", sa.eid, exit_code);
+        cleanup_execution_account(sa.exec, sa.requester, exit_code as u8)?;
     }
     Ok(())
 }
@@ -197,30 +201,14 @@ fn verify_with_prover(
 ) -> Result<bool, ProgramError> {
     let prover_version =
         ProverVersion::try_from(er.prover_version()).unwrap_or(ProverVersion::default());
-    let verified = match prover_version {
-        VERSION_V1_0_1 => {
-            let output_digest = output_digest_v1_0_1(input_digest, co, asud);
-            let proof_inputs = prepare_inputs_v1_0_1(
-                er.image_id().unwrap(),
-                exed,
-                output_digest.as_ref(),
-                st.exit_code_system(),
-                st.exit_code_user(),
-            )?;
-            verify_risc0_v1_0_1(proof, &proof_inputs)?
-        }
-        VERSION_V1_2_1 => {
-            let output_digest = output_digest_v1_2_1(input_digest, co, asud);
-            let proof_inputs = prepare_inputs_v1_2_1(
-                er.image_id().unwrap(),
-                exed,
-                output_digest.as_ref(),
-                st.exit_code_system(),
-                st.exit_code_user(),
-            )?;
-            verify_risc0_v1_2_1(proof, &proof_inputs)?
-        }
-        _ => false,
+    let claim = ProofClaim {
+        image_id: er.image_id().unwrap(),
+        execution_digest: exed,
+        input_digest,
+        committed_outputs: co,
+        assumption_digest: asud,
+        exit_code_system: st.exit_code_system(),
+        exit_code_user: st.exit_code_user(),
     };
-    Ok(verified)
+    Ok(verify_status_proof(prover_version, &claim, proof)?)
 }
@@ -1,6 +1,7 @@
 use std::ops::Neg;
 
 use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, Compress, Validate};
+use bonsol_interface::prover_version::{ProverVersion, VERSION_V1_0_1, VERSION_V1_2_1};
 use groth16_solana::groth16::{Groth16Verifier, Groth16Verifyingkey};
 use solana_program::hash::hashv;
 
@@ -12,6 +13,60 @@ use crate::{
 
 type G1 = ark_bn254::g1::G1Affine;
 
+/// What a status proof commits to, taken from the status and its execution request
+pub struct ProofClaim<'a> {
+    pub image_id: &'a str,
+    pub execution_digest: &'a [u8],
+    pub input_digest: &'a [u8],
+    pub committed_outputs: &'a [u8],
+    pub assumption_digest: &'a [u8],
+    pub exit_code_system: u32,
+    pub exit_code_user: u32,
+}
+
+/// Verifies a status proof against the circuit of the request's prover version, the program runs this on
+/// every status and provers run it before submitting one
+pub fn verify_status_proof(
+    prover_version: ProverVersion,
+    claim: &ProofClaim,
+    proof: &[u8; 256],
+) -> Result<bool, ChannelError> {
+    let verified = match prover_version {
+        VERSION_V1_0_1 => {
+            let output_digest = output_digest_v1_0_1(
+                claim.input_digest,
+                claim.committed_outputs,
+                claim.assumption_digest,
+            );
+            let proof_inputs = prepare_inputs_v1_0_1(
+                claim.image_id,
+                claim.execution_digest,
+                output_digest.as_ref(),
+                claim.exit_code_system,
+                claim.exit_code_user,
+            )?;
+            verify_risc0_v1_0_1(proof, &proof_inputs)?
+        }
+        VERSION_V1_2_1 => {
+            let output_digest = output_digest_v1_2_1(
+                claim.input_digest,
+                claim.committed_outputs,
+                claim.assumption_digest,
+            );
+            let proof_inputs = prepare_inputs_v1_2_1(
+                claim.image_id,
+                claim.execution_digest,
+                output_digest.as_ref(),
+                claim.exit_code_system,
+                claim.exit_code_user,
+            )?;
+            verify_risc0_v1_2_1(proof, &proof_inputs)?
+        }
+        _ => false,
+    };
+    Ok(verified)
+}
+
 pub fn verify_risc0_v1_0_1(proof: &[u8], inputs: &[u8]) -> Result<bool, ChannelError> {
     let ins: [[u8; 32]; 5] = [
         sized_range::<32>(&inputs[0..32])?,
@@ -40,7 +95,8 @@ fn verify_proof<const NI: usize>(
     vkey: &Groth16Verifyingkey,
 ) -> Result<bool, ChannelError> {
     let ace: Vec<u8> = toggle_endianness_256(&[&proof[0..64], &[0u8][..]].concat());
-    let proof_a: G1 = G1::deserialize_with_mode(&*ace, Compress::No, Validate::No).unwrap();
+    let proof_a: G1 = G1::deserialize_with_mode(&*ace, Compress::No, Validate::No)
+        .map_err(|_| ChannelError::InvalidProof)?;
 
     let mut proof_a_neg = [0u8; 65];
     G1::serialize_with_mode(&proof_a.neg(), &mut proof_a_neg[..], Compress::No)
@@ -303,6 +359,40 @@ mod tests {
         assert!(split_digest_reversed_256(&mut d2).is_err());
     }
 
+    fn claim() -> ProofClaim<'static> {
+        ProofClaim {
+            image_id: "a9e4a9a7d8d7f4d4ad4f6c5d3d4a1d6a39f5c2d4f5e8c6e2a7d3b4f1c0e9d8a7",
+            execution_digest: &[1u8; 32],
+            input_digest: &[2u8; 32],
+            committed_outputs: &[3u8; 8],
+            assumption_digest: &[0u8; 32],
+            exit_code_system: 0,
+            exit_code_user: 0,
+        }
+    }
+
+    #[test]
+    fn test_unsupported_version_does_not_verify() {
+        assert!(
+            !verify_status_proof(ProverVersion::UnsupportedVersion, &claim(), &[0u8; 256]).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_garbage_proof_does_not_verify() {
+        let verified = verify_status_proof(VERSION_V1_2_1, &claim(), &[7u8; 256]);
+        assert!(!matches!(verified, Ok(true)));
+    }
+
+    #[test]
+    fn test_invalid_image_id() {
+        let claim = ProofClaim {
+            image_id: "not hex",
+            ..claim()
+        };
+        assert!(verify_status_proof(VERSION_V1_2_1, &claim, &[0u8; 256]).is_err());
+    }
+
     #[test]
     fn test_sized_range() {
         let slice = [1u8; 32];
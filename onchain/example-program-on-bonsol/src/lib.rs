@@ -87,6 +87,7 @@ fn main<'a>(
                         AccountMeta::new_readonly(EA2, false),
                         AccountMeta::new_readonly(EA3, false),
                     ],
+                    lookup_table: None,
                 }),
                 None,
             )
@@ -1,12 +1,12 @@
 use bonsol_schema::{
-    Account, ChannelInstruction, ChannelInstructionArgs, ChannelInstructionIxType, DeployV1,
-    DeployV1Args, ExecutionRequestV1, ExecutionRequestV1Args, InputBuilder, InputType,
-    ProgramInputType, ProverVersion,
+    Account, ChannelInstruction, ChannelInstructionArgs, ChannelInstructionIxType, ClaimV1,
+    ClaimV1Args, DeployV1, DeployV1Args, ExecutionRequestV1, ExecutionRequestV1Args, InputBuilder,
+    InputType, ProgramInputType, ProverVersion,
 };
 use flatbuffers::{FlatBufferBuilder, WIPOffset};
 
 use crate::error::ClientError;
-use crate::util::{deployment_address, execution_address};
+use crate::util::{deployment_address, execution_address, execution_claim_address};
 
 #[cfg(feature = "on-chain")]
 use {
@@ -158,6 +158,10 @@ pub struct CallbackConfig {
     pub program_id: Pubkey,
     pub instruction_prefix: Vec<u8>,
     pub extra_accounts: Vec<AccountMeta>,
+    /// An address lookup table with the extra accounts, lets provers fit callbacks with many accounts in the
+    /// status transaction
+    #[cfg_attr(feature = "serde", serde(default, with = "serde_helpers::optpubkey"))]
+    pub lookup_table: Option<Pubkey>,
 }
 
 pub struct InputRef<'a> {
@@ -182,6 +186,8 @@ impl<'a> InputRef<'a> {
             data,
         }
     }
+    /// A url to a receipt that is added to the guest as an assumption
+    /// The data is the url or, to pin the image id, built with `bonsol_schema::encode_proof_url`
     pub fn public_proof(data: &'a [u8]) -> Self {
         Self {
             input_type: InputType::PublicProof,
@@ -200,6 +206,37 @@ impl<'a> InputRef<'a> {
             data,
         }
     }
+    /// Several account slices read at a single slot
+    /// The data is built with `bonsol_schema::accounts_input::AccountsInput::to_bytes`
+    pub fn public_accounts(data: &'a [u8]) -> Self {
+        Self {
+            input_type: InputType::PublicAccounts,
+            data,
+        }
+    }
+    /// A finalized transaction, the data is its 64 byte signature
+    pub fn public_transaction(data: &'a [u8]) -> Self {
+        Self {
+            input_type: InputType::PublicTransaction,
+            data,
+        }
+    }
+    /// The receipt of a prior execution, added to the guest as an assumption
+    /// The data is built with `bonsol_schema::encode_execution_ref`
+    pub fn execution_proof(data: &'a [u8]) -> Self {
+        Self {
+            input_type: InputType::PublicExecutionProof,
+            data,
+        }
+    }
+    /// A url whose response body must match the hash it was requested with
+    /// The data is built with `bonsol_schema::encode_hashed_url`
+    pub fn url_with_hash(data: &'a [u8]) -> Self {
+        Self {
+            input_type: InputType::PublicUrlHashed,
+            data,
+        }
+    }
 }
 
 /// Executes a bonsol program.
@@ -253,10 +290,11 @@ pub fn execute_v1_with_accounts<'a>(
     config.validate()?;
     let mut fbb = FlatBufferBuilder::new();
     let mut callback_pubkey = None; // aviod clone
-    let (callback_program_id, callback_instruction_prefix, extra_accounts) =
+    let (callback_program_id, callback_instruction_prefix, extra_accounts, lookup_table) =
         if let Some(cb) = callback {
             callback_pubkey = Some(cb.program_id);
             let cb_program_id = fbb.create_vector(cb.program_id.as_ref());
+            let lookup_table = cb.lookup_table.map(|lt| fbb.create_vector(lt.as_ref()));
             let cb_instruction_prefix = fbb.create_vector(cb.instruction_prefix.as_slice());
             let ealen = cb.extra_accounts.len();
             fbb.start_vector::<WIPOffset<Account>>(ealen);
@@ -269,9 +307,10 @@ pub fn execute_v1_with_accounts<'a>(
                 Some(cb_program_id),
                 Some(cb_instruction_prefix),
                 Some(fbb.end_vector(ealen)),
+                lookup_table,
             )
         } else {
-            (None, None, None)
+            (None, None, None, None)
         };
     let accounts = vec![
         AccountMeta::new(*requester, true),
@@ -315,6 +354,7 @@ pub fn execute_v1_with_accounts<'a>(
             input_digest,
             callback_extra_accounts: extra_accounts,
             prover_version,
+            callback_lookup_table: lookup_table,
         },
     );
     fbb.finish(fbb_execute, None);
@@ -333,3 +373,46 @@ pub fn execute_v1_with_accounts<'a>(
     let ix_data = fbb.finished_data();
     Ok(Instruction::new_with_bytes(crate::ID, ix_data, accounts))
 }
+
+/// Claims an execution request for the claimer.
+pub fn claim_v1(
+    claimer: &Pubkey,
+    requester: &Pubkey,
+    execution_id: &str,
+    block_commitment: u64,
+) -> Result<Instruction, ClientError> {
+    let (execution_account, _) = execution_address(requester, execution_id.as_bytes());
+    let (execution_claim_account, _) = execution_claim_address(execution_account.as_ref());
+    let accounts = vec![
+        AccountMeta::new(execution_account, false),
+        AccountMeta::new_readonly(*requester, false),
+        AccountMeta::new(execution_claim_account, false),
+        AccountMeta::new(*claimer, true),
+        AccountMeta::new(*claimer, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    let mut fbb = FlatBufferBuilder::new();
+    let execution_id = fbb.create_string(execution_id);
+    let fbb_claim = ClaimV1::create(
+        &mut fbb,
+        &ClaimV1Args {
+            execution_id: Some(execution_id),
+            block_commitment,
+        },
+    );
+    fbb.finish(fbb_claim, None);
+    let ix_data = fbb.finished_data();
+    let mut fbb = FlatBufferBuilder::new();
+    let ix = fbb.create_vector(ix_data);
+    let fbb_ix = ChannelInstruction::create(
+        &mut fbb,
+        &ChannelInstructionArgs {
+            ix_type: ChannelInstructionIxType::ClaimV1,
+            claim_v1: Some(ix),
+            ..Default::default()
+        },
+    );
+    fbb.finish(fbb_ix, None);
+    let ix_data = fbb.finished_data();
+    Ok(Instruction::new_with_bytes(crate::ID, ix_data, accounts))
+}
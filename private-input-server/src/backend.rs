@@ -0,0 +1,152 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use solana_sdk::pubkey::Pubkey;
+
+/// Input backends store the private inputs this server hands out to claimers
+/// Inputs are keyed by the requester and id of the execution, the requester is checked on chain before a backend is asked
+#[async_trait]
+pub trait InputBackend: Send + Sync {
+    /// Returns the input data or None if the input does not exist
+    async fn input(
+        &self,
+        requester: &Pubkey,
+        execution_id: &str,
+        input_index: u8,
+    ) -> Result<Option<Vec<u8>>>;
+
+    /// Returns the size of the input in bytes or None if the input does not exist
+    async fn input_size(
+        &self,
+        requester: &Pubkey,
+        execution_id: &str,
+        input_index: u8,
+    ) -> Result<Option<u64>> {
+        Ok(self
+            .input(requester, execution_id, input_index)
+            .await?
+            .map(|d| d.len() as u64))
+    }
+}
+
+/// Serves inputs from `<folder>/<requester>/<execution_id>/<input_index>`
+pub struct DirectoryBackend {
+    folder: PathBuf,
+}
+
+impl DirectoryBackend {
+    pub fn new(folder: impl Into<PathBuf>) -> Self {
+        Self {
+            folder: folder.into(),
+        }
+    }
+
+    fn input_path(
+        &self,
+        requester: &Pubkey,
+        execution_id: &str,
+        input_index: u8,
+    ) -> Option<PathBuf> {
+        if !is_safe_path_segment(execution_id) {
+            return None;
+        }
+        let path = self
+            .folder
+            .join(requester.to_string())
+            .join(execution_id)
+            .join(input_index.to_string());
+        path.is_file().then_some(path)
+    }
+}
+
+#[async_trait]
+impl InputBackend for DirectoryBackend {
+    async fn input(
+        &self,
+        requester: &Pubkey,
+        execution_id: &str,
+        input_index: u8,
+    ) -> Result<Option<Vec<u8>>> {
+        match self.input_path(requester, execution_id, input_index) {
+            Some(path) => Ok(Some(tokio::fs::read(path).await?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn input_size(
+        &self,
+        requester: &Pubkey,
+        execution_id: &str,
+        input_index: u8,
+    ) -> Result<Option<u64>> {
+        match self.input_path(requester, execution_id, input_index) {
+            Some(path) => Ok(Some(tokio::fs::metadata(path).await?.len())),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Execution ids come from the request body, never let them escape the input folder
+fn is_safe_path_segment(segment: &str) -> bool {
+    !segment.is_empty() && segment != "." && segment != ".." && !segment.contains(['/', '\\', '\0'])
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_directory_backend() {
+        let dir = tempfile::tempdir().unwrap();
+        let requester = Pubkey::new_unique();
+        let exec_dir = dir.path().join(requester.to_string()).join("exec1");
+        std::fs::create_dir_all(&exec_dir).unwrap();
+        std::fs::write(exec_dir.join("1"), b"private").unwrap();
+        let backend = DirectoryBackend::new(dir.path());
+
+        assert_eq!(
+            backend.input(&requester, "exec1", 1).await.unwrap(),
+            Some(b"private".to_vec())
+        );
+        assert_eq!(
+            backend.input_size(&requester, "exec1", 1).await.unwrap(),
+            Some(7)
+        );
+        assert_eq!(backend.input(&requester, "exec1", 0).await.unwrap(), None);
+        assert_eq!(backend.input(&requester, "exec2", 1).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_directory_backend_only_serves_the_requesters_inputs() {
+        let dir = tempfile::tempdir().unwrap();
+        let requester = Pubkey::new_unique();
+        let exec_dir = dir.path().join(requester.to_string()).join("exec1");
+        std::fs::create_dir_all(&exec_dir).unwrap();
+        std::fs::write(exec_dir.join("1"), b"private").unwrap();
+        let other = Pubkey::new_unique();
+        std::fs::create_dir_all(dir.path().join(other.to_string()).join("exec1")).unwrap();
+        let backend = DirectoryBackend::new(dir.path());
+
+        assert_eq!(backend.input(&other, "exec1", 1).await.unwrap(), None);
+        assert_eq!(
+            backend
+                .input(&Pubkey::new_unique(), "exec1", 1)
+                .await
+                .unwrap(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_directory_backend_rejects_traversal() {
+        let dir = tempfile::tempdir().unwrap();
+        let requester = Pubkey::new_unique();
+        std::fs::create_dir_all(dir.path().join(requester.to_string())).unwrap();
+        std::fs::write(dir.path().join(requester.to_string()).join("1"), b"x").unwrap();
+        let backend = DirectoryBackend::new(dir.path());
+        assert_eq!(backend.input(&requester, ".", 1).await.unwrap(), None);
+        assert_eq!(backend.input(&requester, "..", 1).await.unwrap(), None);
+        assert_eq!(backend.input(&requester, "../etc", 1).await.unwrap(), None);
+    }
+}
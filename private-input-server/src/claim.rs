@@ -0,0 +1,101 @@
+use async_trait::async_trait;
+use bonsol_interface::{
+    bonsol_schema::root_as_execution_request_v1,
+    claim_state::ClaimStateV1,
+    util::{execution_address, execution_claim_address},
+};
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey};
+
+use crate::server::PrivateInputServerError;
+
+/// Claim checkers confirm that the caller currently holds an unexpired claim on the execution
+#[async_trait]
+pub trait ClaimChecker: Send + Sync {
+    async fn check_claim(
+        &self,
+        requester: &Pubkey,
+        execution_id: &str,
+        claimer: &Pubkey,
+    ) -> Result<(), PrivateInputServerError>;
+
+    /// Confirms the execution exists and has not expired, probes are answered before anyone claims
+    async fn check_execution(
+        &self,
+        requester: &Pubkey,
+        execution_id: &str,
+    ) -> Result<(), PrivateInputServerError>;
+}
+
+/// Reads the `ClaimStateV1` account of the execution over rpc
+pub struct RpcClaimChecker {
+    rpc_client: RpcClient,
+}
+
+impl RpcClaimChecker {
+    pub fn new(rpc_url: String) -> Self {
+        Self {
+            rpc_client: RpcClient::new_with_commitment(rpc_url, CommitmentConfig::confirmed()),
+        }
+    }
+}
+
+#[async_trait]
+impl ClaimChecker for RpcClaimChecker {
+    async fn check_claim(
+        &self,
+        requester: &Pubkey,
+        execution_id: &str,
+        claimer: &Pubkey,
+    ) -> Result<(), PrivateInputServerError> {
+        let (execution_account, _) = execution_address(requester, execution_id.as_bytes());
+        let (claim_account, _) = execution_claim_address(execution_account.as_ref());
+        let account = self
+            .rpc_client
+            .get_account_with_commitment(&claim_account, self.rpc_client.commitment())
+            .await
+            .map_err(|e| PrivateInputServerError::Rpc(e.to_string()))?
+            .value
+            .ok_or(PrivateInputServerError::NoClaim)?;
+        let claim = ClaimStateV1::load_claim_owned(&account.data)
+            .map_err(|_| PrivateInputServerError::NoClaim)?;
+        if claim.claimer != claimer.to_bytes() {
+            return Err(PrivateInputServerError::NotClaimer);
+        }
+        let slot = self
+            .rpc_client
+            .get_slot()
+            .await
+            .map_err(|e| PrivateInputServerError::Rpc(e.to_string()))?;
+        if slot > claim.block_commitment {
+            return Err(PrivateInputServerError::ClaimExpired);
+        }
+        Ok(())
+    }
+
+    async fn check_execution(
+        &self,
+        requester: &Pubkey,
+        execution_id: &str,
+    ) -> Result<(), PrivateInputServerError> {
+        let (execution_account, _) = execution_address(requester, execution_id.as_bytes());
+        let account = self
+            .rpc_client
+            .get_account_with_commitment(&execution_account, self.rpc_client.commitment())
+            .await
+            .map_err(|e| PrivateInputServerError::Rpc(e.to_string()))?
+            .value
+            .ok_or(PrivateInputServerError::NoExecution)?;
+        let execution_request = root_as_execution_request_v1(&account.data)
+            .map_err(|_| PrivateInputServerError::NoExecution)?;
+        let slot = self
+            .rpc_client
+            .get_slot()
+            .await
+            .map_err(|e| PrivateInputServerError::Rpc(e.to_string()))?;
+        if slot > execution_request.max_block_height() {
+            return Err(PrivateInputServerError::ExecutionExpired);
+        }
+        Ok(())
+    }
+}
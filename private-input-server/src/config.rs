@@ -0,0 +1,66 @@
+use {
+    figment::{
+        providers::{Format, Toml},
+        Figment,
+    },
+    serde::{Deserialize, Serialize},
+};
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub enum BackendConfig {
+    /// Inputs are read from `<folder>/<requester>/<execution_id>/<input_index>`
+    Directory { folder: String },
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct PrivateInputServerConfig {
+    #[serde(default = "default_listen_address")]
+    pub listen_address: String,
+    #[serde(default = "default_rpc_url")]
+    pub rpc_url: String,
+    #[serde(default = "default_backend_config")]
+    pub backend_config: BackendConfig,
+    #[serde(default = "default_max_request_age_secs")]
+    pub max_request_age_secs: u64,
+    #[serde(default = "default_seal_inputs")]
+    pub seal_inputs: bool,
+}
+
+fn default_listen_address() -> String {
+    "0.0.0.0:8080".to_string()
+}
+
+fn default_rpc_url() -> String {
+    "http://localhost:8899".to_string()
+}
+
+fn default_backend_config() -> BackendConfig {
+    BackendConfig::Directory {
+        folder: "./private-inputs".to_string(),
+    }
+}
+
+const fn default_max_request_age_secs() -> u64 {
+    60
+}
+
+const fn default_seal_inputs() -> bool {
+    true
+}
+
+impl Default for PrivateInputServerConfig {
+    fn default() -> Self {
+        PrivateInputServerConfig {
+            listen_address: default_listen_address(),
+            rpc_url: default_rpc_url(),
+            backend_config: default_backend_config(),
+            max_request_age_secs: default_max_request_age_secs(),
+            seal_inputs: default_seal_inputs(),
+        }
+    }
+}
+
+pub fn load_config(config_path: &str) -> PrivateInputServerConfig {
+    let figment = Figment::new().merge(Toml::file(config_path));
+    figment.extract().unwrap()
+}
@@ -0,0 +1,7 @@
+pub mod backend;
+pub mod claim;
+pub mod config;
+pub mod server;
+
+#[cfg(all(test, feature = "integration-tests"))]
+mod tests;
@@ -0,0 +1,42 @@
+use {
+    anyhow::Result,
+    bonsol_private_input_server::{
+        backend::{DirectoryBackend, InputBackend},
+        claim::RpcClaimChecker,
+        config::{self, BackendConfig},
+        server::PrivateInputServer,
+    },
+    std::sync::Arc,
+    tokio::net::TcpListener,
+    tracing::{error, info},
+};
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    tracing_subscriber::fmt()
+        .json()
+        .with_timer(tracing_subscriber::fmt::time::UtcTime::rfc_3339())
+        .init();
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 3 || args[1] != "-f" {
+        error!("Usage: bonsol-private-input-server -f <config_file>");
+        return Ok(());
+    }
+    let config = config::load_config(&args[2]);
+    let backend: Arc<dyn InputBackend> = match config.backend_config {
+        BackendConfig::Directory { folder } => {
+            info!("Serving inputs from {}", folder);
+            Arc::new(DirectoryBackend::new(folder))
+        }
+    };
+    let server = PrivateInputServer::new(
+        backend,
+        Arc::new(RpcClaimChecker::new(config.rpc_url)),
+        config.max_request_age_secs,
+        config.seal_inputs,
+    );
+    let listener = TcpListener::bind(&config.listen_address).await?;
+    info!("Listening on {}", config.listen_address);
+    axum::serve(listener, server.router()).await?;
+    Ok(())
+}
@@ -0,0 +1,493 @@
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::post,
+    Router,
+};
+use bonsol_prover::{
+    encryption::{seal_input, SEALED_INPUT_CONTENT_TYPE},
+    input_resolver::{PrivateInputRequest, PRIVATE_INPUT_SIZE_HEADER},
+};
+use solana_sdk::signature::Signature;
+use thiserror::Error;
+use tracing::{error, info};
+
+use crate::{backend::InputBackend, claim::ClaimChecker};
+
+#[derive(Debug, Error)]
+pub enum PrivateInputServerError {
+    #[error("Missing or malformed authorization")]
+    InvalidAuthorization,
+    #[error("Invalid request body")]
+    InvalidRequest,
+    #[error("Request signature does not match identity")]
+    InvalidSignature,
+    #[error("Request is too old or from the future")]
+    StaleRequest,
+    #[error("Input not found")]
+    InputNotFound,
+    #[error("Execution not found")]
+    NoExecution,
+    #[error("Execution expired")]
+    ExecutionExpired,
+    #[error("Execution has no claim")]
+    NoClaim,
+    #[error("Identity does not hold the claim")]
+    NotClaimer,
+    #[error("Claim expired")]
+    ClaimExpired,
+    #[error("Rpc error: {0}")]
+    Rpc(String),
+    #[error("Backend error: {0}")]
+    Backend(#[from] anyhow::Error),
+}
+
+impl IntoResponse for PrivateInputServerError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            PrivateInputServerError::InvalidAuthorization
+            | PrivateInputServerError::InvalidSignature
+            | PrivateInputServerError::StaleRequest => StatusCode::UNAUTHORIZED,
+            PrivateInputServerError::InvalidRequest => StatusCode::BAD_REQUEST,
+            PrivateInputServerError::InputNotFound | PrivateInputServerError::NoExecution => {
+                StatusCode::NOT_FOUND
+            }
+            PrivateInputServerError::ExecutionExpired
+            | PrivateInputServerError::NoClaim
+            | PrivateInputServerError::NotClaimer
+            | PrivateInputServerError::ClaimExpired => StatusCode::FORBIDDEN,
+            PrivateInputServerError::Rpc(_) | PrivateInputServerError::Backend(_) => {
+                error!("{}", self);
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        };
+        (status, self.to_string()).into_response()
+    }
+}
+
+pub struct PrivateInputServer {
+    backend: Arc<dyn InputBackend>,
+    claim_checker: Arc<dyn ClaimChecker>,
+    max_request_age_secs: u64,
+    seal_inputs: bool,
+}
+
+impl PrivateInputServer {
+    pub fn new(
+        backend: Arc<dyn InputBackend>,
+        claim_checker: Arc<dyn ClaimChecker>,
+        max_request_age_secs: u64,
+        seal_inputs: bool,
+    ) -> Self {
+        Self {
+            backend,
+            claim_checker,
+            max_request_age_secs,
+            seal_inputs,
+        }
+    }
+
+    /// Every path is served, the signed body says which execution and input is requested
+    pub fn router(self) -> Router {
+        Router::new()
+            .route("/", post(serve_input))
+            .route("/*path", post(serve_input))
+            .with_state(Arc::new(self))
+    }
+
+    async fn handle(
+        &self,
+        headers: &HeaderMap,
+        body: &[u8],
+    ) -> Result<Response, PrivateInputServerError> {
+        let request = verify_request(headers, body)?;
+        check_request_age(&request, self.max_request_age_secs)?;
+        let execution_id = request.claim_id();
+        // execution and claim accounts derive from the requester, the checks below confirm it
+        let requester = request.requester();
+        let index = request.input_index();
+        // nodes probe before they claim, a probe only reveals the size of the input
+        if request.is_probe() {
+            self.claim_checker
+                .check_execution(requester, execution_id)
+                .await?;
+            let size = self
+                .backend
+                .input_size(requester, execution_id, index)
+                .await?
+                .ok_or(PrivateInputServerError::InputNotFound)?;
+            info!(
+                "Probe for {} input {} by {}",
+                execution_id,
+                index,
+                request.identity()
+            );
+            return Ok((
+                StatusCode::OK,
+                [(PRIVATE_INPUT_SIZE_HEADER, size.to_string())],
+            )
+                .into_response());
+        }
+        self.claim_checker
+            .check_claim(requester, execution_id, request.identity())
+            .await?;
+        let data = self
+            .backend
+            .input(requester, execution_id, index)
+            .await?
+            .ok_or(PrivateInputServerError::InputNotFound)?;
+        info!(
+            "Serving {} input {} to {}",
+            execution_id,
+            index,
+            request.identity()
+        );
+        match request.encryption_key().filter(|_| self.seal_inputs) {
+            Some(key) => {
+                let sealed = seal_input(key, &data)?;
+                Ok(([(header::CONTENT_TYPE, SEALED_INPUT_CONTENT_TYPE)], sealed).into_response())
+            }
+            None => {
+                Ok(([(header::CONTENT_TYPE, "application/octet-stream")], data).into_response())
+            }
+        }
+    }
+}
+
+async fn serve_input(
+    State(server): State<Arc<PrivateInputServer>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    match server.handle(&headers, &body).await {
+        Ok(resp) => resp,
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Parses the request and checks it was signed by the identity it names
+/// The bearer token is the base58 signature of the exact json body
+pub fn verify_request(
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<PrivateInputRequest, PrivateInputServerError> {
+    let signature = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .and_then(|v| Signature::from_str(v).ok())
+        .ok_or(PrivateInputServerError::InvalidAuthorization)?;
+    let request: PrivateInputRequest =
+        serde_json::from_slice(body).map_err(|_| PrivateInputServerError::InvalidRequest)?;
+    if !signature.verify(request.identity().as_ref(), body) {
+        return Err(PrivateInputServerError::InvalidSignature);
+    }
+    Ok(request)
+}
+
+fn check_request_age(
+    request: &PrivateInputRequest,
+    max_request_age_secs: u64,
+) -> Result<(), PrivateInputServerError> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|_| PrivateInputServerError::StaleRequest)?
+        .as_secs();
+    if now.abs_diff(request.now_utc()) > max_request_age_secs {
+        return Err(PrivateInputServerError::StaleRequest);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use async_trait::async_trait;
+    use axum::http::HeaderValue;
+    use bonsol_interface::bonsol_schema::ProgramInputType;
+    use bonsol_prover::{
+        encryption::{derive_encryption_secret, open_sealed_input},
+        input_resolver::{
+            sign_private_input_request, DefaultInputResolver, InputResolver, ProgramInput,
+            UnresolvedInput,
+        },
+    };
+    use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+    use solana_sdk::{pubkey::Pubkey, signature::Keypair, signer::Signer};
+
+    const REQUESTER: Pubkey = Pubkey::new_from_array([7; 32]);
+
+    /// Holds input 1 of the execution "exec" of `REQUESTER`
+    struct StaticBackend;
+
+    #[async_trait]
+    impl InputBackend for StaticBackend {
+        async fn input(
+            &self,
+            requester: &Pubkey,
+            execution_id: &str,
+            input_index: u8,
+        ) -> anyhow::Result<Option<Vec<u8>>> {
+            Ok(
+                (*requester == REQUESTER && execution_id == "exec" && input_index == 1)
+                    .then(|| b"private".to_vec()),
+            )
+        }
+    }
+
+    struct StaticClaim {
+        claimer: Option<Pubkey>,
+        expired: bool,
+    }
+
+    #[async_trait]
+    impl ClaimChecker for StaticClaim {
+        async fn check_claim(
+            &self,
+            requester: &Pubkey,
+            execution_id: &str,
+            claimer: &Pubkey,
+        ) -> Result<(), PrivateInputServerError> {
+            if *requester != REQUESTER || execution_id != "exec" {
+                return Err(PrivateInputServerError::NoClaim);
+            }
+            match &self.claimer {
+                None => Err(PrivateInputServerError::NoClaim),
+                Some(c) if c != claimer => Err(PrivateInputServerError::NotClaimer),
+                Some(_) => Ok(()),
+            }
+        }
+
+        async fn check_execution(
+            &self,
+            requester: &Pubkey,
+            execution_id: &str,
+        ) -> Result<(), PrivateInputServerError> {
+            if *requester != REQUESTER || execution_id != "exec" {
+                return Err(PrivateInputServerError::NoExecution);
+            }
+            if self.expired {
+                return Err(PrivateInputServerError::ExecutionExpired);
+            }
+            Ok(())
+        }
+    }
+
+    fn server_with(claim: StaticClaim, seal_inputs: bool) -> PrivateInputServer {
+        PrivateInputServer::new(Arc::new(StaticBackend), Arc::new(claim), 60, seal_inputs)
+    }
+
+    fn server(claimer: Pubkey, seal_inputs: bool) -> PrivateInputServer {
+        server_with(
+            StaticClaim {
+                claimer: Some(claimer),
+                expired: false,
+            },
+            seal_inputs,
+        )
+    }
+
+    fn signed(kp: &Keypair, index: u8, probe: bool) -> (HeaderMap, String) {
+        signed_for(kp, &REQUESTER, index, probe)
+    }
+
+    fn signed_for(kp: &Keypair, requester: &Pubkey, index: u8, probe: bool) -> (HeaderMap, String) {
+        let (body, sig) = sign_private_input_request(kp, requester, "exec", index, probe).unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_str(&format!("Bearer {}", sig)).unwrap(),
+        );
+        (headers, body)
+    }
+
+    #[tokio::test]
+    async fn test_serves_sealed_input_to_claimer() {
+        let kp = Keypair::new();
+        let (headers, body) = signed(&kp, 1, false);
+        let resp = server(kp.pubkey(), true)
+            .handle(&headers, body.as_bytes())
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            SEALED_INPUT_CONTENT_TYPE
+        );
+        let sealed = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let data = open_sealed_input(&derive_encryption_secret(&kp), &sealed).unwrap();
+        assert_eq!(data, b"private");
+    }
+
+    #[tokio::test]
+    async fn test_probe_reports_size() {
+        let kp = Keypair::new();
+        let (headers, body) = signed(&kp, 1, true);
+        let resp = server(kp.pubkey(), true)
+            .handle(&headers, body.as_bytes())
+            .await
+            .unwrap();
+        assert_eq!(resp.headers().get(PRIVATE_INPUT_SIZE_HEADER).unwrap(), "7");
+    }
+
+    #[tokio::test]
+    async fn test_probe_before_claim() {
+        let kp = Keypair::new();
+        let unclaimed = || StaticClaim {
+            claimer: None,
+            expired: false,
+        };
+        let (headers, body) = signed(&kp, 1, true);
+        let resp = server_with(unclaimed(), true)
+            .handle(&headers, body.as_bytes())
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers().get(PRIVATE_INPUT_SIZE_HEADER).unwrap(), "7");
+
+        let (headers, body) = signed(&kp, 1, false);
+        let res = server_with(unclaimed(), true)
+            .handle(&headers, body.as_bytes())
+            .await;
+        assert!(matches!(res, Err(PrivateInputServerError::NoClaim)));
+    }
+
+    #[tokio::test]
+    async fn test_rejects_probe_of_expired_execution() {
+        let kp = Keypair::new();
+        let (headers, body) = signed(&kp, 1, true);
+        let res = server_with(
+            StaticClaim {
+                claimer: None,
+                expired: true,
+            },
+            true,
+        )
+        .handle(&headers, body.as_bytes())
+        .await;
+        assert!(matches!(
+            res,
+            Err(PrivateInputServerError::ExecutionExpired)
+        ));
+    }
+
+    /// Probes the server over http the way a node does before claiming
+    async fn probe_over_http(
+        server: PrivateInputServer,
+        requester: &Pubkey,
+        execution_id: &str,
+        index: u8,
+    ) -> anyhow::Result<()> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let router = server.router();
+        tokio::spawn(async move { axum::serve(listener, router).await });
+        let resolver = DefaultInputResolver::new(
+            Arc::new(reqwest::Client::new()),
+            Arc::new(RpcClient::new(url.clone())),
+        );
+        let inputs = vec![ProgramInput::Unresolved(UnresolvedInput {
+            index,
+            url: url.parse().unwrap(),
+            input_type: ProgramInputType::Private,
+        })];
+        let kp = Keypair::new();
+        resolver
+            .probe_private_inputs(requester, execution_id, &inputs, Arc::new(&kp))
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_node_probe_of_reference_server() {
+        let unclaimed = |expired| StaticClaim {
+            claimer: None,
+            expired,
+        };
+        probe_over_http(server_with(unclaimed(false), true), &REQUESTER, "exec", 1)
+            .await
+            .unwrap();
+        // missing input and unknown execution are 404, an expired execution is 403
+        assert!(
+            probe_over_http(server_with(unclaimed(false), true), &REQUESTER, "exec", 0)
+                .await
+                .is_err()
+        );
+        assert!(
+            probe_over_http(server_with(unclaimed(false), true), &REQUESTER, "other", 1)
+                .await
+                .is_err()
+        );
+        assert!(probe_over_http(
+            server_with(unclaimed(false), true),
+            &Pubkey::new_unique(),
+            "exec",
+            1
+        )
+        .await
+        .is_err());
+        assert!(
+            probe_over_http(server_with(unclaimed(true), true), &REQUESTER, "exec", 1)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rejects_non_claimer() {
+        let kp = Keypair::new();
+        let (headers, body) = signed(&kp, 1, false);
+        let res = server(Pubkey::new_unique(), true)
+            .handle(&headers, body.as_bytes())
+            .await;
+        assert!(matches!(res, Err(PrivateInputServerError::NotClaimer)));
+    }
+
+    #[tokio::test]
+    async fn test_rejects_request_of_another_requester() {
+        let kp = Keypair::new();
+        let other = Pubkey::new_unique();
+        let (headers, body) = signed_for(&kp, &other, 1, true);
+        let res = server(kp.pubkey(), true)
+            .handle(&headers, body.as_bytes())
+            .await;
+        assert!(matches!(res, Err(PrivateInputServerError::NoExecution)));
+
+        let (headers, body) = signed_for(&kp, &other, 1, false);
+        let res = server(kp.pubkey(), true)
+            .handle(&headers, body.as_bytes())
+            .await;
+        assert!(matches!(res, Err(PrivateInputServerError::NoClaim)));
+    }
+
+    #[tokio::test]
+    async fn test_rejects_tampered_body() {
+        let kp = Keypair::new();
+        let (headers, body) = signed(&kp, 1, false);
+        let tampered = body.replace("\"input_index\":1", "\"input_index\":2");
+        let res = server(kp.pubkey(), true)
+            .handle(&headers, tampered.as_bytes())
+            .await;
+        assert!(matches!(
+            res,
+            Err(PrivateInputServerError::InvalidSignature)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_missing_input() {
+        let kp = Keypair::new();
+        let (headers, body) = signed(&kp, 0, false);
+        let res = server(kp.pubkey(), false)
+            .handle(&headers, body.as_bytes())
+            .await;
+        assert!(matches!(res, Err(PrivateInputServerError::InputNotFound)));
+    }
+}
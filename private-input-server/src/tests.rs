@@ -0,0 +1,178 @@
+//! These tests expect a local validator at http://127.0.0.1:8899 with the bonsol program and the
+//! simple test image deployed, the same setup the tester uses.
+use std::sync::Arc;
+
+use bonsol_prover::input_resolver::{
+    DefaultInputResolver, InputResolver, ProgramInput, UnresolvedInput,
+};
+use bonsol_sdk::instructions::{claim_v1, ExecutionConfig, InputRef};
+use bonsol_sdk::{BonsolClient, InputType, ProgramInputType};
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::hash::hashv;
+use solana_sdk::signature::Keypair;
+use solana_sdk::signer::Signer;
+use tokio::net::TcpListener;
+
+use crate::{backend::DirectoryBackend, claim::RpcClaimChecker, server::PrivateInputServer};
+
+const RPC_URL: &str = "http://127.0.0.1:8899";
+const SIMPLE_IMAGE_ID: &str = "68f4b0c5f9ce034aa60ceb264a18d6c410a3af68fafd931bcfd9ebe7c1e42960";
+
+async fn funded_keypair(rpc_client: &RpcClient) -> Keypair {
+    let kp = Keypair::new();
+    let sig = rpc_client
+        .request_airdrop(&kp.pubkey(), 10_000_000_000)
+        .await
+        .unwrap();
+    rpc_client.poll_for_signature(&sig).await.unwrap();
+    kp
+}
+
+async fn start_server(inputs: &std::path::Path) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    let router = PrivateInputServer::new(
+        Arc::new(DirectoryBackend::new(inputs)),
+        Arc::new(RpcClaimChecker::new(RPC_URL.to_string())),
+        60,
+        true,
+    )
+    .router();
+    tokio::spawn(async move { axum::serve(listener, router).await });
+    url
+}
+
+fn private_input(url: &str) -> Vec<ProgramInput> {
+    vec![ProgramInput::Unresolved(UnresolvedInput {
+        index: 1,
+        url: url.parse().unwrap(),
+        input_type: ProgramInputType::Private,
+    })]
+}
+
+#[tokio::test]
+async fn serves_private_input_only_to_claimer() {
+    let rpc_client = RpcClient::new(RPC_URL.to_string());
+    let bonsol_client = BonsolClient::new(RPC_URL.to_string());
+    let requester = funded_keypair(&rpc_client).await;
+    let claimer = funded_keypair(&rpc_client).await;
+    let execution_id: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(16)
+        .map(char::from)
+        .collect();
+
+    let inputs = tempfile::tempdir().unwrap();
+    let exec_dir = inputs
+        .path()
+        .join(requester.pubkey().to_string())
+        .join(&execution_id);
+    std::fs::create_dir_all(&exec_dir).unwrap();
+    std::fs::write(exec_dir.join("1"), b"private input").unwrap();
+    let url = start_server(inputs.path()).await;
+
+    let public_input = "{\"attestation\":\"test\"}";
+    let input_hash = hashv(&[public_input.as_bytes(), b"private input"]);
+    let slot = bonsol_client.get_current_slot().await.unwrap();
+    let ixs = bonsol_client
+        .execute_v1(
+            &requester.pubkey(),
+            SIMPLE_IMAGE_ID,
+            &execution_id,
+            vec![
+                InputRef::new(InputType::PublicData, public_input.as_bytes()),
+                InputRef::new(InputType::Private, url.as_bytes()),
+            ],
+            10000,
+            slot + 1000,
+            ExecutionConfig {
+                verify_input_hash: true,
+                input_hash: Some(input_hash.as_ref()),
+                forward_output: false,
+            },
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+    bonsol_client
+        .send_txn_standard(&requester, ixs)
+        .await
+        .unwrap();
+
+    let resolver = DefaultInputResolver::new(
+        Arc::new(reqwest::Client::new()),
+        Arc::new(RpcClient::new(RPC_URL.to_string())),
+    );
+    // no claim yet, the input can be probed but nobody gets it
+    let mut unclaimed = private_input(&url);
+    resolver
+        .probe_private_inputs(
+            &requester.pubkey(),
+            &execution_id,
+            &unclaimed,
+            Arc::new(&claimer),
+        )
+        .await
+        .unwrap();
+    assert!(resolver
+        .resolve_private_inputs(
+            &requester.pubkey(),
+            &execution_id,
+            &mut unclaimed,
+            Arc::new(&claimer)
+        )
+        .await
+        .is_err());
+
+    let slot = bonsol_client.get_current_slot().await.unwrap();
+    let claim = claim_v1(
+        &claimer.pubkey(),
+        &requester.pubkey(),
+        &execution_id,
+        slot + 500,
+    )
+    .unwrap();
+    bonsol_client
+        .send_txn_standard(&claimer, vec![claim])
+        .await
+        .unwrap();
+
+    let other = Keypair::new();
+    let mut not_claimer = private_input(&url);
+    assert!(resolver
+        .resolve_private_inputs(
+            &requester.pubkey(),
+            &execution_id,
+            &mut not_claimer,
+            Arc::new(&other)
+        )
+        .await
+        .is_err());
+
+    let mut claimed = private_input(&url);
+    resolver
+        .probe_private_inputs(
+            &requester.pubkey(),
+            &execution_id,
+            &claimed,
+            Arc::new(&claimer),
+        )
+        .await
+        .unwrap();
+    resolver
+        .resolve_private_inputs(
+            &requester.pubkey(),
+            &execution_id,
+            &mut claimed,
+            Arc::new(&claimer),
+        )
+        .await
+        .unwrap();
+    match &claimed[0] {
+        ProgramInput::Resolved(ri) => assert_eq!(ri.data, b"private input"),
+        _ => panic!("input was not resolved"),
+    }
+}
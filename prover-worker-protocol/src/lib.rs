@@ -0,0 +1,79 @@
+use serde::{Deserialize, Serialize};
+
+/// A request to a prover worker, the worker reads one bincode request from stdin and writes one response to
+/// stdout. Everything in the protocol is plain bytes so workers built against other risc0 versions speak it too
+#[derive(Debug, Serialize, Deserialize)]
+pub enum ProverWorkerRequest {
+    /// Asks for the groth16 verifier digest the worker was built with
+    Version,
+    Prove {
+        elf: Vec<u8>,
+        inputs: Vec<WorkerInput>,
+    },
+    /// Compresses a receipt returned by `Prove` of the same worker
+    Compress {
+        receipt: Vec<u8>,
+        tools_path: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkerInput {
+    /// Proof inputs are bincode receipts that are added as assumptions
+    pub proof: bool,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum ProverWorkerResponse {
+    Version {
+        verifier_digest: String,
+    },
+    Proven {
+        journal: Vec<u8>,
+        assumptions_digest: Vec<u8>,
+        /// Opaque to the node, it is only passed back in `Compress`
+        receipt: Vec<u8>,
+    },
+    Compressed {
+        execution_digest: Vec<u8>,
+        exit_code_system: u32,
+        exit_code_user: u32,
+        proof: Vec<u8>,
+    },
+    Failed(WorkerFailure),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum WorkerFailure {
+    GuestPanic(String),
+    CycleLimitExceeded,
+    ProofGeneration(String),
+    Compression(String),
+    InvalidRequest(String),
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_request_roundtrip() {
+        let request = ProverWorkerRequest::Prove {
+            elf: vec![1, 2, 3],
+            inputs: vec![WorkerInput {
+                proof: true,
+                data: vec![4],
+            }],
+        };
+
+        let decoded: ProverWorkerRequest =
+            bincode::deserialize(&bincode::serialize(&request).unwrap()).unwrap();
+
+        assert!(matches!(
+            decoded,
+            ProverWorkerRequest::Prove { elf, inputs }
+                if elf == [1, 2, 3] && inputs.len() == 1 && inputs[0].proof && inputs[0].data == [4]
+        ));
+    }
+}
@@ -0,0 +1,49 @@
+mod prover;
+
+use {
+    anyhow::Result,
+    bonsol_prover_worker_protocol::{ProverWorkerRequest, ProverWorkerResponse, WorkerFailure},
+    risc0_zkvm::{sha::Digestible, Groth16ReceiptVerifierParameters},
+    rlimit::Resource,
+    tokio::io::{AsyncReadExt, AsyncWriteExt},
+    tracing::error,
+};
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // proving needs the same stack as the node
+    match rlimit::setrlimit(Resource::STACK, u64::MAX, u64::MAX) {
+        Ok(_) => {}
+        Err(e) => error!("Error setting rlimit: {}", e),
+    }
+    // stdout carries the response to the node
+    tracing_subscriber::fmt()
+        .json()
+        .with_writer(std::io::stderr)
+        .with_timer(tracing_subscriber::fmt::time::UtcTime::rfc_3339())
+        .init();
+    run().await
+}
+
+/// Serves a single request from stdin, logs must go to stderr since stdout carries the response
+async fn run() -> Result<()> {
+    let mut request = Vec::new();
+    tokio::io::stdin().read_to_end(&mut request).await?;
+    let response = match bincode::deserialize::<ProverWorkerRequest>(&request) {
+        Ok(ProverWorkerRequest::Version) => ProverWorkerResponse::Version {
+            verifier_digest: Groth16ReceiptVerifierParameters::default()
+                .digest()
+                .to_string(),
+        },
+        Ok(ProverWorkerRequest::Prove { elf, inputs }) => prover::prove(elf, inputs).await,
+        Ok(ProverWorkerRequest::Compress {
+            receipt,
+            tools_path,
+        }) => prover::compress(receipt, tools_path).await,
+        Err(e) => ProverWorkerResponse::Failed(WorkerFailure::InvalidRequest(e.to_string())),
+    };
+    let mut stdout = tokio::io::stdout();
+    stdout.write_all(&bincode::serialize(&response)?).await?;
+    stdout.flush().await?;
+    Ok(())
+}
@@ -0,0 +1,260 @@
+//! Proving and compression with the risc0 version this worker is built against. Only api that risc0 1.0 and
+//! 1.2 share is used here, so the same source builds workers for every supported prover version.
+
+use {
+    anyhow::{anyhow, Context, Result},
+    bonsol_prover_worker_protocol::{ProverWorkerResponse, WorkerFailure, WorkerInput},
+    iop::*,
+    num_bigint::BigUint,
+    num_traits::Num,
+    risc0_groth16::{ProofJson, Seal},
+    risc0_zkp::{
+        core::{
+            digest::{Digest, DIGEST_WORDS},
+            hash::poseidon_254::digest_to_fr,
+        },
+        field::baby_bear::BabyBearElem,
+    },
+    risc0_zkvm::{
+        get_prover_server, recursion::identity_p254, sha::Digestible, ExecutorEnv, ExecutorImpl,
+        ExitCode, InnerReceipt, MaybePruned, ProverOpts, Receipt, ReceiptClaim, SuccinctReceipt,
+        VerifierContext,
+    },
+    std::{env::consts::ARCH, fmt::Write as _, path::Path},
+    tempfile::tempdir,
+    tokio::process::Command,
+    tracing::info,
+};
+
+/// Messages risc0 uses for panics and faults of the guest itself, only matched when an error has no type to go by
+const GUEST_FAULTS: [&str; 5] = [
+    "guest panicked",
+    "illegal instruction",
+    "invalid load address",
+    "invalid store address",
+    "unaligned",
+];
+
+pub async fn prove(elf: Vec<u8>, inputs: Vec<WorkerInput>) -> ProverWorkerResponse {
+    tokio::task::spawn_blocking(move || prove_blocking(&elf, inputs))
+        .await
+        .unwrap_or_else(|e| Err(WorkerFailure::ProofGeneration(e.to_string())))
+        .unwrap_or_else(ProverWorkerResponse::Failed)
+}
+
+fn prove_blocking(
+    elf: &[u8],
+    inputs: Vec<WorkerInput>,
+) -> Result<ProverWorkerResponse, WorkerFailure> {
+    let proof_generation = |e: anyhow::Error| WorkerFailure::ProofGeneration(e.to_string());
+    let mut env_builder = ExecutorEnv::builder();
+    for input in inputs {
+        if input.proof {
+            let receipt: Receipt = bincode::deserialize(&input.data)
+                .map_err(|e| WorkerFailure::InvalidRequest(e.to_string()))?;
+            env_builder.add_assumption(receipt);
+        } else {
+            env_builder.write_slice(&input.data);
+        }
+    }
+    let env = env_builder.build().map_err(proof_generation)?;
+    let mut exec = ExecutorImpl::from_elf(env, elf).map_err(proof_generation)?;
+    let session = exec.run().map_err(classify_execution_error)?;
+    if matches!(session.exit_code, ExitCode::SessionLimit) {
+        return Err(WorkerFailure::CycleLimitExceeded);
+    }
+    let prover = get_prover_server(&ProverOpts::default()).map_err(proof_generation)?;
+    let info = prover
+        .prove_session(&VerifierContext::default(), &session)
+        .map_err(proof_generation)?;
+    let InnerReceipt::Composite(cr) = &info.receipt.inner else {
+        return Err(WorkerFailure::ProofGeneration(
+            "expected a composite receipt".to_string(),
+        ));
+    };
+    let sr = prover.composite_to_succinct(cr).map_err(proof_generation)?;
+    let ident_receipt = identity_p254(&sr).map_err(proof_generation)?;
+    let assumptions_digest = match sr.claim {
+        MaybePruned::Value(rc) => match rc.output {
+            MaybePruned::Value(Some(op)) => match op.assumptions {
+                MaybePruned::Value(ass) => ass.digest(),
+                _ => return Err(pruned_claim()),
+            },
+            _ => return Err(pruned_claim()),
+        },
+        _ => return Err(pruned_claim()),
+    };
+    Ok(ProverWorkerResponse::Proven {
+        journal: info.receipt.journal.bytes,
+        assumptions_digest: assumptions_digest.as_bytes().to_vec(),
+        receipt: bincode::serialize(&ident_receipt)
+            .map_err(|e| WorkerFailure::ProofGeneration(e.to_string()))?,
+    })
+}
+
+fn pruned_claim() -> WorkerFailure {
+    WorkerFailure::ProofGeneration("receipt claim is pruned".to_string())
+}
+
+/// Io errors are the worker's failure and typed, they are never reported as the guest's. risc0 surfaces
+/// guest panics, faults and the session limit as untyped errors, so as a fallback they are told apart by
+/// their message. Should risc0 change that wording they are reported as a generic proving failure.
+fn classify_execution_error(e: anyhow::Error) -> WorkerFailure {
+    let msg = e.to_string();
+    if e.chain().any(|cause| cause.is::<std::io::Error>()) {
+        return WorkerFailure::ProofGeneration(msg);
+    }
+    let lower = msg.to_lowercase();
+    if lower.contains("session limit") {
+        WorkerFailure::CycleLimitExceeded
+    } else if GUEST_FAULTS.iter().any(|fault| lower.contains(fault)) {
+        WorkerFailure::GuestPanic(msg)
+    } else {
+        WorkerFailure::ProofGeneration(msg)
+    }
+}
+
+pub async fn compress(receipt: Vec<u8>, tools_path: String) -> ProverWorkerResponse {
+    let receipt: SuccinctReceipt<ReceiptClaim> = match bincode::deserialize(&receipt) {
+        Ok(receipt) => receipt,
+        Err(e) => {
+            return ProverWorkerResponse::Failed(WorkerFailure::InvalidRequest(e.to_string()))
+        }
+    };
+    compress_receipt(&tools_path, receipt)
+        .await
+        .unwrap_or_else(|e| ProverWorkerResponse::Failed(WorkerFailure::Compression(e.to_string())))
+}
+
+/// Compresses the receipt to groth16 with the stark compression tools of this risc0 version
+async fn compress_receipt(
+    tools_path: &str,
+    receipt: SuccinctReceipt<ReceiptClaim>,
+) -> Result<ProverWorkerResponse> {
+    if !(ARCH == "x86_64" || ARCH == "x86") {
+        return Err(anyhow!("Stark compression is only supported on x86"));
+    }
+    let tmp = tempdir()?;
+    let prove_dir = tmp.path();
+    let root_path = Path::new(tools_path);
+    let inputs = prove_dir.join("input.json");
+    let witness = prove_dir.join("out.wtns");
+    let proof_out = prove_dir.join("proof.json");
+    let public = prove_dir.join("public.json");
+    tokio::fs::write(&inputs, seal_to_json(&receipt.get_seal_bytes())?).await?;
+    let status = Command::new(root_path.join("stark_verify"))
+        .arg(&inputs)
+        .arg(&witness)
+        .output()
+        .await?;
+    if !status.status.success() {
+        info!("witness {:?}", status);
+        return Err(anyhow!("stark_verify failed"));
+    }
+    let snark_status = Command::new(root_path.join("rapidsnark"))
+        .arg(root_path.join("stark_verify_final.zkey"))
+        .arg(&witness)
+        .arg(&proof_out)
+        .arg(&public)
+        .output()
+        .await?;
+    if !snark_status.status.success() {
+        info!("snark {:?}", snark_status);
+        return Err(anyhow!("rapidsnark failed"));
+    }
+    let proof: ProofJson = serde_json::from_slice(&tokio::fs::read(proof_out).await?)?;
+    let seal: Seal = proof.try_into()?;
+    let MaybePruned::Value(rc) = receipt.claim else {
+        return Err(anyhow!("receipt claim is pruned"));
+    };
+    let (exit_code_system, exit_code_user) = match rc.exit_code {
+        ExitCode::Halted(user_exit) => (0, user_exit),
+        ExitCode::Paused(user_exit) => (1, user_exit),
+        ExitCode::SystemSplit => (2, 0),
+        ExitCode::SessionLimit => (2, 2),
+    };
+    Ok(ProverWorkerResponse::Compressed {
+        execution_digest: rc.post.digest().as_bytes().to_vec(),
+        exit_code_system,
+        exit_code_user,
+        proof: seal.to_vec(),
+    })
+}
+
+/// The input of the stark_verify circuit, like `async_to_json` of the node
+fn seal_to_json(seal: &[u8]) -> Result<String> {
+    let mut iop = vec![0u32; K_SEAL_WORDS];
+    bytemuck::cast_slice_mut::<u32, u8>(&mut iop)
+        .copy_from_slice(seal.get(..K_SEAL_WORDS * 4).context("seal is too short")?);
+    let mut json = String::from("{\n  \"iop\": [\n");
+    let mut pos = 0;
+    for seal_type in K_SEAL_TYPES.iter().take(K_SEAL_ELEMS) {
+        if pos != 0 {
+            json.push_str(",\n");
+        }
+        match seal_type {
+            IopType::Fp => {
+                let value = BabyBearElem::new_raw(iop[pos]).as_u32();
+                pos += 1;
+                write!(json, "    \"{value}\"")?;
+            }
+            _ => {
+                let digest = Digest::try_from(&iop[pos..pos + DIGEST_WORDS])?;
+                let value = digest_to_decimal(&digest)?;
+                pos += 8;
+                write!(json, "    \"{value}\"")?;
+            }
+        }
+    }
+    json.push_str("\n  ]\n}");
+    Ok(json)
+}
+
+fn digest_to_decimal(digest: &Digest) -> Result<String> {
+    to_decimal(&format!("{:?}", digest_to_fr(digest))).context("digest_to_decimal failed")
+}
+
+fn to_decimal(s: &str) -> Option<String> {
+    s.strip_prefix("Fr(0x")
+        .and_then(|s| s.strip_suffix(')'))
+        .and_then(|stripped| BigUint::from_str_radix(stripped, 16).ok())
+        .map(|n| n.to_str_radix(10))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_classify_execution_error() {
+        let classified = |msg: &str| classify_execution_error(anyhow!(msg.to_string()));
+        assert!(matches!(
+            classified("Session limit exceeded"),
+            WorkerFailure::CycleLimitExceeded
+        ));
+        assert!(matches!(
+            classified("Guest panicked: oops"),
+            WorkerFailure::GuestPanic(_)
+        ));
+        assert!(matches!(
+            classified("Could not parse ELF"),
+            WorkerFailure::ProofGeneration(_)
+        ));
+        let io = std::io::Error::new(std::io::ErrorKind::Other, "guest panicked");
+        assert!(matches!(
+            classify_execution_error(anyhow::Error::from(io)),
+            WorkerFailure::ProofGeneration(_)
+        ));
+    }
+
+    #[test]
+    fn test_to_decimal() {
+        assert_eq!(to_decimal("Fr(0xff)").as_deref(), Some("255"));
+        assert_eq!(to_decimal("0xff"), None);
+    }
+
+    #[test]
+    fn test_seal_to_json_rejects_short_seals() {
+        assert!(seal_to_json(&[0u8; 16]).is_err());
+    }
+}
@@ -0,0 +1,465 @@
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::time::Duration;
+
+use anyhow::Result;
+use bytes::Bytes;
+use cid::Cid;
+use reqwest::Url;
+use sha2::{Digest, Sha256};
+
+use crate::fetch::{FetchError, UrlFetcher};
+
+pub const IPFS_SCHEME: &str = "ipfs";
+pub const ARWEAVE_SCHEME: &str = "ar";
+
+/// Gateways tried in order when no others are configured
+pub const DEFAULT_IPFS_GATEWAYS: &[&str] = &[
+    "https://trustless-gateway.link",
+    "https://ipfs.io",
+    "https://dweb.link",
+];
+pub const DEFAULT_ARWEAVE_GATEWAYS: &[&str] = &["https://arweave.net", "https://ar-io.net"];
+
+const CAR_CONTENT_TYPE: &str = "application/vnd.ipld.car";
+const SHA2_256: u64 = 0x12;
+const RAW_CODEC: u64 = 0x55;
+const DAG_PB_CODEC: u64 = 0x70;
+const UNIXFS_RAW: u64 = 0;
+const UNIXFS_FILE: u64 = 2;
+const MAX_DAG_DEPTH: usize = 32;
+
+/// Ordered lists of gateways used to fetch `ipfs://` and `ar://` uris
+#[derive(Debug, Clone)]
+pub struct Gateways {
+    ipfs: Vec<Url>,
+    arweave: Vec<Url>,
+}
+
+impl Gateways {
+    pub fn new(ipfs: &[impl AsRef<str>], arweave: &[impl AsRef<str>]) -> Result<Self> {
+        Ok(Gateways {
+            ipfs: parse_gateways(ipfs)?,
+            arweave: parse_gateways(arweave)?,
+        })
+    }
+}
+
+fn parse_gateways(gateways: &[impl AsRef<str>]) -> Result<Vec<Url>> {
+    gateways
+        .iter()
+        .map(|g| Ok(Url::parse(g.as_ref())?))
+        .collect()
+}
+
+impl Default for Gateways {
+    fn default() -> Self {
+        Gateways::new(DEFAULT_IPFS_GATEWAYS, DEFAULT_ARWEAVE_GATEWAYS)
+            .expect("Default gateways are valid urls")
+    }
+}
+
+pub fn is_content_addressed(url: &Url) -> bool {
+    matches!(url.scheme(), IPFS_SCHEME | ARWEAVE_SCHEME)
+}
+
+/// Fetches an `ipfs://<cid>` or `ar://<txid>` uri, trying each gateway in order until one succeeds
+/// IPFS content is requested as a CAR and every block is checked against its CID, so a gateway cannot serve altered data
+pub async fn fetch_content_addressed(
+    fetcher: &UrlFetcher,
+    url: &Url,
+    max_size: usize,
+    timeout: Duration,
+) -> Result<Bytes, FetchError> {
+    let gateways = fetcher.gateways();
+    let mut errors = Vec::new();
+    match url.scheme() {
+        IPFS_SCHEME => {
+            let cid = ipfs_cid(url).map_err(|e| FetchError::InvalidUri(e.to_string()))?;
+            for gateway in &gateways.ipfs {
+                match fetch_ipfs(fetcher, gateway, &cid, max_size, timeout).await {
+                    Ok(data) => return Ok(data),
+                    Err(e) => errors.push(format!("{}: {}", gateway, e)),
+                }
+            }
+        }
+        ARWEAVE_SCHEME => {
+            let txid = arweave_txid(url).map_err(|e| FetchError::InvalidUri(e.to_string()))?;
+            for gateway in &gateways.arweave {
+                match fetch_arweave(fetcher, gateway, txid, max_size, timeout).await {
+                    Ok(data) => return Ok(data),
+                    Err(e) => errors.push(format!("{}: {}", gateway, e)),
+                }
+            }
+        }
+        scheme => {
+            return Err(FetchError::InvalidUri(format!(
+                "Unsupported uri scheme {}",
+                scheme
+            )))
+        }
+    }
+    Err(FetchError::AllGatewaysFailed(errors.join(", ")))
+}
+
+fn ipfs_cid(url: &Url) -> Result<Cid> {
+    if !matches!(url.path(), "" | "/") || url.query().is_some() {
+        return Err(anyhow::anyhow!("Only bare ipfs cids are supported"));
+    }
+    let cid = url.host_str().ok_or(anyhow::anyhow!("Missing cid"))?;
+    Ok(Cid::try_from(cid)?)
+}
+
+fn arweave_txid(url: &Url) -> Result<&str> {
+    let txid = url.host_str().unwrap_or_default();
+    let valid = txid.len() == 43
+        && txid
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_');
+    if !valid || !matches!(url.path(), "" | "/") {
+        return Err(anyhow::anyhow!("Invalid arweave transaction id"));
+    }
+    Ok(txid)
+}
+
+fn gateway_url(gateway: &Url, path: &str) -> Result<Url, FetchError> {
+    Url::parse(&format!(
+        "{}/{}",
+        gateway.as_str().trim_end_matches('/'),
+        path
+    ))
+    .map_err(|e| FetchError::InvalidUri(e.to_string()))
+}
+
+async fn fetch_ipfs(
+    fetcher: &UrlFetcher,
+    gateway: &Url,
+    cid: &Cid,
+    max_size: usize,
+    timeout: Duration,
+) -> Result<Bytes, FetchError> {
+    let url = gateway_url(gateway, &format!("ipfs/{}?format=car&dag-scope=all", cid))?;
+    // CAR framing and dag-pb nodes add some overhead on top of the file itself
+    let car = fetcher
+        .get(
+            &url,
+            Some(CAR_CONTENT_TYPE),
+            max_size.saturating_mul(2),
+            timeout,
+        )
+        .await?;
+    read_verified_file(&car, cid, max_size)
+        .map(Bytes::from)
+        .map_err(|e| match e.downcast::<FetchError>() {
+            Ok(e) => e,
+            Err(e) => FetchError::InvalidContent(e.to_string()),
+        })
+}
+
+async fn fetch_arweave(
+    fetcher: &UrlFetcher,
+    gateway: &Url,
+    txid: &str,
+    max_size: usize,
+    timeout: Duration,
+) -> Result<Bytes, FetchError> {
+    let url = gateway_url(gateway, txid)?;
+    fetcher.get(&url, None, max_size, timeout).await
+}
+
+/// Verifies every block of a CARv1 against its CID and reassembles the UnixFS file rooted at `root`
+pub fn read_verified_file(car: &[u8], root: &Cid, max_size: usize) -> Result<Vec<u8>> {
+    let blocks = read_car_blocks(car)?;
+    let mut out = Vec::new();
+    assemble_file(&blocks, root, &mut out, max_size, 0)?;
+    Ok(out)
+}
+
+fn read_car_blocks(mut car: &[u8]) -> Result<HashMap<Cid, &[u8]>> {
+    // the header only lists the roots, the root we want is already known
+    let header_len = read_varint(&mut car)? as usize;
+    take(&mut car, header_len)?;
+    let mut blocks = HashMap::new();
+    while !car.is_empty() {
+        let section_len = read_varint(&mut car)? as usize;
+        let section = take(&mut car, section_len)?;
+        let mut cursor = Cursor::new(section);
+        let cid = Cid::read_bytes(&mut cursor)?;
+        let block = &section[cursor.position() as usize..];
+        verify_block(&cid, block)?;
+        blocks.insert(cid, block);
+    }
+    Ok(blocks)
+}
+
+fn verify_block(cid: &Cid, block: &[u8]) -> Result<()> {
+    if cid.hash().code() != SHA2_256 {
+        return Err(anyhow::anyhow!("Unsupported multihash in {}", cid));
+    }
+    if Sha256::digest(block).as_slice() != cid.hash().digest() {
+        return Err(anyhow::anyhow!("Block does not match cid {}", cid));
+    }
+    Ok(())
+}
+
+fn assemble_file(
+    blocks: &HashMap<Cid, &[u8]>,
+    cid: &Cid,
+    out: &mut Vec<u8>,
+    max_size: usize,
+    depth: usize,
+) -> Result<()> {
+    if depth > MAX_DAG_DEPTH {
+        return Err(anyhow::anyhow!("Dag too deep"));
+    }
+    let block = blocks
+        .get(cid)
+        .ok_or(anyhow::anyhow!("Missing block {}", cid))?;
+    match cid.codec() {
+        RAW_CODEC => append(out, block, max_size),
+        DAG_PB_CODEC => {
+            let mut data = None;
+            let mut links = Vec::new();
+            for (field, value) in read_pb_fields(block)? {
+                match (field, value) {
+                    (1, PbField::Bytes(d)) => data = Some(d),
+                    (2, PbField::Bytes(link)) => links.push(read_pb_link(link)?),
+                    _ => {}
+                }
+            }
+            let unixfs = data.ok_or(anyhow::anyhow!("Node {} is not unixfs", cid))?;
+            let mut kind = None;
+            for (field, value) in read_pb_fields(unixfs)? {
+                match (field, value) {
+                    (1, PbField::Varint(k)) => kind = Some(k),
+                    (2, PbField::Bytes(d)) => append(out, d, max_size)?,
+                    _ => {}
+                }
+            }
+            if !matches!(kind, Some(UNIXFS_RAW | UNIXFS_FILE)) {
+                return Err(anyhow::anyhow!("Only unixfs files are supported"));
+            }
+            for link in links.iter() {
+                assemble_file(blocks, link, out, max_size, depth + 1)?;
+            }
+            Ok(())
+        }
+        codec => Err(anyhow::anyhow!("Unsupported codec {:#x}", codec)),
+    }
+}
+
+fn append(out: &mut Vec<u8>, data: &[u8], max_size: usize) -> Result<()> {
+    if out.len() + data.len() > max_size {
+        return Err(FetchError::TooLarge.into());
+    }
+    out.extend_from_slice(data);
+    Ok(())
+}
+
+fn read_pb_link(link: &[u8]) -> Result<Cid> {
+    for (field, value) in read_pb_fields(link)? {
+        if let (1, PbField::Bytes(hash)) = (field, value) {
+            return Ok(Cid::try_from(hash)?);
+        }
+    }
+    Err(anyhow::anyhow!("Link without hash"))
+}
+
+enum PbField<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+}
+
+/// Minimal protobuf reader, dag-pb and unixfs only use varint and length delimited fields
+fn read_pb_fields(mut data: &[u8]) -> Result<Vec<(u64, PbField)>> {
+    let mut fields = Vec::new();
+    while !data.is_empty() {
+        let key = read_varint(&mut data)?;
+        let value = match key & 0x7 {
+            0 => PbField::Varint(read_varint(&mut data)?),
+            2 => {
+                let len = read_varint(&mut data)? as usize;
+                PbField::Bytes(take(&mut data, len)?)
+            }
+            1 => {
+                take(&mut data, 8)?;
+                continue;
+            }
+            5 => {
+                take(&mut data, 4)?;
+                continue;
+            }
+            wire_type => return Err(anyhow::anyhow!("Invalid wire type {}", wire_type)),
+        };
+        fields.push((key >> 3, value));
+    }
+    Ok(fields)
+}
+
+fn read_varint(data: &mut &[u8]) -> Result<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = data
+            .split_first()
+            .ok_or(anyhow::anyhow!("Truncated varint"))?;
+        *data = rest;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(anyhow::anyhow!("Varint overflow"))
+}
+
+fn take<'a>(data: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+    if len > data.len() {
+        return Err(anyhow::anyhow!("Truncated data"));
+    }
+    let (head, rest) = data.split_at(len);
+    *data = rest;
+    Ok(head)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fetch::RetryPolicy;
+    use cid::multihash::Multihash;
+
+    fn varint(mut v: u64, out: &mut Vec<u8>) {
+        while v >= 0x80 {
+            out.push((v as u8) | 0x80);
+            v >>= 7;
+        }
+        out.push(v as u8);
+    }
+
+    fn pb_bytes(field: u64, data: &[u8], out: &mut Vec<u8>) {
+        varint((field << 3) | 2, out);
+        varint(data.len() as u64, out);
+        out.extend_from_slice(data);
+    }
+
+    fn cid_of(codec: u64, block: &[u8]) -> Cid {
+        let mh = Multihash::<64>::wrap(SHA2_256, &Sha256::digest(block)).unwrap();
+        Cid::new_v1(codec, mh)
+    }
+
+    fn car(blocks: &[(Cid, Vec<u8>)]) -> Vec<u8> {
+        // the reader skips the header so its content does not matter here
+        let mut out = vec![1, 0];
+        for (cid, block) in blocks {
+            let cid = cid.to_bytes();
+            varint((cid.len() + block.len()) as u64, &mut out);
+            out.extend_from_slice(&cid);
+            out.extend_from_slice(block);
+        }
+        out
+    }
+
+    fn file_dag() -> (Cid, Vec<(Cid, Vec<u8>)>) {
+        let leaves = [b"hello ".to_vec(), b"bonsol".to_vec()];
+        let mut root = Vec::new();
+        for leaf in leaves.iter() {
+            let mut link = Vec::new();
+            pb_bytes(1, &cid_of(RAW_CODEC, leaf).to_bytes(), &mut link);
+            pb_bytes(2, &link, &mut root);
+        }
+        pb_bytes(1, &[0x08, UNIXFS_FILE as u8], &mut root);
+        let root_cid = cid_of(DAG_PB_CODEC, &root);
+        let mut blocks = vec![(root_cid, root)];
+        blocks.extend(leaves.iter().map(|l| (cid_of(RAW_CODEC, l), l.clone())));
+        (root_cid, blocks)
+    }
+
+    #[test]
+    fn test_raw_block() {
+        let cid = cid_of(RAW_CODEC, b"raw input");
+        let car = car(&[(cid, b"raw input".to_vec())]);
+        assert_eq!(read_verified_file(&car, &cid, 1024).unwrap(), b"raw input");
+    }
+
+    #[test]
+    fn test_unixfs_file() {
+        let (root, blocks) = file_dag();
+        let car = car(&blocks);
+        assert_eq!(
+            read_verified_file(&car, &root, 1024).unwrap(),
+            b"hello bonsol"
+        );
+        assert_eq!(
+            read_verified_file(&car, &root, 8).unwrap_err().to_string(),
+            "Max size exceeded"
+        );
+    }
+
+    #[test]
+    fn test_tampered_block_rejected() {
+        let (root, mut blocks) = file_dag();
+        blocks[2].1 = b"evil".to_vec();
+        let res = read_verified_file(&car(&blocks), &root, 1024);
+        assert!(res
+            .unwrap_err()
+            .to_string()
+            .starts_with("Block does not match"));
+    }
+
+    #[test]
+    fn test_missing_block_rejected() {
+        let (root, mut blocks) = file_dag();
+        blocks.pop();
+        assert!(read_verified_file(&car(&blocks), &root, 1024).is_err());
+    }
+
+    #[test]
+    fn test_arweave_txid() {
+        let url = Url::parse("ar://bNbA3TEQVL60xlgCcqdz4ZPHFZ711cZ3hmkpGttDt_U").unwrap();
+        assert_eq!(
+            arweave_txid(&url).unwrap(),
+            "bNbA3TEQVL60xlgCcqdz4ZPHFZ711cZ3hmkpGttDt_U"
+        );
+        assert!(arweave_txid(&Url::parse("ar://short").unwrap()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_ipfs_gateway_failover() {
+        let (root, blocks) = file_dag();
+        let path = format!("/ipfs/{}", root);
+        let mut down = mockito::Server::new_async().await;
+        let down_mock = down
+            .mock("GET", path.as_str())
+            .match_query(mockito::Matcher::Any)
+            .with_status(502)
+            .create_async()
+            .await;
+        let mut up = mockito::Server::new_async().await;
+        let up_mock = up
+            .mock("GET", path.as_str())
+            .match_query(mockito::Matcher::Any)
+            .match_header("accept", CAR_CONTENT_TYPE)
+            .with_status(200)
+            .with_body(car(&blocks))
+            .create_async()
+            .await;
+        let gateways = Gateways::new(&[down.url(), up.url()], &[] as &[String]).unwrap();
+        let url = Url::parse(&format!("ipfs://{}", root)).unwrap();
+
+        let fetcher = UrlFetcher::new(
+            reqwest::Client::new(),
+            gateways,
+            RetryPolicy {
+                max_attempts: 1,
+                ..Default::default()
+            },
+        );
+
+        let data = fetcher
+            .fetch(&url, 1024, Duration::from_secs(30))
+            .await
+            .unwrap();
+
+        assert_eq!(data.as_ref(), b"hello bonsol");
+        down_mock.assert();
+        up_mock.assert();
+    }
+}
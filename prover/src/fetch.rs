@@ -0,0 +1,344 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use bytes::{Bytes, BytesMut};
+use futures_util::StreamExt;
+use rand::Rng;
+use reqwest::header::{HeaderMap, ACCEPT, RETRY_AFTER};
+use reqwest::{StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::content::{fetch_content_addressed, is_content_addressed, Gateways};
+use crate::url_guard::{is_guard_error, UrlGuard, UrlGuardError};
+
+#[derive(Debug, Error)]
+pub enum FetchError {
+    #[error("Http status {status}")]
+    Status {
+        status: StatusCode,
+        retry_after: Option<Duration>,
+    },
+    #[error("Max size exceeded")]
+    TooLarge,
+    #[error("Request failed: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("Url not allowed: {0}")]
+    Blocked(#[from] UrlGuardError),
+    #[error("Invalid uri: {0}")]
+    InvalidUri(String),
+    #[error("Invalid content: {0}")]
+    InvalidContent(String),
+    #[error("All gateways failed: [{0}]")]
+    AllGatewaysFailed(String),
+}
+
+impl FetchError {
+    /// Transient failures may succeed if the same request is sent again, everything else is permanent
+    pub fn is_transient(&self) -> bool {
+        match self {
+            FetchError::Status { status, .. } => matches!(
+                *status,
+                StatusCode::REQUEST_TIMEOUT
+                    | StatusCode::TOO_MANY_REQUESTS
+                    | StatusCode::INTERNAL_SERVER_ERROR
+                    | StatusCode::BAD_GATEWAY
+                    | StatusCode::SERVICE_UNAVAILABLE
+                    | StatusCode::GATEWAY_TIMEOUT
+            ),
+            FetchError::Request(e) => {
+                !is_guard_error(e) && (e.is_timeout() || e.is_connect() || e.is_body())
+            }
+            _ => false,
+        }
+    }
+
+    const fn retry_after(&self) -> Option<Duration> {
+        match self {
+            FetchError::Status { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+}
+
+/// How downloads are retried, the defaults suit nodes racing to claim
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// Total attempts including the first one
+    pub max_attempts: u32,
+    pub initial_backoff_ms: u64,
+    /// Also the longest `Retry-After` that is honoured, longer waits give up instead
+    pub max_backoff_ms: u64,
+    pub max_concurrent_per_host: usize,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            initial_backoff_ms: 250,
+            max_backoff_ms: 5_000,
+            max_concurrent_per_host: 8,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Exponential backoff with jitter, the wait is between half and all of the exponential delay
+    fn backoff(&self, attempt: u32) -> Duration {
+        let exp = self
+            .initial_backoff_ms
+            .saturating_mul(1u64 << attempt.saturating_sub(1).min(20))
+            .min(self.max_backoff_ms);
+        Duration::from_millis(rand::thread_rng().gen_range(exp / 2..=exp))
+    }
+}
+
+/// Downloads urls for inputs and images, applying the retry policy and a per host concurrency limit
+pub struct UrlFetcher {
+    client: reqwest::Client,
+    gateways: Gateways,
+    retry_policy: RetryPolicy,
+    guard: Option<Arc<UrlGuard>>,
+    host_permits: Mutex<HashMap<String, Arc<Semaphore>>>,
+}
+
+impl UrlFetcher {
+    pub fn new(client: reqwest::Client, gateways: Gateways, retry_policy: RetryPolicy) -> Self {
+        UrlFetcher {
+            client,
+            gateways,
+            retry_policy,
+            guard: None,
+            host_permits: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Checks every url before it is fetched, the client should come from [`UrlGuard::client_builder`] so
+    /// resolved addresses and redirects are checked too
+    pub fn with_guard(mut self, guard: Arc<UrlGuard>) -> Self {
+        self.guard = Some(guard);
+        self
+    }
+
+    pub fn check_url(&self, url: &Url) -> Result<(), FetchError> {
+        match &self.guard {
+            Some(guard) => Ok(guard.check_url(url)?),
+            None => Ok(()),
+        }
+    }
+
+    pub const fn gateways(&self) -> &Gateways {
+        &self.gateways
+    }
+
+    /// Downloads a url, content addressed uris are fetched through the gateways and everything else directly
+    pub async fn fetch(
+        &self,
+        url: &Url,
+        max_size: usize,
+        timeout: Duration,
+    ) -> Result<Bytes, FetchError> {
+        if is_content_addressed(url) {
+            return fetch_content_addressed(self, url, max_size, timeout).await;
+        }
+        self.get(url, None, max_size, timeout).await
+    }
+
+    /// Sends a GET, retrying transient failures with backoff
+    pub async fn get(
+        &self,
+        url: &Url,
+        accept: Option<&str>,
+        max_size: usize,
+        timeout: Duration,
+    ) -> Result<Bytes, FetchError> {
+        self.check_url(url)?;
+        let mut attempt = 1;
+        loop {
+            let result = {
+                let _permit = self.host_permit(url).await;
+                self.get_once(url, accept, max_size, timeout).await
+            };
+            match result {
+                Err(e) if e.is_transient() && attempt < self.retry_policy.max_attempts => {
+                    let delay = match e.retry_after() {
+                        Some(d) if d > Duration::from_millis(self.retry_policy.max_backoff_ms) => {
+                            return Err(e);
+                        }
+                        Some(d) => d,
+                        None => self.retry_policy.backoff(attempt),
+                    };
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn get_once(
+        &self,
+        url: &Url,
+        accept: Option<&str>,
+        max_size: usize,
+        timeout: Duration,
+    ) -> Result<Bytes, FetchError> {
+        let mut req = self.client.get(url.clone()).timeout(timeout);
+        if let Some(accept) = accept {
+            req = req.header(ACCEPT, accept);
+        }
+        let resp = req.send().await?;
+        let status = resp.status();
+        if !status.is_success() {
+            return Err(FetchError::Status {
+                status,
+                retry_after: retry_after(resp.headers()),
+            });
+        }
+        if resp.content_length().is_some_and(|l| l > max_size as u64) {
+            return Err(FetchError::TooLarge);
+        }
+        let mut body = BytesMut::new();
+        let mut stream = resp.bytes_stream();
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk?;
+            if body.len() + chunk.len() > max_size {
+                return Err(FetchError::TooLarge);
+            }
+            body.extend_from_slice(&chunk);
+        }
+        Ok(body.freeze())
+    }
+
+    async fn host_permit(&self, url: &Url) -> OwnedSemaphorePermit {
+        let host = url.host_str().unwrap_or_default().to_string();
+        let semaphore = self
+            .host_permits
+            .lock()
+            .expect("Host permit lock poisoned")
+            .entry(host)
+            .or_insert_with(|| {
+                Arc::new(Semaphore::new(
+                    self.retry_policy.max_concurrent_per_host.max(1),
+                ))
+            })
+            .clone();
+        semaphore
+            .acquire_owned()
+            .await
+            .expect("Host semaphores are never closed")
+    }
+}
+
+/// Only the delay-seconds form is understood, http dates fall back to the regular backoff
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    headers
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn fetcher(max_attempts: u32) -> UrlFetcher {
+        UrlFetcher::new(
+            reqwest::Client::new(),
+            Gateways::default(),
+            RetryPolicy {
+                max_attempts,
+                initial_backoff_ms: 1,
+                max_backoff_ms: 10,
+                max_concurrent_per_host: 1,
+            },
+        )
+    }
+
+    async fn fetch_status(
+        status: usize,
+        headers: &[(&str, &str)],
+        expected_calls: usize,
+    ) -> FetchError {
+        let mut server = mockito::Server::new_async().await;
+        let mut mock = server.mock("GET", "/input").with_status(status);
+        for (k, v) in headers {
+            mock = mock.with_header(*k, *v);
+        }
+        let mock = mock.expect(expected_calls).create_async().await;
+        let url = Url::parse(&format!("{}/input", server.url())).unwrap();
+
+        let err = fetcher(3)
+            .fetch(&url, 1024, Duration::from_secs(30))
+            .await
+            .unwrap_err();
+        mock.assert_async().await;
+        err
+    }
+
+    #[tokio::test]
+    async fn test_retries_transient_status() {
+        let err = fetch_status(503, &[], 3).await;
+        assert!(err.is_transient());
+    }
+
+    #[tokio::test]
+    async fn test_not_found_is_permanent() {
+        let err = fetch_status(404, &[], 1).await;
+        assert!(!err.is_transient());
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_on_long_retry_after() {
+        let err = fetch_status(429, &[("retry-after", "3600")], 1).await;
+        assert!(matches!(
+            err,
+            FetchError::Status {
+                retry_after: Some(_),
+                ..
+            }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_oversized_is_permanent() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/input")
+            .with_status(200)
+            .with_body(vec![1u8; 2048])
+            .expect(1)
+            .create_async()
+            .await;
+        let url = Url::parse(&format!("{}/input", server.url())).unwrap();
+
+        let err = fetcher(3)
+            .fetch(&url, 1024, Duration::from_secs(30))
+            .await
+            .unwrap_err();
+
+        assert!(matches!(err, FetchError::TooLarge));
+        mock.assert_async().await;
+    }
+
+    #[test]
+    fn test_backoff_is_capped() {
+        let policy = RetryPolicy {
+            max_attempts: 10,
+            initial_backoff_ms: 100,
+            max_backoff_ms: 1_000,
+            max_concurrent_per_host: 1,
+        };
+        assert!(policy.backoff(1) <= Duration::from_millis(100));
+        assert!(policy.backoff(1) >= Duration::from_millis(50));
+        assert!(policy.backoff(30) <= Duration::from_millis(1_000));
+    }
+}
@@ -5,13 +5,32 @@ use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use anyhow::Result;
 use arrayref::array_ref;
 use async_trait::async_trait;
-use bonsol_schema::{InputT, InputType, ProgramInputType};
+use bonsol_schema::accounts_input::AccountsInput;
+use bonsol_schema::{
+    decode_execution_ref, decode_hashed_url, decode_proof_url, InputT, InputType, ProgramInputType,
+};
 use reqwest::Url;
+use risc0_zkvm::sha::Digest;
 use serde::{Deserialize, Serialize};
+use solana_account_decoder::UiAccountEncoding;
+use solana_rpc_client_api::config::{RpcAccountInfoConfig, RpcTransactionConfig};
+use solana_sdk::account::Account;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::hash::{hash, Hash};
 use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
 use solana_sdk::signer::Signer;
+use solana_transaction_status::UiTransactionEncoding;
 use tokio::task::{JoinHandle, JoinSet};
 
+use crate::encryption::{
+    derive_encryption_secret, encryption_public_key, is_sealed_content_type, open_sealed_input,
+    SecretKey,
+};
+use crate::fetch::{RetryPolicy, UrlFetcher};
+use crate::proof_input::{verify_proof_input, verify_receipt};
+use crate::receipt_store::ReceiptStore;
+use crate::transaction_input::TransactionInput;
 use crate::util::get_body_max_size;
 
 #[derive(Debug, Clone, PartialEq)]
@@ -60,12 +79,27 @@ pub trait InputResolver: Send + Sync {
     ) -> Result<Vec<ProgramInput>, anyhow::Error>;
 
     /// Resolves private inputs by sigining the request and attempting to download the inputs
+    /// The requester of the execution is named in the request so servers can find the execution account
     async fn resolve_private_inputs(
         &self,
+        requester: &Pubkey,
         execution_id: &str,
         inputs: &mut Vec<ProgramInput>,
         signer: Arc<&(dyn Signer + Send + Sync)>,
     ) -> Result<(), anyhow::Error>;
+
+    /// Asks the private input servers whether every unresolved private input exists and fits within the size limit
+    /// This is signed the same way as a download so nodes can check availability before claiming
+    /// Resolvers that cannot probe accept optimistically
+    async fn probe_private_inputs(
+        &self,
+        _requester: &Pubkey,
+        _execution_id: &str,
+        _inputs: &[ProgramInput],
+        _signer: Arc<&(dyn Signer + Send + Sync)>,
+    ) -> Result<(), anyhow::Error> {
+        Ok(())
+    }
 }
 
 // naive resolver that downloads inputs just in time
@@ -74,6 +108,8 @@ pub struct DefaultInputResolver {
     solana_rpc_client: Arc<solana_rpc_client::nonblocking::rpc_client::RpcClient>,
     max_input_size_mb: u32,
     timeout: Duration,
+    fetcher: Arc<UrlFetcher>,
+    receipt_store: Option<Arc<dyn ReceiptStore>>,
 }
 
 impl DefaultInputResolver {
@@ -82,10 +118,12 @@ impl DefaultInputResolver {
         solana_rpc_client: Arc<solana_rpc_client::nonblocking::rpc_client::RpcClient>,
     ) -> Self {
         DefaultInputResolver {
-            http_client,
             solana_rpc_client,
             max_input_size_mb: 10,
             timeout: Duration::from_secs(30),
+            fetcher: default_fetcher(&http_client),
+            receipt_store: None,
+            http_client,
         }
     }
 
@@ -96,16 +134,29 @@ impl DefaultInputResolver {
         timeout: Option<Duration>,
     ) -> Self {
         DefaultInputResolver {
-            http_client,
             solana_rpc_client,
             max_input_size_mb: max_input_size_mb.unwrap_or(10),
             timeout: timeout.unwrap_or(Duration::from_secs(30)),
+            fetcher: default_fetcher(&http_client),
+            receipt_store: None,
+            http_client,
         }
     }
 
+    /// Sets the fetcher used for url inputs, it holds the gateways and the retry policy
+    pub fn with_fetcher(mut self, fetcher: Arc<UrlFetcher>) -> Self {
+        self.fetcher = fetcher;
+        self
+    }
+
+    /// Enables execution proof inputs, the receipts of prior executions are read from the store
+    pub fn with_receipt_store(mut self, receipt_store: Arc<dyn ReceiptStore>) -> Self {
+        self.receipt_store = Some(receipt_store);
+        self
+    }
+
     fn par_resolve_input(
         &self,
-        client: Arc<reqwest::Client>,
         index: u8,
         input: InputT,
         task_set: &mut JoinSet<Result<ResolvedInput>>,
@@ -116,7 +167,7 @@ impl DefaultInputResolver {
                 let url = from_utf8(&url)?;
                 let url = Url::parse(url)?;
                 task_set.spawn(download_public_input(
-                    client,
+                    self.fetcher.clone(),
                     index,
                     url.clone(),
                     self.max_input_size_mb as usize,
@@ -129,10 +180,30 @@ impl DefaultInputResolver {
                     input_type: ProgramInputType::Public,
                 }))
             }
+            InputType::PublicUrlHashed => {
+                let data = input.data.ok_or(anyhow::anyhow!("Invalid data"))?;
+                let (body_hash, url) =
+                    decode_hashed_url(&data).ok_or(anyhow::anyhow!("Invalid data"))?;
+                let url = Url::parse(from_utf8(url)?)?;
+                task_set.spawn(download_hashed_public_input(
+                    self.fetcher.clone(),
+                    index,
+                    url.clone(),
+                    Hash::new_from_array(*body_hash),
+                    self.max_input_size_mb as usize,
+                    self.timeout,
+                ));
+                Ok(ProgramInput::Unresolved(UnresolvedInput {
+                    index,
+                    url,
+                    input_type: ProgramInputType::Public,
+                }))
+            }
             InputType::Private => {
                 let url = input.data.ok_or(anyhow::anyhow!("Invalid data"))?;
                 let url = from_utf8(&url)?;
                 let url = Url::parse(url)?;
+                self.fetcher.check_url(&url)?;
                 Ok(ProgramInput::Unresolved(UnresolvedInput {
                     index,
                     url,
@@ -149,15 +220,16 @@ impl DefaultInputResolver {
                 }))
             }
             InputType::PublicProof => {
-                let url = input.data.ok_or(anyhow::anyhow!("Invalid data"))?;
-                let url = from_utf8(&url)?;
-                let url = Url::parse(url)?;
-                task_set.spawn(download_public_input(
-                    client,
+                let data = input.data.ok_or(anyhow::anyhow!("Invalid data"))?;
+                let (image_id, url) =
+                    decode_proof_url(&data).ok_or(anyhow::anyhow!("Invalid data"))?;
+                let url = Url::parse(from_utf8(url)?)?;
+                task_set.spawn(download_proof_input(
+                    self.fetcher.clone(),
                     index,
                     url.clone(),
+                    image_id.map(|id| Digest::from(*id)),
                     self.max_input_size_mb as usize,
-                    ProgramInputType::PublicProof,
                     self.timeout,
                 ));
                 Ok(ProgramInput::Unresolved(UnresolvedInput {
@@ -185,6 +257,69 @@ impl DefaultInputResolver {
                     input_type: ProgramInputType::Public,
                 }))
             }
+            InputType::PublicAccounts => {
+                let data = input.data.ok_or(anyhow::anyhow!("Invalid data"))?;
+                let accounts = AccountsInput::from_bytes(&data)?;
+                let url = format!(
+                    "solana://{}",
+                    Pubkey::new_from_array(accounts.accounts[0].pubkey)
+                )
+                .parse()?;
+                task_set.spawn(download_public_accounts(
+                    self.solana_rpc_client.clone(),
+                    index,
+                    accounts,
+                    self.max_input_size_mb as usize,
+                ));
+                Ok(ProgramInput::Unresolved(UnresolvedInput {
+                    index,
+                    url,
+                    input_type: ProgramInputType::Public,
+                }))
+            }
+            InputType::PublicTransaction => {
+                let data = input.data.ok_or(anyhow::anyhow!("Invalid data"))?;
+                let signature = Signature::try_from(data.as_slice())
+                    .map_err(|_| anyhow::anyhow!("Invalid signature"))?;
+                task_set.spawn(download_public_transaction(
+                    self.solana_rpc_client.clone(),
+                    index,
+                    signature,
+                    self.max_input_size_mb as usize,
+                ));
+                Ok(ProgramInput::Unresolved(UnresolvedInput {
+                    index,
+                    url: format!("solana://{}", signature).parse()?,
+                    input_type: ProgramInputType::Public,
+                }))
+            }
+            InputType::PublicExecutionProof => {
+                let data = input.data.ok_or(anyhow::anyhow!("Invalid data"))?;
+                let (requester, image_id, execution_id) =
+                    decode_execution_ref(&data).ok_or(anyhow::anyhow!("Invalid data"))?;
+                let requester = Pubkey::new_from_array(*requester);
+                let receipt_store = self
+                    .receipt_store
+                    .clone()
+                    .ok_or(anyhow::anyhow!("No receipt store configured"))?;
+                let mut url = Url::parse(&format!("bonsol://{}", requester))?;
+                url.path_segments_mut()
+                    .map_err(|_| anyhow::anyhow!("Invalid execution id"))?
+                    .push(execution_id);
+                task_set.spawn(download_execution_receipt(
+                    receipt_store,
+                    index,
+                    requester,
+                    execution_id.to_string(),
+                    image_id.map(|id| Digest::from(*id)),
+                    self.max_input_size_mb as usize,
+                ));
+                Ok(ProgramInput::Unresolved(UnresolvedInput {
+                    index,
+                    url,
+                    input_type: ProgramInputType::PublicProof,
+                }))
+            }
             _ => {
                 // not implemented yet / or unknown
                 Err(anyhow::anyhow!("Invalid input type"))
@@ -198,10 +333,14 @@ impl InputResolver for DefaultInputResolver {
     fn supports(&self, input_type: InputType) -> bool {
         match input_type {
             InputType::PublicUrl => true,
+            InputType::PublicUrlHashed => true,
             InputType::PublicData => true,
             InputType::PublicAccountData => true,
+            InputType::PublicAccounts => true,
+            InputType::PublicTransaction => true,
             InputType::Private => true,
             InputType::PublicProof => true,
+            InputType::PublicExecutionProof => self.receipt_store.is_some(),
             _ => false,
         }
     }
@@ -213,9 +352,7 @@ impl InputResolver for DefaultInputResolver {
         let mut url_set = JoinSet::new();
         let mut res = vec![ProgramInput::Empty; inputs.len()];
         for (index, input) in inputs.into_iter().enumerate() {
-            let client = self.http_client.clone();
-            res[index] =
-                self.par_resolve_input(client, index as u8, input, &mut url_set)?;
+            res[index] = self.par_resolve_input(index as u8, input, &mut url_set)?;
         }
         while let Some(url) = url_set.join_next().await {
             match url {
@@ -233,29 +370,26 @@ impl InputResolver for DefaultInputResolver {
 
     async fn resolve_private_inputs(
         &self,
+        requester: &Pubkey,
         execution_id: &str,
         inputs: &mut Vec<ProgramInput>,
         signer: Arc<&(dyn Signer + Send + Sync)>,
     ) -> Result<(), anyhow::Error> {
         let mut url_set = JoinSet::new();
+        let encryption_secret = derive_encryption_secret(*signer);
         for (index, input) in inputs.iter().enumerate() {
             let client = self.http_client.clone();
             if let ProgramInput::Unresolved(ui) = input {
-                let pir = PrivateInputRequest {
-                    identity: signer.pubkey(),
-                    claim_id: execution_id.to_string(),
-                    input_index: ui.index,
-                    now_utc: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
-                };
-                let pir_str = serde_json::to_string(&pir)?;
-                let claim_authorization = signer.sign_message(pir_str.as_bytes());
+                let (pir_str, claim_authorization) =
+                    sign_private_input_request(*signer, requester, execution_id, ui.index, false)?;
                 url_set.spawn(download_private_input(
                     client,
                     index as u8,
                     ui.url.clone(),
                     self.max_input_size_mb as usize,
                     pir_str,
-                    claim_authorization.to_string(), // base58 encoded string
+                    claim_authorization,
+                    encryption_secret.clone(),
                     self.timeout,
                 ));
             }
@@ -273,6 +407,42 @@ impl InputResolver for DefaultInputResolver {
         }
         Ok(())
     }
+
+    async fn probe_private_inputs(
+        &self,
+        requester: &Pubkey,
+        execution_id: &str,
+        inputs: &[ProgramInput],
+        signer: Arc<&(dyn Signer + Send + Sync)>,
+    ) -> Result<(), anyhow::Error> {
+        let mut probe_set = JoinSet::new();
+        for input in inputs.iter() {
+            if let ProgramInput::Unresolved(ui) = input {
+                if ui.input_type != ProgramInputType::Private {
+                    continue;
+                }
+                let (pir_str, claim_authorization) =
+                    sign_private_input_request(*signer, requester, execution_id, ui.index, true)?;
+                probe_set.spawn(probe_private_input(
+                    self.http_client.clone(),
+                    ui.url.clone(),
+                    self.max_input_size_mb as usize,
+                    pir_str,
+                    claim_authorization,
+                    self.timeout,
+                ));
+            }
+        }
+        while let Some(probe) = probe_set.join_next().await {
+            match probe {
+                Ok(Ok(_)) => {}
+                e => {
+                    return Err(anyhow::anyhow!("Private input unavailable: {:?}", e));
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 pub fn resolve_public_data(index: usize, data: &[u8]) -> Result<ProgramInput> {
@@ -294,7 +464,7 @@ pub fn resolve_remote_public_data(
     let url = from_utf8(data)?;
     let url = Url::parse(url)?;
     Ok(tokio::task::spawn(download_public_input(
-        client,
+        default_fetcher(&client),
         index as u8,
         url,
         max_input_size_mb as usize,
@@ -306,26 +476,93 @@ pub fn resolve_remote_public_data(
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PrivateInputRequest {
     identity: Pubkey,
+    /// Requester of the execution, the execution account is derived from it and the claim id
+    requester: Pubkey,
     claim_id: String,
     input_index: u8,
     now_utc: u64,
+    /// When set the server only reports whether the input exists and its size, no data is returned
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    probe: bool,
+    /// X25519 key of the claimer, servers that encrypt inputs seal the response to this key
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    encryption_key: Option<[u8; 32]>,
+}
+
+impl PrivateInputRequest {
+    pub const fn identity(&self) -> &Pubkey {
+        &self.identity
+    }
+
+    pub const fn requester(&self) -> &Pubkey {
+        &self.requester
+    }
+
+    pub fn claim_id(&self) -> &str {
+        &self.claim_id
+    }
+
+    pub const fn input_index(&self) -> u8 {
+        self.input_index
+    }
+
+    pub const fn now_utc(&self) -> u64 {
+        self.now_utc
+    }
+
+    pub const fn is_probe(&self) -> bool {
+        self.probe
+    }
+
+    pub const fn encryption_key(&self) -> Option<&[u8; 32]> {
+        self.encryption_key.as_ref()
+    }
+}
+
+/// Header a private input server uses to report the size of an input in response to a probe
+pub const PRIVATE_INPUT_SIZE_HEADER: &str = "x-bonsol-input-size";
+
+/// Builds and signs a private input request, returning the json payload and the base58 encoded signature
+pub fn sign_private_input_request(
+    signer: &(dyn Signer + Send + Sync),
+    requester: &Pubkey,
+    execution_id: &str,
+    input_index: u8,
+    probe: bool,
+) -> Result<(String, String)> {
+    let pir = PrivateInputRequest {
+        identity: signer.pubkey(),
+        requester: *requester,
+        claim_id: execution_id.to_string(),
+        input_index,
+        now_utc: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+        probe,
+        encryption_key: Some(encryption_public_key(signer)),
+    };
+    let pir_str = serde_json::to_string(&pir)?;
+    let claim_authorization = signer.sign_message(pir_str.as_bytes());
+    Ok((pir_str, claim_authorization.to_string()))
+}
+
+fn default_fetcher(client: &reqwest::Client) -> Arc<UrlFetcher> {
+    Arc::new(UrlFetcher::new(
+        client.clone(),
+        Default::default(),
+        RetryPolicy::default(),
+    ))
 }
 
 async fn download_public_input(
-    client: Arc<reqwest::Client>,
+    fetcher: Arc<UrlFetcher>,
     index: u8,
     url: Url,
     max_size_mb: usize,
     input_type: ProgramInputType,
     timeout: Duration,
 ) -> Result<ResolvedInput> {
-    let resp = client
-        .get(url)
-        .timeout(timeout)
-        .send()
-        .await?
-        .error_for_status()?;
-    let byte = get_body_max_size(resp.bytes_stream(), max_size_mb * 1024 * 1024).await?;
+    let byte = fetcher
+        .fetch(&url, max_size_mb * 1024 * 1024, timeout)
+        .await?;
     Ok(ResolvedInput {
         index,
         data: byte.to_vec(),
@@ -333,6 +570,84 @@ async fn download_public_input(
     })
 }
 
+/// Downloads a public url input and rejects it unless the body matches the sha256 committed to in the request
+async fn download_hashed_public_input(
+    fetcher: Arc<UrlFetcher>,
+    index: u8,
+    url: Url,
+    body_hash: Hash,
+    max_size_mb: usize,
+    timeout: Duration,
+) -> Result<ResolvedInput> {
+    let ri = download_public_input(
+        fetcher,
+        index,
+        url,
+        max_size_mb,
+        ProgramInputType::Public,
+        timeout,
+    )
+    .await?;
+    let actual = hash(&ri.data);
+    if actual != body_hash {
+        return Err(anyhow::anyhow!(
+            "Input hash mismatch, expected {} got {}",
+            body_hash,
+            actual
+        ));
+    }
+    Ok(ri)
+}
+
+/// Downloads a proof input and verifies the receipt, and its image id if the input names one, before claiming
+async fn download_proof_input(
+    fetcher: Arc<UrlFetcher>,
+    index: u8,
+    url: Url,
+    image_id: Option<Digest>,
+    max_size_mb: usize,
+    timeout: Duration,
+) -> Result<ResolvedInput> {
+    let ri = download_public_input(
+        fetcher,
+        index,
+        url,
+        max_size_mb,
+        ProgramInputType::PublicProof,
+        timeout,
+    )
+    .await?;
+    verify_proof_input(&ri.data, image_id.as_ref())?;
+    Ok(ri)
+}
+
+/// Reads the receipt of a prior execution from the store and checks it before it becomes an assumption
+/// Anyone who can write to a shared store can put a receipt under any execution, only a pinned image id binds
+/// the receipt to the program of the prior execution
+async fn download_execution_receipt(
+    receipt_store: Arc<dyn ReceiptStore>,
+    index: u8,
+    requester: Pubkey,
+    execution_id: String,
+    image_id: Option<Digest>,
+    max_size_mb: usize,
+) -> Result<ResolvedInput> {
+    let receipt = receipt_store
+        .get(&requester, &execution_id, max_size_mb * 1024 * 1024)
+        .await?
+        .ok_or(anyhow::anyhow!(
+            "No receipt for execution {} of {}",
+            execution_id,
+            requester
+        ))?;
+    verify_receipt(&receipt, image_id.as_ref())?;
+    Ok(ResolvedInput {
+        index,
+        data: bincode::serialize(&receipt)?,
+        input_type: ProgramInputType::PublicProof,
+    })
+}
+
 async fn download_public_account(
     solana_client: Arc<solana_rpc_client::nonblocking::rpc_client::RpcClient>,
     index: u8,
@@ -350,6 +665,155 @@ async fn download_public_account(
     })
 }
 
+/// Reads every requested account in a single `getMultipleAccounts` call so they all come from the same slot
+async fn download_public_accounts(
+    solana_client: Arc<solana_rpc_client::nonblocking::rpc_client::RpcClient>,
+    index: u8,
+    input: AccountsInput,
+    max_size_mb: usize,
+) -> Result<ResolvedInput> {
+    let pubkeys: Vec<Pubkey> = input
+        .accounts
+        .iter()
+        .map(|a| Pubkey::new_from_array(a.pubkey))
+        .collect();
+    let config = RpcAccountInfoConfig {
+        encoding: Some(UiAccountEncoding::Base64),
+        commitment: Some(CommitmentConfig::confirmed()),
+        min_context_slot: (input.min_context_slot > 0).then_some(input.min_context_slot),
+        data_slice: None,
+    };
+    let resp = solana_client
+        .get_multiple_accounts_with_config(&pubkeys, config)
+        .await?;
+    let data = encode_accounts_input(
+        &input,
+        resp.context.slot,
+        &resp.value,
+        max_size_mb * 1024 * 1024,
+    )?;
+    Ok(ResolvedInput {
+        index,
+        data,
+        input_type: ProgramInputType::Public,
+    })
+}
+
+/// Fetches a finalized transaction and encodes it as documented in `transaction_input`
+async fn download_public_transaction(
+    solana_client: Arc<solana_rpc_client::nonblocking::rpc_client::RpcClient>,
+    index: u8,
+    signature: Signature,
+    max_size_mb: usize,
+) -> Result<ResolvedInput> {
+    let config = RpcTransactionConfig {
+        encoding: Some(UiTransactionEncoding::Base64),
+        commitment: Some(CommitmentConfig::finalized()),
+        max_supported_transaction_version: Some(0),
+    };
+    let tx = solana_client
+        .get_transaction_with_config(&signature, config)
+        .await?;
+    let transaction = TransactionInput::try_from(tx)?;
+    transaction.check_signature(&signature)?;
+    let data = transaction.to_bytes();
+    if data.len() > max_size_mb * 1024 * 1024 {
+        return Err(anyhow::anyhow!("Max size exceeded"));
+    }
+    Ok(ResolvedInput {
+        index,
+        data,
+        input_type: ProgramInputType::Public,
+    })
+}
+
+/// Writes the guest layout documented in `bonsol_schema::accounts_input`
+fn encode_accounts_input(
+    input: &AccountsInput,
+    slot: u64,
+    accounts: &[Option<Account>],
+    max_size: usize,
+) -> Result<Vec<u8>> {
+    if accounts.len() != input.accounts.len() {
+        return Err(anyhow::anyhow!("Account count mismatch"));
+    }
+    let mut data = slot.to_le_bytes().to_vec();
+    for (slice, account) in input.accounts.iter().zip(accounts) {
+        let pubkey = Pubkey::new_from_array(slice.pubkey);
+        let account = account
+            .as_ref()
+            .ok_or(anyhow::anyhow!("Account {} not found", pubkey))?;
+        if slice.owner.is_some_and(|o| o != account.owner.to_bytes()) {
+            return Err(anyhow::anyhow!(
+                "Account {} is owned by {}",
+                pubkey,
+                account.owner
+            ));
+        }
+        let start = slice.offset as usize;
+        let end = match slice.length {
+            0 => account.data.len(),
+            l => start.saturating_add(l as usize),
+        };
+        let bytes = account.data.get(start..end).ok_or(anyhow::anyhow!(
+            "Slice out of bounds for account {}",
+            pubkey
+        ))?;
+        if data.len() + 36 + bytes.len() > max_size {
+            return Err(anyhow::anyhow!("Max size exceeded"));
+        }
+        data.extend_from_slice(account.owner.as_ref());
+        data.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+        data.extend_from_slice(bytes);
+    }
+    Ok(data)
+}
+
+/// Sends a signed probe for a private input and returns the size the server reports
+/// Servers that do not understand probes answer with the input itself, in that case the content length is used and the body is never read
+/// Servers without a probe route answer 405 or 501, that is None and the input is accepted optimistically
+/// Any other refusal, such as a missing input or an expired execution, means the input is unavailable
+async fn probe_private_input(
+    client: Arc<reqwest::Client>,
+    url: Url,
+    max_size_mb: usize,
+    body: String,
+    claim_authorization: String,
+    timeout: Duration,
+) -> Result<Option<u64>> {
+    let resp = client
+        .post(url)
+        .body(body)
+        .timeout(timeout)
+        .header("Authorization", format!("Bearer {}", claim_authorization))
+        .header("Content-Type", "application/json")
+        .send()
+        .await?;
+    if probe_unsupported(resp.status()) {
+        return Ok(None);
+    }
+    let resp = resp.error_for_status()?;
+    let size = resp
+        .headers()
+        .get(PRIVATE_INPUT_SIZE_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok())
+        .or(resp.content_length())
+        .ok_or(anyhow::anyhow!("Input size unknown"))?;
+    if size > (max_size_mb * 1024 * 1024) as u64 {
+        return Err(anyhow::anyhow!("Max size exceeded"));
+    }
+    Ok(Some(size))
+}
+
+/// Statuses of servers that have no probe route
+fn probe_unsupported(status: reqwest::StatusCode) -> bool {
+    matches!(
+        status,
+        reqwest::StatusCode::METHOD_NOT_ALLOWED | reqwest::StatusCode::NOT_IMPLEMENTED
+    )
+}
+
 async fn download_private_input(
     client: Arc<reqwest::Client>,
     index: u8,
@@ -357,6 +821,7 @@ async fn download_private_input(
     max_size_mb: usize,
     body: String,
     claim_authorization: String,
+    encryption_secret: SecretKey,
     timeout: Duration,
 ) -> Result<ResolvedInput> {
     let resp = client
@@ -369,10 +834,29 @@ async fn download_private_input(
         .send()
         .await?
         .error_for_status()?;
+    let content_type = match resp.headers().get(reqwest::header::CONTENT_TYPE) {
+        Some(ct) => ct
+            .to_str()
+            .map_err(|_| anyhow::anyhow!("Invalid content type"))?,
+        None => "",
+    };
+    let sealed = is_sealed_content_type(content_type);
+    // ciphertext must never reach the guest as if it were the input
+    if !sealed && content_type.to_ascii_lowercase().contains("bonsol-sealed") {
+        return Err(anyhow::anyhow!(
+            "Unsupported sealed content type {}",
+            content_type
+        ));
+    }
     let byte = get_body_max_size(resp.bytes_stream(), max_size_mb * 1024 * 1024).await?;
+    let data = if sealed {
+        open_sealed_input(&encryption_secret, &byte)?
+    } else {
+        byte.to_vec()
+    };
     Ok(ResolvedInput {
         index,
-        data: byte.to_vec(),
+        data,
         input_type: ProgramInputType::Private,
     })
 }
@@ -380,6 +864,8 @@ async fn download_private_input(
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::proof_input::ProofInputError;
+    use bonsol_schema::accounts_input::AccountSlice;
     use mockito::Mock;
     use reqwest::{Client, Url};
 
@@ -412,7 +898,7 @@ mod test {
         let client = Arc::new(Client::new());
 
         let valid_result = download_public_input(
-            client.clone(),
+            default_fetcher(&client),
             1u8,
             url,
             max_size_mb,
@@ -444,7 +930,7 @@ mod test {
         let client = Arc::new(Client::new());
 
         let valid_result = download_public_input(
-            client.clone(),
+            default_fetcher(&client),
             1u8,
             url,
             max_size_mb,
@@ -458,4 +944,314 @@ mod test {
 
         mock.assert();
     }
+
+    #[tokio::test]
+    async fn test_download_hashed_public_input() {
+        let input_data = b"pinned input".to_vec();
+        let (mock, url, _server) = get_server("/download", &input_data).await;
+        let client = Arc::new(Client::new());
+
+        let result = download_hashed_public_input(
+            default_fetcher(&client),
+            1u8,
+            url,
+            hash(&input_data),
+            1,
+            Duration::from_secs(30),
+        )
+        .await;
+
+        assert_eq!(result.unwrap().data, input_data);
+        mock.assert();
+    }
+
+    #[tokio::test]
+    async fn test_download_hashed_public_input_mismatch() {
+        let (mock, url, _server) = get_server("/download", b"changed input").await;
+        let client = Arc::new(Client::new());
+
+        let result = download_hashed_public_input(
+            default_fetcher(&client),
+            1u8,
+            url,
+            hash(b"pinned input"),
+            1,
+            Duration::from_secs(30),
+        )
+        .await;
+
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .starts_with("Input hash mismatch"));
+        mock.assert();
+    }
+
+    fn account(owner: Pubkey, data: &[u8]) -> Option<Account> {
+        Some(Account {
+            lamports: 1,
+            data: data.to_vec(),
+            owner,
+            executable: false,
+            rent_epoch: 0,
+        })
+    }
+
+    #[test]
+    fn test_encode_accounts_input() {
+        let owner = Pubkey::new_unique();
+        let input = AccountsInput {
+            min_context_slot: 0,
+            accounts: vec![
+                AccountSlice {
+                    pubkey: Pubkey::new_unique().to_bytes(),
+                    owner: Some(owner.to_bytes()),
+                    offset: 2,
+                    length: 3,
+                },
+                AccountSlice::whole(Pubkey::new_unique().to_bytes()),
+            ],
+        };
+        let data = encode_accounts_input(
+            &input,
+            42,
+            &[account(owner, b"abcdefg"), account(owner, b"xy")],
+            1024,
+        )
+        .unwrap();
+
+        let mut expected = 42u64.to_le_bytes().to_vec();
+        expected.extend_from_slice(owner.as_ref());
+        expected.extend_from_slice(&3u32.to_le_bytes());
+        expected.extend_from_slice(b"cde");
+        expected.extend_from_slice(owner.as_ref());
+        expected.extend_from_slice(&2u32.to_le_bytes());
+        expected.extend_from_slice(b"xy");
+        assert_eq!(data, expected);
+    }
+
+    #[test]
+    fn test_encode_accounts_input_rejects() {
+        let owner = Pubkey::new_unique();
+        let mut input = AccountsInput {
+            min_context_slot: 0,
+            accounts: vec![AccountSlice {
+                pubkey: Pubkey::new_unique().to_bytes(),
+                owner: Some(Pubkey::new_unique().to_bytes()),
+                offset: 0,
+                length: 0,
+            }],
+        };
+        // wrong owner
+        assert!(encode_accounts_input(&input, 1, &[account(owner, b"abc")], 1024).is_err());
+        // missing account
+        assert!(encode_accounts_input(&input, 1, &[None], 1024).is_err());
+        // slice past the end of the data
+        input.accounts[0].owner = None;
+        input.accounts[0].offset = 2;
+        input.accounts[0].length = 4;
+        assert!(encode_accounts_input(&input, 1, &[account(owner, b"abc")], 1024).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_probe_private_input_reports_size() {
+        let mut server = mockito::Server::new_async().await;
+        let url = Url::parse(&format!("{}/private", server.url())).unwrap();
+        let mock = server
+            .mock("POST", "/private")
+            .match_header("authorization", "Bearer sig")
+            .with_status(200)
+            .with_header(PRIVATE_INPUT_SIZE_HEADER, "1024")
+            .create_async()
+            .await;
+        let client = Arc::new(Client::new());
+
+        let size = probe_private_input(
+            client,
+            url,
+            1,
+            "{}".to_string(),
+            "sig".to_string(),
+            Duration::from_secs(30),
+        )
+        .await;
+
+        assert_eq!(size.unwrap(), Some(1024));
+        mock.assert();
+    }
+
+    #[tokio::test]
+    async fn test_probe_private_input_unavailable() {
+        let mut server = mockito::Server::new_async().await;
+        let url = Url::parse(&format!("{}/private", server.url())).unwrap();
+        let mock = server
+            .mock("POST", "/private")
+            .with_status(500)
+            .create_async()
+            .await;
+        let client = Arc::new(Client::new());
+
+        let size = probe_private_input(
+            client,
+            url,
+            1,
+            "{}".to_string(),
+            "sig".to_string(),
+            Duration::from_secs(30),
+        )
+        .await;
+
+        assert!(size.is_err());
+        mock.assert();
+    }
+
+    async fn probe_with_status(status: usize) -> Result<()> {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/private")
+            .with_status(status)
+            .create_async()
+            .await;
+        let resolver = DefaultInputResolver::new(
+            Arc::new(Client::new()),
+            Arc::new(solana_rpc_client::nonblocking::rpc_client::RpcClient::new(
+                server.url(),
+            )),
+        );
+        let inputs = vec![ProgramInput::Unresolved(UnresolvedInput {
+            index: 1,
+            url: Url::parse(&format!("{}/private", server.url())).unwrap(),
+            input_type: ProgramInputType::Private,
+        })];
+        let signer = solana_sdk::signature::Keypair::new();
+
+        let res = resolver
+            .probe_private_inputs(&Pubkey::new_unique(), "exec", &inputs, Arc::new(&signer))
+            .await;
+        mock.assert();
+        res
+    }
+
+    #[tokio::test]
+    async fn test_probe_private_inputs_of_server_without_probes() {
+        for status in [405, 501] {
+            probe_with_status(status).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_probe_private_inputs_refused() {
+        for status in [401, 403, 404] {
+            assert!(probe_with_status(status).await.is_err());
+        }
+    }
+
+    async fn download_sealed(content_type: &str) -> Result<ResolvedInput> {
+        let kp = solana_sdk::signature::Keypair::new();
+        let sealed = crate::encryption::seal_input(&encryption_public_key(&kp), b"private")?;
+        let mut server = mockito::Server::new_async().await;
+        let url = Url::parse(&format!("{}/private", server.url())).unwrap();
+        let _mock = server
+            .mock("POST", "/private")
+            .with_status(200)
+            .with_header("content-type", content_type)
+            .with_body(sealed)
+            .create_async()
+            .await;
+
+        download_private_input(
+            Arc::new(Client::new()),
+            1,
+            url,
+            1,
+            "{}".to_string(),
+            "sig".to_string(),
+            derive_encryption_secret(&kp),
+            Duration::from_secs(30),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_download_private_input_sealed_with_parameters() {
+        let input = download_sealed("Application/X-Bonsol-Sealed; charset=binary")
+            .await
+            .unwrap();
+        assert_eq!(input.data, b"private");
+    }
+
+    #[tokio::test]
+    async fn test_download_private_input_refuses_unknown_sealing() {
+        let res = download_sealed("application/x-bonsol-sealed-v2").await;
+        assert!(res.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_probe_private_input_oversized() {
+        let mut server = mockito::Server::new_async().await;
+        let url = Url::parse(&format!("{}/private", server.url())).unwrap();
+        let mock = server
+            .mock("POST", "/private")
+            .with_status(200)
+            .with_header(PRIVATE_INPUT_SIZE_HEADER, &(2 * 1024 * 1024).to_string())
+            .create_async()
+            .await;
+        let client = Arc::new(Client::new());
+
+        let size = probe_private_input(
+            client,
+            url,
+            1,
+            "{}".to_string(),
+            "sig".to_string(),
+            Duration::from_secs(30),
+        )
+        .await;
+
+        assert_eq!(size.unwrap_err().to_string(), "Max size exceeded");
+        mock.assert();
+    }
+
+    #[tokio::test]
+    async fn test_download_proof_input_rejects_garbage() {
+        let (mock, url, _server) = get_server("/proof", &[1u8; 256]).await;
+        let client = Arc::new(Client::new());
+
+        let res = download_proof_input(
+            default_fetcher(&client),
+            0,
+            url,
+            None,
+            1,
+            Duration::from_secs(30),
+        )
+        .await;
+
+        let err = res.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ProofInputError>(),
+            Some(ProofInputError::Malformed(_))
+        ));
+        mock.assert();
+    }
+
+    #[tokio::test]
+    async fn test_execution_proof_needs_published_receipt() {
+        let dir = tempfile::tempdir().unwrap();
+        let receipt_store = crate::receipt_store::ReceiptStoreConfig::Local {
+            path: dir.path().to_string_lossy().to_string(),
+        }
+        .build()
+        .unwrap();
+        let requester = Pubkey::new_unique();
+
+        let res =
+            download_execution_receipt(receipt_store, 0, requester, "exec".to_string(), None, 1)
+                .await;
+
+        assert!(res
+            .unwrap_err()
+            .to_string()
+            .starts_with("No receipt for execution exec"));
+    }
 }
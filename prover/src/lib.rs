@@ -1,8 +1,16 @@
+pub mod content;
+pub mod fetch;
 pub mod image;
 pub mod input_resolver;
+pub mod proof_input;
 pub mod prover;
+pub mod receipt_store;
+pub mod resolver_registry;
+pub mod transaction_input;
+pub mod url_guard;
 pub mod util;
 
+pub use bonsol_encryption as encryption;
 pub use bonsol_schema::{
     ClaimV1T, DeployV1T, ExecutionRequestV1T, ExitCode, InputT, InputType, ProgramInputType,
     StatusTypes,
@@ -0,0 +1,60 @@
+use risc0_zkvm::sha::{Digest, Digestible};
+use risc0_zkvm::{Receipt, VerificationError, VerifierContext};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ProofInputError {
+    #[error("Malformed receipt: {0}")]
+    Malformed(#[from] bincode::Error),
+    #[error("Invalid receipt: {0}")]
+    Invalid(#[from] VerificationError),
+    #[error("Receipt claim is pruned, the image id can not be checked")]
+    PrunedClaim,
+    #[error("Receipt image id {actual} does not match the expected image id {expected}")]
+    ImageIdMismatch { expected: Digest, actual: Digest },
+}
+
+/// Checks the receipt's seal against its claim and, if given, that the claim is for the expected image
+/// Done when inputs are resolved so a bad proof is rejected before the node claims, not deep inside proving
+pub fn verify_receipt(
+    receipt: &Receipt,
+    expected_image_id: Option<&Digest>,
+) -> Result<(), ProofInputError> {
+    receipt.verify_integrity_with_context(&VerifierContext::default())?;
+    if let Some(expected) = expected_image_id {
+        let claim = receipt.claim()?;
+        let actual = claim
+            .as_value()
+            .map_err(|_| ProofInputError::PrunedClaim)?
+            .pre
+            .digest();
+        if actual != *expected {
+            return Err(ProofInputError::ImageIdMismatch {
+                expected: *expected,
+                actual,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Deserializes a bincode receipt, as `PublicProof` inputs are passed to the prover, and verifies it
+pub fn verify_proof_input(
+    data: &[u8],
+    expected_image_id: Option<&Digest>,
+) -> Result<Receipt, ProofInputError> {
+    let receipt: Receipt = bincode::deserialize(data)?;
+    verify_receipt(&receipt, expected_image_id)?;
+    Ok(receipt)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_garbage_proof_is_malformed() {
+        let err = verify_proof_input(&[1u8; 64], None).unwrap_err();
+        assert!(matches!(err, ProofInputError::Malformed(_)));
+    }
+}
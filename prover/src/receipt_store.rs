@@ -0,0 +1,135 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use object_store::aws::AmazonS3Builder;
+use object_store::local::LocalFileSystem;
+use object_store::path::Path;
+use object_store::ObjectStore;
+use risc0_zkvm::Receipt;
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+
+/// Receipts of finished executions, nodes publish what they prove so later executions can use it as an input
+#[async_trait]
+pub trait ReceiptStore: Send + Sync {
+    async fn put(&self, requester: &Pubkey, execution_id: &str, receipt: &Receipt) -> Result<()>;
+
+    /// Returns None if no receipt was published for the execution
+    async fn get(
+        &self,
+        requester: &Pubkey,
+        execution_id: &str,
+        max_size: usize,
+    ) -> Result<Option<Receipt>>;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ReceiptStoreConfig {
+    Local {
+        path: String,
+    },
+    /// Credentials are read from the standard `AWS_*` environment variables
+    S3 {
+        bucket: String,
+        region: String,
+        endpoint: Option<String>,
+    },
+}
+
+impl ReceiptStoreConfig {
+    pub fn build(&self) -> Result<Arc<dyn ReceiptStore>> {
+        let store: Arc<dyn ObjectStore> = match self {
+            ReceiptStoreConfig::Local { path } => {
+                std::fs::create_dir_all(path)?;
+                Arc::new(LocalFileSystem::new_with_prefix(path)?)
+            }
+            ReceiptStoreConfig::S3 {
+                bucket,
+                region,
+                endpoint,
+            } => {
+                let mut builder = AmazonS3Builder::from_env()
+                    .with_bucket_name(bucket)
+                    .with_region(region);
+                if let Some(endpoint) = endpoint {
+                    builder = builder.with_endpoint(endpoint);
+                }
+                Arc::new(builder.build()?)
+            }
+        };
+        Ok(Arc::new(ObjectReceiptStore::new(store)))
+    }
+}
+
+/// Stores bincode receipts at `<requester>/<execution_id>` in any object store
+pub struct ObjectReceiptStore {
+    store: Arc<dyn ObjectStore>,
+}
+
+impl ObjectReceiptStore {
+    pub fn new(store: Arc<dyn ObjectStore>) -> Self {
+        ObjectReceiptStore { store }
+    }
+}
+
+/// Each id is a single path part, so an execution id cannot reach outside its requester
+fn receipt_path(requester: &Pubkey, execution_id: &str) -> Path {
+    Path::from_iter([requester.to_string().as_str(), execution_id])
+}
+
+#[async_trait]
+impl ReceiptStore for ObjectReceiptStore {
+    async fn put(&self, requester: &Pubkey, execution_id: &str, receipt: &Receipt) -> Result<()> {
+        let data = bincode::serialize(receipt)?;
+        self.store
+            .put(&receipt_path(requester, execution_id), data.into())
+            .await?;
+        Ok(())
+    }
+
+    async fn get(
+        &self,
+        requester: &Pubkey,
+        execution_id: &str,
+        max_size: usize,
+    ) -> Result<Option<Receipt>> {
+        let res = match self.store.get(&receipt_path(requester, execution_id)).await {
+            Ok(res) => res,
+            Err(object_store::Error::NotFound { .. }) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        if res.meta.size > max_size {
+            return Err(anyhow::anyhow!("Max size exceeded"));
+        }
+        Ok(Some(bincode::deserialize(&res.bytes().await?)?))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_receipt_path_stays_under_requester() {
+        let requester = Pubkey::new_unique();
+        let path = receipt_path(&requester, "../other/exec");
+        assert_eq!(path.parts().count(), 2);
+        assert_eq!(
+            path.parts().next().unwrap().as_ref(),
+            requester.to_string().as_str()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_missing_receipt() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = ReceiptStoreConfig::Local {
+            path: dir.path().to_string_lossy().to_string(),
+        }
+        .build()
+        .unwrap();
+        let res = store.get(&Pubkey::new_unique(), "exec", 1024).await;
+        assert!(res.unwrap().is_none());
+    }
+}
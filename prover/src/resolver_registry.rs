@@ -0,0 +1,357 @@
+use std::str::from_utf8;
+use std::sync::Arc;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use bonsol_schema::{decode_hashed_url, decode_proof_url, InputT, InputType};
+use reqwest::Url;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signer::Signer;
+
+use crate::input_resolver::{InputResolver, ProgramInput};
+
+struct Route {
+    input_type: InputType,
+    scheme: Option<String>,
+    resolver: Arc<dyn InputResolver>,
+}
+
+impl Route {
+    fn matches(&self, input_type: InputType, scheme: Option<&str>) -> bool {
+        self.input_type == input_type
+            && match &self.scheme {
+                Some(s) => scheme.is_some_and(|scheme| s.eq_ignore_ascii_case(scheme)),
+                None => true,
+            }
+    }
+}
+
+/// Chains input resolvers by input type and url scheme, inputs no registered resolver takes go to the fallback
+/// Each resolver only sees the inputs routed to it, the registry puts the results back at their original index
+pub struct InputResolverRegistry {
+    routes: Vec<Route>,
+    fallback: Arc<dyn InputResolver>,
+}
+
+pub struct InputResolverRegistryBuilder {
+    routes: Vec<Route>,
+    fallback: Arc<dyn InputResolver>,
+}
+
+impl InputResolverRegistryBuilder {
+    /// Routes every input of this type to the resolver
+    pub fn register(mut self, input_type: InputType, resolver: Arc<dyn InputResolver>) -> Self {
+        self.routes.push(Route {
+            input_type,
+            scheme: None,
+            resolver,
+        });
+        self
+    }
+
+    /// Routes inputs of this type whose url has the scheme to the resolver, i.e. `blob` for `blob://bucket/key`
+    pub fn register_scheme(
+        mut self,
+        input_type: InputType,
+        scheme: &str,
+        resolver: Arc<dyn InputResolver>,
+    ) -> Self {
+        self.routes.push(Route {
+            input_type,
+            scheme: Some(scheme.to_string()),
+            resolver,
+        });
+        self
+    }
+
+    pub fn build(self) -> InputResolverRegistry {
+        InputResolverRegistry {
+            routes: self.routes,
+            fallback: self.fallback,
+        }
+    }
+}
+
+impl InputResolverRegistry {
+    /// Routes are tried in registration order, scheme routes should be registered before catch all routes of the
+    /// same input type
+    pub fn builder(fallback: Arc<dyn InputResolver>) -> InputResolverRegistryBuilder {
+        InputResolverRegistryBuilder {
+            routes: Vec::new(),
+            fallback,
+        }
+    }
+
+    /// Returns the route index for the input or None for the fallback
+    fn route(&self, input_type: InputType, scheme: Option<&str>) -> Option<usize> {
+        self.routes
+            .iter()
+            .position(|r| r.matches(input_type, scheme) && r.resolver.supports(input_type))
+    }
+
+    fn resolver(&self, route: Option<usize>) -> &Arc<dyn InputResolver> {
+        match route {
+            Some(i) => &self.routes[i].resolver,
+            None => &self.fallback,
+        }
+    }
+
+    fn private_routes(&self, inputs: &[ProgramInput]) -> Vec<Option<Option<usize>>> {
+        inputs
+            .iter()
+            .map(|input| match input {
+                ProgramInput::Unresolved(ui) => {
+                    Some(self.route(InputType::Private, Some(ui.url.scheme())))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Every resolver that has private inputs routed to it gets a copy of the inputs where the others are empty
+    fn private_groups(&self, inputs: &[ProgramInput]) -> Vec<(Option<usize>, Vec<ProgramInput>)> {
+        let routes = self.private_routes(inputs);
+        let mut groups: Vec<(Option<usize>, Vec<ProgramInput>)> = Vec::new();
+        for route in routes.iter().flatten() {
+            if groups.iter().any(|(r, _)| r == route) {
+                continue;
+            }
+            let group = inputs
+                .iter()
+                .zip(routes.iter())
+                .map(|(input, r)| match r {
+                    Some(r) if r == route => input.clone(),
+                    _ => ProgramInput::Empty,
+                })
+                .collect();
+            groups.push((*route, group));
+        }
+        groups
+    }
+}
+
+#[async_trait]
+impl InputResolver for InputResolverRegistry {
+    fn supports(&self, input_type: InputType) -> bool {
+        self.fallback.supports(input_type)
+            || self
+                .routes
+                .iter()
+                .any(|r| r.input_type == input_type && r.resolver.supports(input_type))
+    }
+
+    async fn resolve_public_inputs(
+        &self,
+        inputs: Vec<InputT>,
+    ) -> Result<Vec<ProgramInput>, anyhow::Error> {
+        let mut res = vec![ProgramInput::Empty; inputs.len()];
+        let mut groups: Vec<(Option<usize>, Vec<usize>, Vec<InputT>)> = Vec::new();
+        for (index, input) in inputs.into_iter().enumerate() {
+            let route = self.route(input.input_type, input_scheme(&input).as_deref());
+            match groups.iter_mut().find(|(r, _, _)| *r == route) {
+                Some((_, indexes, group)) => {
+                    indexes.push(index);
+                    group.push(input);
+                }
+                None => groups.push((route, vec![index], vec![input])),
+            }
+        }
+        for (route, indexes, group) in groups {
+            let resolved = self.resolver(route).resolve_public_inputs(group).await?;
+            for (index, input) in indexes.into_iter().zip(resolved) {
+                res[index] = with_index(input, index as u8);
+            }
+        }
+        Ok(res)
+    }
+
+    async fn resolve_private_inputs(
+        &self,
+        requester: &Pubkey,
+        execution_id: &str,
+        inputs: &mut Vec<ProgramInput>,
+        signer: Arc<&(dyn Signer + Send + Sync)>,
+    ) -> Result<(), anyhow::Error> {
+        for (route, mut group) in self.private_groups(inputs) {
+            self.resolver(route)
+                .resolve_private_inputs(requester, execution_id, &mut group, signer.clone())
+                .await?;
+            for (input, resolved) in inputs.iter_mut().zip(group) {
+                if let ProgramInput::Resolved(_) = resolved {
+                    *input = resolved;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn probe_private_inputs(
+        &self,
+        requester: &Pubkey,
+        execution_id: &str,
+        inputs: &[ProgramInput],
+        signer: Arc<&(dyn Signer + Send + Sync)>,
+    ) -> Result<(), anyhow::Error> {
+        for (route, group) in self.private_groups(inputs) {
+            self.resolver(route)
+                .probe_private_inputs(requester, execution_id, &group, signer.clone())
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+/// The scheme of url based inputs, other inputs have none
+fn input_scheme(input: &InputT) -> Option<String> {
+    let data = input.data.as_deref()?;
+    let url = match input.input_type {
+        InputType::PublicUrl | InputType::Private => data,
+        InputType::PublicUrlHashed => decode_hashed_url(data)?.1,
+        InputType::PublicProof => decode_proof_url(data)?.1,
+        _ => return None,
+    };
+    Some(Url::parse(from_utf8(url).ok()?).ok()?.scheme().to_string())
+}
+
+fn with_index(input: ProgramInput, index: u8) -> ProgramInput {
+    match input {
+        ProgramInput::Resolved(mut ri) => {
+            ri.index = index;
+            ProgramInput::Resolved(ri)
+        }
+        ProgramInput::Unresolved(mut ui) => {
+            ui.index = index;
+            ProgramInput::Unresolved(ui)
+        }
+        ProgramInput::Empty => ProgramInput::Empty,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::input_resolver::{DefaultInputResolver, ResolvedInput, UnresolvedInput};
+    use bonsol_schema::ProgramInputType;
+    use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+    use solana_sdk::signature::Keypair;
+
+    /// Resolves `blob://<data>` urls to the host part
+    struct BlobResolver;
+
+    #[async_trait]
+    impl InputResolver for BlobResolver {
+        fn supports(&self, input_type: InputType) -> bool {
+            matches!(input_type, InputType::PublicUrl | InputType::Private)
+        }
+
+        async fn resolve_public_inputs(&self, inputs: Vec<InputT>) -> Result<Vec<ProgramInput>> {
+            inputs
+                .into_iter()
+                .enumerate()
+                .map(|(index, input)| {
+                    let url = Url::parse(from_utf8(&input.data.unwrap_or_default())?)?;
+                    Ok(blob(index as u8, &url, ProgramInputType::Public))
+                })
+                .collect()
+        }
+
+        async fn resolve_private_inputs(
+            &self,
+            _requester: &Pubkey,
+            _execution_id: &str,
+            inputs: &mut Vec<ProgramInput>,
+            _signer: Arc<&(dyn Signer + Send + Sync)>,
+        ) -> Result<()> {
+            for input in inputs.iter_mut() {
+                match input {
+                    ProgramInput::Unresolved(ui) if ui.url.scheme() == "blob" => {
+                        *input = blob(ui.index, &ui.url, ProgramInputType::Private);
+                    }
+                    ProgramInput::Unresolved(ui) => {
+                        return Err(anyhow::anyhow!("Not a blob url {}", ui.url));
+                    }
+                    _ => {}
+                }
+            }
+            Ok(())
+        }
+    }
+
+    fn blob(index: u8, url: &Url, input_type: ProgramInputType) -> ProgramInput {
+        ProgramInput::Resolved(ResolvedInput {
+            index,
+            data: url.host_str().unwrap_or_default().as_bytes().to_vec(),
+            input_type,
+        })
+    }
+
+    fn registry() -> InputResolverRegistry {
+        let fallback = DefaultInputResolver::new(
+            Arc::new(reqwest::Client::new()),
+            Arc::new(RpcClient::new("http://localhost:8899".to_string())),
+        );
+        InputResolverRegistry::builder(Arc::new(fallback))
+            .register_scheme(InputType::PublicUrl, "blob", Arc::new(BlobResolver))
+            .register_scheme(InputType::Private, "blob", Arc::new(BlobResolver))
+            .build()
+    }
+
+    #[tokio::test]
+    async fn test_routes_public_inputs_by_scheme() {
+        let inputs = vec![
+            InputT::public(b"first".to_vec()),
+            InputT::url(b"blob://second".to_vec()),
+            InputT::public(b"third".to_vec()),
+        ];
+
+        let res = registry().resolve_public_inputs(inputs).await.unwrap();
+
+        let data: Vec<(u8, Vec<u8>)> = res
+            .into_iter()
+            .map(|input| match input {
+                ProgramInput::Resolved(ri) => (ri.index, ri.data),
+                _ => panic!("input was not resolved"),
+            })
+            .collect();
+        assert_eq!(
+            data,
+            vec![
+                (0, b"first".to_vec()),
+                (1, b"second".to_vec()),
+                (2, b"third".to_vec())
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_routes_private_inputs_by_scheme() {
+        let mut inputs = vec![
+            ProgramInput::Resolved(ResolvedInput {
+                index: 0,
+                data: b"public".to_vec(),
+                input_type: ProgramInputType::Public,
+            }),
+            ProgramInput::Unresolved(UnresolvedInput {
+                index: 1,
+                url: Url::parse("blob://private").unwrap(),
+                input_type: ProgramInputType::Private,
+            }),
+        ];
+        let kp = Keypair::new();
+
+        registry()
+            .resolve_private_inputs(&Pubkey::new_unique(), "exec", &mut inputs, Arc::new(&kp))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            inputs[1],
+            ProgramInput::Resolved(ResolvedInput {
+                index: 1,
+                data: b"private".to_vec(),
+                input_type: ProgramInputType::Private,
+            })
+        );
+        assert!(matches!(&inputs[0], ProgramInput::Resolved(ri) if ri.data == b"public"));
+    }
+}
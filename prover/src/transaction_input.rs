@@ -0,0 +1,212 @@
+//! Guest encoding of `PublicTransaction` inputs.
+//!
+//! All integers are little endian, vectors are prefixed with a `u32` element count and strings with a `u32` byte length.
+//! ```text
+//! slot: u64
+//! block_time: i64                  // 0 when the rpc does not know it
+//! success: u8                      // 1 when the transaction executed without error
+//! fee: u64
+//! compute_units_consumed: u64      // 0 when the rpc does not report it
+//! signatures: Vec<[u8; 64]>        // in signer order, the first is the signature the input names
+//! message: Vec<u8>                 // the serialized legacy or v0 message the signers signed
+//! loaded_writable_addresses: Vec<[u8; 32]>
+//! loaded_readonly_addresses: Vec<[u8; 32]>
+//! pre_balances: Vec<u64>
+//! post_balances: Vec<u64>
+//! log_messages: Vec<String>
+//! ```
+use std::str::FromStr;
+
+use anyhow::Result;
+use solana_sdk::message::VersionedMessage;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_transaction_status::{
+    option_serializer::OptionSerializer, EncodedConfirmedTransactionWithStatusMeta,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransactionInput {
+    pub slot: u64,
+    pub block_time: i64,
+    pub success: bool,
+    pub fee: u64,
+    pub compute_units_consumed: u64,
+    pub signatures: Vec<Signature>,
+    pub message: VersionedMessage,
+    pub loaded_writable_addresses: Vec<Pubkey>,
+    pub loaded_readonly_addresses: Vec<Pubkey>,
+    pub pre_balances: Vec<u64>,
+    pub post_balances: Vec<u64>,
+    pub log_messages: Vec<String>,
+}
+
+impl TransactionInput {
+    /// Fails unless the transaction carries the signature it was fetched by, the guest binds the
+    /// input to the requested transaction through it
+    pub fn check_signature(&self, signature: &Signature) -> Result<()> {
+        if !self.signatures.contains(signature) {
+            return Err(anyhow::anyhow!(
+                "Transaction does not carry signature {}",
+                signature
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&self.slot.to_le_bytes());
+        out.extend_from_slice(&self.block_time.to_le_bytes());
+        out.push(self.success as u8);
+        out.extend_from_slice(&self.fee.to_le_bytes());
+        out.extend_from_slice(&self.compute_units_consumed.to_le_bytes());
+        write_len(&mut out, self.signatures.len());
+        for signature in self.signatures.iter() {
+            out.extend_from_slice(signature.as_ref());
+        }
+        write_bytes(&mut out, &self.message.serialize());
+        write_len(&mut out, self.loaded_writable_addresses.len());
+        for address in self.loaded_writable_addresses.iter() {
+            out.extend_from_slice(address.as_ref());
+        }
+        write_len(&mut out, self.loaded_readonly_addresses.len());
+        for address in self.loaded_readonly_addresses.iter() {
+            out.extend_from_slice(address.as_ref());
+        }
+        for balances in [&self.pre_balances, &self.post_balances] {
+            write_len(&mut out, balances.len());
+            for balance in balances.iter() {
+                out.extend_from_slice(&balance.to_le_bytes());
+            }
+        }
+        write_len(&mut out, self.log_messages.len());
+        for log in self.log_messages.iter() {
+            write_bytes(&mut out, log.as_bytes());
+        }
+        out
+    }
+}
+
+impl TryFrom<EncodedConfirmedTransactionWithStatusMeta> for TransactionInput {
+    type Error = anyhow::Error;
+
+    fn try_from(tx: EncodedConfirmedTransactionWithStatusMeta) -> Result<Self> {
+        let transaction = tx
+            .transaction
+            .transaction
+            .decode()
+            .ok_or(anyhow::anyhow!("Transaction could not be decoded"))?;
+        let meta = tx
+            .transaction
+            .meta
+            .ok_or(anyhow::anyhow!("Transaction has no status meta"))?;
+        let (loaded_writable_addresses, loaded_readonly_addresses) = match meta.loaded_addresses {
+            OptionSerializer::Some(loaded) => (
+                parse_pubkeys(&loaded.writable)?,
+                parse_pubkeys(&loaded.readonly)?,
+            ),
+            _ => (vec![], vec![]),
+        };
+        Ok(TransactionInput {
+            slot: tx.slot,
+            block_time: tx.block_time.unwrap_or_default(),
+            success: meta.err.is_none(),
+            fee: meta.fee,
+            compute_units_consumed: Option::from(meta.compute_units_consumed).unwrap_or_default(),
+            signatures: transaction.signatures,
+            message: transaction.message,
+            loaded_writable_addresses,
+            loaded_readonly_addresses,
+            pre_balances: meta.pre_balances,
+            post_balances: meta.post_balances,
+            log_messages: Option::from(meta.log_messages).unwrap_or_default(),
+        })
+    }
+}
+
+fn parse_pubkeys(keys: &[String]) -> Result<Vec<Pubkey>> {
+    keys.iter()
+        .map(|k| Pubkey::from_str(k).map_err(Into::into))
+        .collect()
+}
+
+fn write_len(out: &mut Vec<u8>, len: usize) {
+    out.extend_from_slice(&(len as u32).to_le_bytes());
+}
+
+fn write_bytes(out: &mut Vec<u8>, data: &[u8]) {
+    write_len(out, data.len());
+    out.extend_from_slice(data);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use solana_sdk::message::Message;
+    use solana_sdk::signature::Keypair;
+    use solana_sdk::signer::Signer;
+    use solana_sdk::system_instruction;
+
+    fn transaction_input() -> TransactionInput {
+        let payer = Keypair::new();
+        let message = VersionedMessage::Legacy(Message::new(
+            &[system_instruction::transfer(
+                &payer.pubkey(),
+                &Pubkey::new_unique(),
+                10,
+            )],
+            Some(&payer.pubkey()),
+        ));
+        TransactionInput {
+            slot: 9,
+            block_time: 1_700_000_000,
+            success: true,
+            fee: 5000,
+            compute_units_consumed: 150,
+            signatures: vec![payer.sign_message(&message.serialize())],
+            message,
+            loaded_writable_addresses: vec![],
+            loaded_readonly_addresses: vec![],
+            pre_balances: vec![100, 0, 1],
+            post_balances: vec![85, 10, 1],
+            log_messages: vec!["Program log: ok".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_transaction_input_layout() {
+        let input = transaction_input();
+        let bytes = input.to_bytes();
+
+        let message_bytes = input.message.serialize();
+        assert_eq!(&bytes[0..8], &9u64.to_le_bytes());
+        assert_eq!(&bytes[8..16], &1_700_000_000i64.to_le_bytes());
+        assert_eq!(bytes[16], 1);
+        assert_eq!(&bytes[17..25], &5000u64.to_le_bytes());
+        assert_eq!(&bytes[25..33], &150u64.to_le_bytes());
+        assert_eq!(&bytes[33..37], &1u32.to_le_bytes());
+        assert_eq!(&bytes[37..101], input.signatures[0].as_ref());
+        assert_eq!(
+            &bytes[101..105],
+            &(message_bytes.len() as u32).to_le_bytes()
+        );
+        assert_eq!(&bytes[105..105 + message_bytes.len()], &message_bytes[..]);
+        let rest = &bytes[105 + message_bytes.len()..];
+        // no loaded addresses
+        assert_eq!(&rest[0..8], &[0u8; 8]);
+        assert_eq!(&rest[8..12], &3u32.to_le_bytes());
+        assert_eq!(&rest[12..20], &100u64.to_le_bytes());
+        let logs = &rest[12 + 24 + 4 + 24..];
+        assert_eq!(&logs[0..4], &1u32.to_le_bytes());
+        assert_eq!(&logs[4..8], &15u32.to_le_bytes());
+        assert_eq!(&logs[8..], b"Program log: ok");
+    }
+
+    #[test]
+    fn test_check_signature() {
+        let input = transaction_input();
+        input.check_signature(&input.signatures[0]).unwrap();
+        assert!(input.check_signature(&Signature::new_unique()).is_err());
+    }
+}
@@ -0,0 +1,377 @@
+use std::error::Error as StdError;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::{redirect, Url};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum UrlGuardError {
+    #[error("Scheme {0} is not allowed")]
+    SchemeNotAllowed(String),
+    #[error("Port {0} is not allowed")]
+    PortNotAllowed(u16),
+    #[error("Url has no host")]
+    MissingHost,
+    #[error("Address {0} is not allowed")]
+    AddressNotAllowed(IpAddr),
+    #[error("No allowed address for {0}")]
+    NoAllowedAddress(String),
+    #[error("Too many redirects")]
+    TooManyRedirects,
+    #[error("Invalid network {0}")]
+    InvalidNetwork(String),
+}
+
+/// Limits which urls a node fetches on behalf of requesters
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UrlGuardConfig {
+    pub allowed_schemes: Vec<String>,
+    pub allowed_ports: Vec<u16>,
+    /// Hosts that may resolve to loopback, link-local or private addresses, i.e. a local ipfs gateway
+    pub allowed_hosts: Vec<String>,
+    /// Networks in CIDR notation that are reachable even though they are private
+    pub allowed_networks: Vec<String>,
+    pub max_redirects: usize,
+}
+
+impl Default for UrlGuardConfig {
+    fn default() -> Self {
+        UrlGuardConfig {
+            allowed_schemes: vec!["https".to_string(), "http".to_string()],
+            allowed_ports: vec![80, 443],
+            allowed_hosts: vec![],
+            allowed_networks: vec![],
+            max_redirects: 5,
+        }
+    }
+}
+
+/// Checks urls and redirect targets before they are fetched and resolves dns itself, so the address that was
+/// checked is the address that is connected to
+#[derive(Debug, Clone)]
+pub struct UrlGuard {
+    config: UrlGuardConfig,
+    allowed_networks: Vec<(IpAddr, u8)>,
+}
+
+impl UrlGuard {
+    pub fn new(config: UrlGuardConfig) -> Result<Self, UrlGuardError> {
+        let allowed_networks = config
+            .allowed_networks
+            .iter()
+            .map(|n| parse_network(n).ok_or_else(|| UrlGuardError::InvalidNetwork(n.clone())))
+            .collect::<Result<_, _>>()?;
+        Ok(UrlGuard {
+            config,
+            allowed_networks,
+        })
+    }
+
+    /// A client builder whose dns resolution and redirects go through this guard
+    pub fn client_builder(self: &Arc<Self>) -> reqwest::ClientBuilder {
+        let guard = self.clone();
+        reqwest::Client::builder()
+            .dns_resolver(self.clone())
+            .redirect(redirect::Policy::custom(move |attempt| {
+                if attempt.previous().len() > guard.config.max_redirects {
+                    return attempt.error(UrlGuardError::TooManyRedirects);
+                }
+                match guard.check_url(attempt.url()) {
+                    Ok(()) => attempt.follow(),
+                    Err(e) => attempt.error(e),
+                }
+            }))
+    }
+
+    /// Checks the scheme, port and literal ip hosts, named hosts are checked when they are resolved
+    pub fn check_url(&self, url: &Url) -> Result<(), UrlGuardError> {
+        if !self
+            .config
+            .allowed_schemes
+            .iter()
+            .any(|s| s.eq_ignore_ascii_case(url.scheme()))
+        {
+            return Err(UrlGuardError::SchemeNotAllowed(url.scheme().to_string()));
+        }
+        let port = url.port_or_known_default().unwrap_or_default();
+        if !self.config.allowed_ports.contains(&port) {
+            return Err(UrlGuardError::PortNotAllowed(port));
+        }
+        let host = url.host_str().ok_or(UrlGuardError::MissingHost)?;
+        match host.trim_matches(['[', ']']).parse::<IpAddr>() {
+            Ok(ip) if !self.is_allowed_ip(ip) => Err(UrlGuardError::AddressNotAllowed(ip)),
+            _ => Ok(()),
+        }
+    }
+
+    fn is_allowed_host(&self, host: &str) -> bool {
+        self.config
+            .allowed_hosts
+            .iter()
+            .any(|h| h.eq_ignore_ascii_case(host))
+    }
+
+    fn is_allowed_ip(&self, ip: IpAddr) -> bool {
+        !is_internal_ip(ip)
+            || self
+                .allowed_networks
+                .iter()
+                .any(|(net, prefix)| in_network(ip, *net, *prefix))
+    }
+
+    fn allowed_addrs(&self, host: &str, addrs: Vec<SocketAddr>) -> Result<Addrs, UrlGuardError> {
+        if self.is_allowed_host(host) {
+            return Ok(Box::new(addrs.into_iter()));
+        }
+        let allowed: Vec<SocketAddr> = addrs
+            .into_iter()
+            .filter(|a| self.is_allowed_ip(a.ip()))
+            .collect();
+        if allowed.is_empty() {
+            return Err(UrlGuardError::NoAllowedAddress(host.to_string()));
+        }
+        Ok(Box::new(allowed.into_iter()))
+    }
+}
+
+impl Resolve for UrlGuard {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(resolve_allowed(self.clone(), name.as_str().to_string()))
+    }
+}
+
+async fn resolve_allowed(
+    guard: UrlGuard,
+    host: String,
+) -> Result<Addrs, Box<dyn StdError + Send + Sync>> {
+    // the port is replaced by reqwest, only the addresses matter here
+    let addrs = tokio::net::lookup_host((host.as_str(), 0)).await?.collect();
+    Ok(guard.allowed_addrs(&host, addrs)?)
+}
+
+/// True if an error came from the guard, such requests must not be retried
+pub fn is_guard_error(err: &(dyn StdError + 'static)) -> bool {
+    let mut source = Some(err);
+    while let Some(e) = source {
+        if e.is::<UrlGuardError>() {
+            return true;
+        }
+        source = e.source();
+    }
+    false
+}
+
+/// Loopback, link-local, private, shared, multicast and other addresses that are not on the public internet
+pub fn is_internal_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_internal_ipv4(ip),
+        IpAddr::V6(ip) => is_internal_ipv6(ip) || embedded_ipv4(ip).is_some_and(is_internal_ipv4),
+    }
+}
+
+/// The IPv4 address an IPv6 address reaches, for IPv4-mapped `::ffff:0:0/96`, IPv4-compatible `::/96`,
+/// NAT64 `64:ff9b::/96` and 6to4 `2002::/16` addresses
+fn embedded_ipv4(ip: Ipv6Addr) -> Option<Ipv4Addr> {
+    let segments = ip.segments();
+    let octets = ip.octets();
+    let last = Ipv4Addr::new(octets[12], octets[13], octets[14], octets[15]);
+    match segments {
+        [0, 0, 0, 0, 0, 0xffff, _, _] | [0, 0, 0, 0, 0, 0, _, _] => Some(last),
+        [0x64, 0xff9b, 0, 0, 0, 0, _, _] => Some(last),
+        [0x2002, _, _, _, _, _, _, _] => {
+            Some(Ipv4Addr::new(octets[2], octets[3], octets[4], octets[5]))
+        }
+        _ => None,
+    }
+}
+
+fn is_internal_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+    ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        || a == 0
+        // shared address space 100.64.0.0/10
+        || (a == 100 && (b & 0xc0) == 64)
+        // ietf protocol assignments and benchmarking
+        || (a == 192 && b == 0 && c == 0)
+        || (a == 198 && (b & 0xfe) == 18)
+        || a >= 240
+}
+
+fn is_internal_ipv6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        // unique local fc00::/7
+        || (first & 0xfe00) == 0xfc00
+        // link local fe80::/10
+        || (first & 0xffc0) == 0xfe80
+        // documentation 2001:db8::/32
+        || (first == 0x2001 && ip.segments()[1] == 0x0db8)
+}
+
+fn parse_network(network: &str) -> Option<(IpAddr, u8)> {
+    let (ip, prefix) = match network.split_once('/') {
+        Some((ip, prefix)) => (ip.parse::<IpAddr>().ok()?, prefix.parse::<u8>().ok()?),
+        None => {
+            let ip = network.parse::<IpAddr>().ok()?;
+            (ip, if ip.is_ipv4() { 32 } else { 128 })
+        }
+    };
+    let max = if ip.is_ipv4() { 32 } else { 128 };
+    (prefix <= max).then_some((ip, prefix))
+}
+
+fn in_network(ip: IpAddr, network: IpAddr, prefix: u8) -> bool {
+    match (ip, network) {
+        (IpAddr::V4(ip), IpAddr::V4(net)) => {
+            let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+            u32::from(ip) & mask == u32::from(net) & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V6(net)) => {
+            let mask = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+            u128::from(ip) & mask == u128::from(net) & mask
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn guard(config: UrlGuardConfig) -> Arc<UrlGuard> {
+        Arc::new(UrlGuard::new(config).unwrap())
+    }
+
+    fn local_config(port: u16) -> UrlGuardConfig {
+        UrlGuardConfig {
+            allowed_ports: vec![port],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_internal_ips() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:169.254.169.254",
+            "::127.0.0.1",
+            "::10.0.0.1",
+            "64:ff9b::169.254.169.254",
+            "64:ff9b::a00:1",
+            "2002:7f00:1::",
+            "2002:a9fe:a9fe::1",
+        ] {
+            assert!(is_internal_ip(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in [
+            "1.1.1.1",
+            "8.8.8.8",
+            "2606:4700:4700::1111",
+            "::ffff:1.1.1.1",
+            "64:ff9b::8.8.8.8",
+            "2002:808:808::1",
+        ] {
+            assert!(!is_internal_ip(ip.parse().unwrap()), "{}", ip);
+        }
+    }
+
+    #[test]
+    fn test_check_url() {
+        let guard = guard(UrlGuardConfig {
+            allowed_networks: vec!["10.0.0.0/8".to_string()],
+            ..Default::default()
+        });
+        let check = |url: &str| guard.check_url(&Url::parse(url).unwrap());
+        assert!(check("https://example.com/input").is_ok());
+        assert!(check("http://10.1.2.3/input").is_ok());
+        assert!(matches!(
+            check("http://169.254.169.254/latest/meta-data"),
+            Err(UrlGuardError::AddressNotAllowed(_))
+        ));
+        assert!(matches!(
+            check("http://[::1]/"),
+            Err(UrlGuardError::AddressNotAllowed(_))
+        ));
+        assert!(matches!(
+            check("https://example.com:6379/"),
+            Err(UrlGuardError::PortNotAllowed(6379))
+        ));
+        assert!(matches!(
+            check("file:///etc/passwd"),
+            Err(UrlGuardError::SchemeNotAllowed(_))
+        ));
+        assert!(UrlGuard::new(UrlGuardConfig {
+            allowed_networks: vec!["10.0.0.0/33".to_string()],
+            ..Default::default()
+        })
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_blocks_loopback_host() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server.mock("GET", "/").expect(0).create_async().await;
+        let url = Url::parse(&server.url()).unwrap();
+        let guard = guard(local_config(url.port().unwrap()));
+        let client = guard.client_builder().build().unwrap();
+
+        let err = client
+            .get(format!("http://localhost:{}/", url.port().unwrap()))
+            .send()
+            .await
+            .unwrap_err();
+
+        assert!(is_guard_error(&err));
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_rechecks_redirects() {
+        let mut server = mockito::Server::new_async().await;
+        let port = Url::parse(&server.url()).unwrap().port().unwrap();
+        let redirect = server
+            .mock("GET", "/redirect")
+            .with_status(302)
+            .with_header("location", &format!("http://127.0.0.1:{}/target", port))
+            .create_async()
+            .await;
+        let target = server.mock("GET", "/target").expect(0).create_async().await;
+        let guard = guard(UrlGuardConfig {
+            allowed_hosts: vec!["localhost".to_string()],
+            ..local_config(port)
+        });
+        let client = guard.client_builder().build().unwrap();
+
+        let err = client
+            .get(format!("http://localhost:{}/redirect", port))
+            .send()
+            .await
+            .unwrap_err();
+
+        assert!(err.is_redirect());
+        assert!(is_guard_error(&err));
+        redirect.assert_async().await;
+        target.assert_async().await;
+    }
+}
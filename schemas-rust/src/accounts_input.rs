@@ -0,0 +1,93 @@
+//! Encoding of `PublicAccounts` input data.
+//!
+//! The request data is a little endian `min_context_slot: u64` followed by one 73 byte entry per account:
+//! `pubkey: [u8; 32]`, `flags: u8`, `owner: [u8; 32]`, `offset: u32`, `length: u32`.
+//! Bit 0 of `flags` marks the owner as expected, otherwise the owner bytes are ignored.
+//! A `length` of 0 selects the account data from `offset` to the end.
+//!
+//! Provers read every account in one call at a single slot, no earlier than `min_context_slot` when it is set,
+//! and hand the guest `slot: u64` followed by `owner: [u8; 32]`, `len: u32`, `data: [u8; len]` for each account in request order.
+use crate::error::ChannelSchemaError;
+
+pub const ACCOUNT_ENTRY_LEN: usize = 73;
+/// Upper bound of the rpc `getMultipleAccounts` call
+pub const MAX_ACCOUNTS: usize = 100;
+const HEADER_LEN: usize = 8;
+const FLAG_CHECK_OWNER: u8 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AccountSlice {
+    pub pubkey: [u8; 32],
+    /// Rejects the input if the account is owned by another program
+    pub owner: Option<[u8; 32]>,
+    pub offset: u32,
+    /// Number of bytes from `offset`, 0 reads to the end of the account data
+    pub length: u32,
+}
+
+impl AccountSlice {
+    pub const fn whole(pubkey: [u8; 32]) -> Self {
+        Self {
+            pubkey,
+            owner: None,
+            offset: 0,
+            length: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct AccountsInput {
+    /// 0 lets the prover read at any slot
+    pub min_context_slot: u64,
+    pub accounts: Vec<AccountSlice>,
+}
+
+impl AccountsInput {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(HEADER_LEN + self.accounts.len() * ACCOUNT_ENTRY_LEN);
+        data.extend_from_slice(&self.min_context_slot.to_le_bytes());
+        for account in self.accounts.iter() {
+            data.extend_from_slice(&account.pubkey);
+            data.push(if account.owner.is_some() {
+                FLAG_CHECK_OWNER
+            } else {
+                0
+            });
+            data.extend_from_slice(&account.owner.unwrap_or_default());
+            data.extend_from_slice(&account.offset.to_le_bytes());
+            data.extend_from_slice(&account.length.to_le_bytes());
+        }
+        data
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, ChannelSchemaError> {
+        if data.len() < HEADER_LEN || (data.len() - HEADER_LEN) % ACCOUNT_ENTRY_LEN != 0 {
+            return Err(ChannelSchemaError::InvalidInputData);
+        }
+        let (header, entries) = data.split_at(HEADER_LEN);
+        let count = entries.len() / ACCOUNT_ENTRY_LEN;
+        if count == 0 || count > MAX_ACCOUNTS {
+            return Err(ChannelSchemaError::InvalidInputData);
+        }
+        let accounts = entries
+            .chunks_exact(ACCOUNT_ENTRY_LEN)
+            .map(|e| AccountSlice {
+                pubkey: array32(&e[0..32]),
+                owner: (e[32] & FLAG_CHECK_OWNER != 0).then(|| array32(&e[33..65])),
+                offset: u32::from_le_bytes([e[65], e[66], e[67], e[68]]),
+                length: u32::from_le_bytes([e[69], e[70], e[71], e[72]]),
+            })
+            .collect();
+        Ok(AccountsInput {
+            min_context_slot: u64::from_le_bytes(header.try_into().unwrap_or_default()),
+            accounts,
+        })
+    }
+}
+
+fn array32(s: &[u8]) -> [u8; 32] {
+    let mut a = [0u8; 32];
+    a.copy_from_slice(s);
+    a
+}
@@ -4,4 +4,6 @@ use thiserror::Error;
 pub enum ChannelSchemaError {
     #[error("Invalid Instruction")]
     InvalidInstruction,
+    #[error("Invalid Input Data")]
+    InvalidInputData,
 }
@@ -8,6 +8,7 @@ use std::fmt::Display;
 
 use error::ChannelSchemaError;
 use num_derive::{FromPrimitive, ToPrimitive};
+pub mod accounts_input;
 pub mod error;
 pub use channel_instruction_generated::*;
 pub use claim_v1_generated::*;
@@ -29,6 +30,9 @@ pub enum ExitCode {
     ProvingError = 2,
     InputError = 3,
     Expired = 4,
+    GuestPanic = 5,
+    CycleLimitExceeded = 6,
+    CompressionError = 7,
 }
 
 impl Display for ExitCode {
@@ -39,6 +43,23 @@ impl Display for ExitCode {
             ExitCode::ProvingError => write!(f, "ProvingError"),
             ExitCode::InputError => write!(f, "InputError"),
             ExitCode::Expired => write!(f, "Expired"),
+            ExitCode::GuestPanic => write!(f, "GuestPanic"),
+            ExitCode::CycleLimitExceeded => write!(f, "CycleLimitExceeded"),
+            ExitCode::CompressionError => write!(f, "CompressionError"),
+        }
+    }
+}
+
+impl From<FailureReason> for ExitCode {
+    /// Maps the failure a prover reported in a StatusV1 to the exit code recorded on the execution account.
+    /// Statuses without a reason are treated as generic proving errors.
+    fn from(reason: FailureReason) -> Self {
+        match reason {
+            FailureReason::GuestPanic => ExitCode::GuestPanic,
+            FailureReason::CycleLimitExceeded => ExitCode::CycleLimitExceeded,
+            FailureReason::InputDownloadFailure => ExitCode::InputError,
+            FailureReason::CompressionFailure => ExitCode::CompressionError,
+            _ => ExitCode::ProvingError,
         }
     }
 }
@@ -78,4 +99,188 @@ impl InputT {
             data: Some(data),
         }
     }
+    pub fn public_accounts(accounts: &accounts_input::AccountsInput) -> Self {
+        Self {
+            input_type: InputType::PublicAccounts,
+            data: Some(accounts.to_bytes()),
+        }
+    }
+    pub fn public_transaction(signature: &[u8; 64]) -> Self {
+        Self {
+            input_type: InputType::PublicTransaction,
+            data: Some(signature.to_vec()),
+        }
+    }
+    pub fn url_with_hash(url: &str, body_hash: &[u8; URL_BODY_HASH_LEN]) -> Self {
+        Self {
+            input_type: InputType::PublicUrlHashed,
+            data: Some(encode_hashed_url(url, body_hash)),
+        }
+    }
+    /// A proof url whose receipt must have been produced by the image
+    pub fn public_proof_with_image_id(url: &str, image_id: &[u8; 32]) -> Self {
+        Self {
+            input_type: InputType::PublicProof,
+            data: Some(encode_proof_url(url, Some(image_id))),
+        }
+    }
+    pub fn execution_proof(requester: &[u8; 32], execution_id: &str) -> Self {
+        Self {
+            input_type: InputType::PublicExecutionProof,
+            data: Some(encode_execution_ref(requester, execution_id, None)),
+        }
+    }
+    /// A prior execution whose receipt must have been produced by the image
+    pub fn execution_proof_with_image_id(
+        requester: &[u8; 32],
+        execution_id: &str,
+        image_id: &[u8; 32],
+    ) -> Self {
+        Self {
+            input_type: InputType::PublicExecutionProof,
+            data: Some(encode_execution_ref(
+                requester,
+                execution_id,
+                Some(image_id),
+            )),
+        }
+    }
+}
+
+/// Length of the sha256 hash of the response body that prefixes the url of a `PublicUrlHashed` input
+pub const URL_BODY_HASH_LEN: usize = 32;
+
+/// Encodes `PublicUrlHashed` input data, the expected body hash followed by the url
+pub fn encode_hashed_url(url: &str, body_hash: &[u8; URL_BODY_HASH_LEN]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(URL_BODY_HASH_LEN + url.len());
+    data.extend_from_slice(body_hash);
+    data.extend_from_slice(url.as_bytes());
+    data
+}
+
+/// Splits `PublicUrlHashed` input data into the expected body hash and the url bytes
+pub fn decode_hashed_url(data: &[u8]) -> Option<(&[u8; URL_BODY_HASH_LEN], &[u8])> {
+    if data.len() <= URL_BODY_HASH_LEN {
+        return None;
+    }
+    let (hash, url) = data.split_at(URL_BODY_HASH_LEN);
+    Some((hash.try_into().ok()?, url))
+}
+
+/// Marks `PublicProof` input data that carries an expected image id, a plain url never starts with it
+pub const PROOF_IMAGE_ID_MARKER: u8 = 0;
+
+/// Encodes `PublicProof` input data, the url alone or the marker and the expected image id followed by the url
+pub fn encode_proof_url(url: &str, image_id: Option<&[u8; 32]>) -> Vec<u8> {
+    match image_id {
+        Some(image_id) => {
+            let mut data = Vec::with_capacity(1 + 32 + url.len());
+            data.push(PROOF_IMAGE_ID_MARKER);
+            data.extend_from_slice(image_id);
+            data.extend_from_slice(url.as_bytes());
+            data
+        }
+        None => url.as_bytes().to_vec(),
+    }
+}
+
+/// Splits `PublicProof` input data into the expected image id, if any, and the url bytes
+pub fn decode_proof_url(data: &[u8]) -> Option<(Option<&[u8; 32]>, &[u8])> {
+    match data.split_first() {
+        Some((&PROOF_IMAGE_ID_MARKER, rest)) if rest.len() > 32 => {
+            let (image_id, url) = rest.split_at(32);
+            Some((Some(image_id.try_into().ok()?), url))
+        }
+        Some((&PROOF_IMAGE_ID_MARKER, _)) | None => None,
+        Some(_) => Some((None, data)),
+    }
+}
+
+/// Marks `PublicExecutionProof` input data that carries an expected image id after the requester, an
+/// execution id never starts with it
+pub const EXECUTION_REF_IMAGE_ID_MARKER: u8 = 0;
+
+/// Encodes `PublicExecutionProof` input data, the requester of the prior execution followed by its id or by the
+/// marker, the expected image id and its id
+pub fn encode_execution_ref(
+    requester: &[u8; 32],
+    execution_id: &str,
+    image_id: Option<&[u8; 32]>,
+) -> Vec<u8> {
+    let mut data = Vec::with_capacity(32 + 1 + 32 + execution_id.len());
+    data.extend_from_slice(requester);
+    if let Some(image_id) = image_id {
+        data.push(EXECUTION_REF_IMAGE_ID_MARKER);
+        data.extend_from_slice(image_id);
+    }
+    data.extend_from_slice(execution_id.as_bytes());
+    data
+}
+
+/// Splits `PublicExecutionProof` input data into the requester, the expected image id, if any, and the
+/// execution id
+pub fn decode_execution_ref(data: &[u8]) -> Option<(&[u8; 32], Option<&[u8; 32]>, &str)> {
+    if data.len() <= 32 {
+        return None;
+    }
+    let (requester, rest) = data.split_at(32);
+    let (image_id, execution_id) = match rest.split_first() {
+        Some((&EXECUTION_REF_IMAGE_ID_MARKER, rest)) if rest.len() > 32 => {
+            let (image_id, execution_id) = rest.split_at(32);
+            (Some(image_id.try_into().ok()?), execution_id)
+        }
+        Some((&EXECUTION_REF_IMAGE_ID_MARKER, _)) | None => return None,
+        Some(_) => (None, rest),
+    };
+    Some((
+        requester.try_into().ok()?,
+        image_id,
+        std::str::from_utf8(execution_id).ok()?,
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_failure_reason_exit_codes() {
+        let cases = [
+            (FailureReason::GuestPanic, ExitCode::GuestPanic),
+            (
+                FailureReason::CycleLimitExceeded,
+                ExitCode::CycleLimitExceeded,
+            ),
+            (FailureReason::InputDownloadFailure, ExitCode::InputError),
+            (
+                FailureReason::CompressionFailure,
+                ExitCode::CompressionError,
+            ),
+            (FailureReason::Unknown, ExitCode::ProvingError),
+            // reasons added after this program version are generic proving errors
+            (FailureReason(200), ExitCode::ProvingError),
+        ];
+        for (reason, exit_code) in cases {
+            assert_eq!(ExitCode::from(reason).to_string(), exit_code.to_string());
+        }
+    }
+
+    #[test]
+    fn test_execution_ref() {
+        let requester = [1u8; 32];
+        let image_id = [2u8; 32];
+
+        let data = encode_execution_ref(&requester, "exec", None);
+        assert_eq!(
+            decode_execution_ref(&data),
+            Some((&requester, None, "exec"))
+        );
+        let data = encode_execution_ref(&requester, "exec", Some(&image_id));
+        assert_eq!(
+            decode_execution_ref(&data),
+            Some((&requester, Some(&image_id), "exec"))
+        );
+        assert_eq!(decode_execution_ref(&requester), None);
+        assert_eq!(decode_execution_ref(&data[..40]), None);
+    }
 }
@@ -186,8 +186,23 @@ mutate_prover_version(value:ProverVersion):boolean {
   return true;
 }
 
+callbackLookupTable(index: number):number|null {
+  const offset = this.bb!.__offset(this.bb_pos, 28);
+  return offset ? this.bb!.readUint8(this.bb!.__vector(this.bb_pos + offset) + index) : 0;
+}
+
+callbackLookupTableLength():number {
+  const offset = this.bb!.__offset(this.bb_pos, 28);
+  return offset ? this.bb!.__vector_len(this.bb_pos + offset) : 0;
+}
+
+callbackLookupTableArray():Uint8Array|null {
+  const offset = this.bb!.__offset(this.bb_pos, 28);
+  return offset ? new Uint8Array(this.bb!.bytes().buffer, this.bb!.bytes().byteOffset + this.bb!.__vector(this.bb_pos + offset), this.bb!.__vector_len(this.bb_pos + offset)) : null;
+}
+
 static startExecutionRequestV1(builder:flatbuffers.Builder) {
-  builder.startObject(12);
+  builder.startObject(13);
 }
 
 static addTip(builder:flatbuffers.Builder, tip:bigint) {
@@ -290,6 +305,22 @@ static addProverVersion(builder:flatbuffers.Builder, proverVersion:ProverVersion
   builder.addFieldInt16(11, proverVersion, ProverVersion.DEFAULT);
 }
 
+static addCallbackLookupTable(builder:flatbuffers.Builder, callbackLookupTableOffset:flatbuffers.Offset) {
+  builder.addFieldOffset(12, callbackLookupTableOffset, 0);
+}
+
+static createCallbackLookupTableVector(builder:flatbuffers.Builder, data:number[]|Uint8Array):flatbuffers.Offset {
+  builder.startVector(1, data.length, 1);
+  for (let i = data.length - 1; i >= 0; i--) {
+    builder.addInt8(data[i]!);
+  }
+  return builder.endVector();
+}
+
+static startCallbackLookupTableVector(builder:flatbuffers.Builder, numElems:number) {
+  builder.startVector(1, numElems, 1);
+}
+
 static endExecutionRequestV1(builder:flatbuffers.Builder):flatbuffers.Offset {
   const offset = builder.endObject();
   return offset;
@@ -303,7 +334,7 @@ static finishSizePrefixedExecutionRequestV1Buffer(builder:flatbuffers.Builder, o
   builder.finish(offset, undefined, true);
 }
 
-static createExecutionRequestV1(builder:flatbuffers.Builder, tip:bigint, executionIdOffset:flatbuffers.Offset, imageIdOffset:flatbuffers.Offset, callbackProgramIdOffset:flatbuffers.Offset, callbackInstructionPrefixOffset:flatbuffers.Offset, forwardOutput:boolean, verifyInputHash:boolean, inputOffset:flatbuffers.Offset, inputDigestOffset:flatbuffers.Offset, maxBlockHeight:bigint, callbackExtraAccountsOffset:flatbuffers.Offset, proverVersion:ProverVersion):flatbuffers.Offset {
+static createExecutionRequestV1(builder:flatbuffers.Builder, tip:bigint, executionIdOffset:flatbuffers.Offset, imageIdOffset:flatbuffers.Offset, callbackProgramIdOffset:flatbuffers.Offset, callbackInstructionPrefixOffset:flatbuffers.Offset, forwardOutput:boolean, verifyInputHash:boolean, inputOffset:flatbuffers.Offset, inputDigestOffset:flatbuffers.Offset, maxBlockHeight:bigint, callbackExtraAccountsOffset:flatbuffers.Offset, proverVersion:ProverVersion, callbackLookupTableOffset:flatbuffers.Offset):flatbuffers.Offset {
   ExecutionRequestV1.startExecutionRequestV1(builder);
   ExecutionRequestV1.addTip(builder, tip);
   ExecutionRequestV1.addExecutionId(builder, executionIdOffset);
@@ -317,6 +348,7 @@ static createExecutionRequestV1(builder:flatbuffers.Builder, tip:bigint, executi
   ExecutionRequestV1.addMaxBlockHeight(builder, maxBlockHeight);
   ExecutionRequestV1.addCallbackExtraAccounts(builder, callbackExtraAccountsOffset);
   ExecutionRequestV1.addProverVersion(builder, proverVersion);
+  ExecutionRequestV1.addCallbackLookupTable(builder, callbackLookupTableOffset);
   return ExecutionRequestV1.endExecutionRequestV1(builder);
 }
 
@@ -333,7 +365,8 @@ unpack(): ExecutionRequestV1T {
     this.bb!.createScalarList<number>(this.inputDigest.bind(this), this.inputDigestLength()),
     this.maxBlockHeight(),
     this.bb!.createObjList<Account, AccountT>(this.callbackExtraAccounts.bind(this), this.callbackExtraAccountsLength()),
-    this.proverVersion()
+    this.proverVersion(),
+    this.bb!.createScalarList<number>(this.callbackLookupTable.bind(this), this.callbackLookupTableLength())
   );
 }
 
@@ -351,6 +384,7 @@ unpackTo(_o: ExecutionRequestV1T): void {
   _o.maxBlockHeight = this.maxBlockHeight();
   _o.callbackExtraAccounts = this.bb!.createObjList<Account, AccountT>(this.callbackExtraAccounts.bind(this), this.callbackExtraAccountsLength());
   _o.proverVersion = this.proverVersion();
+  _o.callbackLookupTable = this.bb!.createScalarList<number>(this.callbackLookupTable.bind(this), this.callbackLookupTableLength());
 }
 }
 
@@ -367,7 +401,8 @@ constructor(
   public inputDigest: (number)[] = [],
   public maxBlockHeight: bigint = BigInt('0'),
   public callbackExtraAccounts: (AccountT)[] = [],
-  public proverVersion: ProverVersion = ProverVersion.DEFAULT
+  public proverVersion: ProverVersion = ProverVersion.DEFAULT,
+  public callbackLookupTable: (number)[] = []
 ){}
 
 
@@ -379,6 +414,7 @@ pack(builder:flatbuffers.Builder): flatbuffers.Offset {
   const input = ExecutionRequestV1.createInputVector(builder, builder.createObjectOffsetList(this.input));
   const inputDigest = ExecutionRequestV1.createInputDigestVector(builder, this.inputDigest);
   const callbackExtraAccounts = builder.createStructOffsetList(this.callbackExtraAccounts, ExecutionRequestV1.startCallbackExtraAccountsVector);
+  const callbackLookupTable = ExecutionRequestV1.createCallbackLookupTableVector(builder, this.callbackLookupTable);
 
   return ExecutionRequestV1.createExecutionRequestV1(builder,
     this.tip,
@@ -392,7 +428,8 @@ pack(builder:flatbuffers.Builder): flatbuffers.Offset {
     inputDigest,
     this.maxBlockHeight,
     callbackExtraAccounts,
-    this.proverVersion
+    this.proverVersion,
+    callbackLookupTable
   );
 }
 }
@@ -0,0 +1,11 @@
+// automatically generated by the FlatBuffers compiler, do not modify
+
+/* eslint-disable @typescript-eslint/no-unused-vars, @typescript-eslint/no-explicit-any, @typescript-eslint/no-non-null-assertion */
+
+export enum FailureReason {
+  Unknown = 0,
+  GuestPanic = 1,
+  CycleLimitExceeded = 2,
+  InputDownloadFailure = 3,
+  CompressionFailure = 4
+}
@@ -9,5 +9,9 @@ export enum ExitCode {
   Success = 0,
   VerifyError = 1,
   ProvingError = 2,
-  InputError = 3
+  InputError = 3,
+  Expired = 4,
+  GuestPanic = 5,
+  CycleLimitExceeded = 6,
+  CompressionError = 7
 }
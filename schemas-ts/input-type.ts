@@ -9,5 +9,9 @@ export enum InputType {
   PublicUrl = 4,
   Private = 5,
   PublicProof = 7,
-  PrivateLocal = 8
+  PrivateLocal = 8,
+  PublicUrlHashed = 9,
+  PublicAccounts = 10,
+  PublicTransaction = 11,
+  PublicExecutionProof = 12
 }
@@ -19,7 +19,12 @@ use solana_sdk::transaction::VersionedTransaction;
 
 use tokio::time::Instant;
 
-use bonsol_interface::bonsol_schema::{root_as_deploy_v1, root_as_execution_request_v1};
+/// Helpers for private input servers that seal inputs to the claiming node's encryption key
+pub use bonsol_encryption as encryption;
+pub use bonsol_interface::bonsol_schema::accounts_input;
+use bonsol_interface::bonsol_schema::{
+    encode_hashed_url, root_as_deploy_v1, root_as_execution_request_v1,
+};
 pub use bonsol_interface::bonsol_schema::{
     ClaimV1T, DeployV1T, ExecutionRequestV1T, ExitCode, InputT, InputType, ProgramInputType,
     StatusTypes,
@@ -332,3 +337,17 @@ impl BonsolClient {
         }
     }
 }
+
+/// Downloads the url and builds `PublicUrlHashed` input data committing to the current response body
+/// Use the result with `InputRef::url_with_hash`, provers reject the input if the body changes before proving
+pub async fn url_with_hash_input(url: &str) -> Result<Vec<u8>> {
+    let body = reqwest::get(url)
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| anyhow::anyhow!("Failed to download input: {:?}", e))?
+        .bytes()
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to download input: {:?}", e))?;
+    let body_hash = solana_sdk::hash::hash(&body).to_bytes();
+    Ok(encode_hashed_url(url, &body_hash))
+}
@@ -81,6 +81,7 @@ async fn example_sdk_test(
                     AccountMeta::new_readonly(ea2, false),
                     AccountMeta::new_readonly(ea3, false),
                 ],
+                lookup_table: None,
             }),
             None,
         )